@@ -0,0 +1,49 @@
+//! Runs the emulator core on a microcontroller with a 128x64 SSD1306 OLED 
+//! attached over I2C. The 64x32 CHIP-8 display fills the panel exactly at a 
+//! scale of 2.
+//!
+//! This is built as a library so it stays independent of any particular board
+//! support crate: call `run` from your firmware's entry point with the board's
+//! I2C bus and delay provider. The firmware must also provide a global 
+//! allocator, since the core needs `alloc`.
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use chip8::{cpu::{Cpu, CpuError}, embedded};
+use embedded_hal::{delay::DelayNs, i2c::I2c};
+use ssd1306::{
+    mode::DisplayConfig, prelude::DisplayRotation, size::DisplaySize128x64, 
+    I2CDisplayInterface, Ssd1306
+};
+use embedded_graphics_core::pixelcolor::BinaryColor;
+
+const FRAME_MICROS: u32 = 16_667;
+
+pub fn run<I: I2c, D: DelayNs>(i2c: I, mut delay: D, rom: &[u8]) -> CpuError {
+    let interface = I2CDisplayInterface::new(i2c);
+    let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+    // There is nothing to report a display error to, so draw on regardless.
+    let _ = display.init();
+
+    let mut cpu = match Cpu::from_program(Vec::from(rom)) {
+        Ok(cpu) => cpu,
+        Err(e) => return e
+    };
+
+    loop {
+        let frame = match cpu.run_frame() {
+            Ok(frame) => frame,
+            Err(e) => return e
+        };
+
+        if frame.drawn {
+            let _ = embedded::present(cpu.screen(), &mut display, 2, BinaryColor::On, BinaryColor::Off);
+            let _ = display.flush();
+        }
+
+        delay.delay_us(FRAME_MICROS);
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * The C API for the chip8 emulator core. Build the library with
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * and link against target/release/libchip8.so (chip8.dll, libchip8.dylib).
+ *
+ * Functions that can fail return 0 on success, or the exit code of the
+ * error's kind (10 for a stack overflow through 24 for a script error; see
+ * `chip8 --help`), with its message available from chip8_last_error.
+ */
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Bumped whenever a function's signature or meaning changes. */
+#define CHIP8_ABI_VERSION 1
+
+/* Returned by chip8_step and chip8_run_frame when the display changed. */
+#define CHIP8_DREW (-1)
+
+typedef struct Chip8 Chip8;
+
+uint32_t chip8_abi_version(void);
+
+/* A machine with no program loaded, to be freed with chip8_destroy. */
+Chip8 *chip8_create(void);
+void chip8_destroy(Chip8 *chip8);
+
+/*
+ * Load a ROM and start running it from the beginning. rom may be NULL only
+ * if len is 0.
+ */
+int chip8_load(Chip8 *chip8, const uint8_t *rom, size_t len);
+/* Like chip8_load, reading the ROM, which may be zipped, from a UTF-8 path. */
+int chip8_load_file(Chip8 *chip8, const char *path);
+
+/* Run one instruction. */
+int chip8_step(Chip8 *chip8);
+/* Run a 60th of a second's worth of instructions and tick the timers. */
+int chip8_run_frame(Chip8 *chip8);
+
+/*
+ * The display, row by row, one byte per pixel that's 1 where it's lit, with
+ * its size written to width and height unless they're NULL. The buffer stays
+ * valid until the next call to chip8_framebuffer or chip8_destroy.
+ */
+const uint8_t *chip8_framebuffer(Chip8 *chip8, size_t *width, size_t *height);
+
+/* Press (pressed non-zero) or release key 0 through 0xF. */
+void chip8_set_key(Chip8 *chip8, uint8_t key, int pressed);
+/* Seed RND, for runs that can be repeated. */
+void chip8_set_seed(Chip8 *chip8, uint64_t seed);
+
+/*
+ * Write the machine state to buf if it fits in cap bytes. Returns the
+ * state's size, so buf can be NULL to find how big it needs to be, or 0 on an
+ * error.
+ */
+size_t chip8_save_state(Chip8 *chip8, uint8_t *buf, size_t cap);
+/*
+ * Restore a state from chip8_save_state of the same ROM. state may be NULL
+ * only if len is 0, which fails as not being a state.
+ */
+int chip8_load_state(Chip8 *chip8, const uint8_t *state, size_t len);
+
+/*
+ * The message for the last error, or "". It stays valid until the next call
+ * that fails or chip8_destroy.
+ */
+const char *chip8_last_error(const Chip8 *chip8);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -1,14 +1,47 @@
-use std::ops::{Add, AddAssign, Index, Sub};
-use std::fmt::{Display, Formatter, Debug};
+use core::ops::{Add, AddAssign, Index, Sub};
+use core::fmt::{Display, Formatter, Debug};
+use alloc::{format, string::String};
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Address(pub u16);
 
 impl Address {
+    /// Mask for the classic 12-bit address space.
     pub const MASK: u16 = 0xfff;
 
+    /// `self + off`, wrapping around within the 12-bit address space.
     pub fn offset(&self, off: u16) -> Address {
-        Address(self.0 + off)
+        self.wrapping_add(off, Self::MASK)
+    }
+
+    /// `self + off`, wrapping around within the address space covered by 
+    /// `mask`, e.g. `Address::MASK` or `0xffff` for XO-CHIP.
+    pub fn wrapping_add(self, off: u16, mask: u16) -> Address {
+        Address(self.0.wrapping_add(off) & mask)
+    }
+
+    /// `self - off`, wrapping around within the address space covered by `mask`.
+    pub fn wrapping_sub(self, off: u16, mask: u16) -> Address {
+        Address(self.0.wrapping_sub(off) & mask)
+    }
+
+    /// `self + off`, failing if the result falls outside the address space 
+    /// covered by `mask`.
+    pub fn checked_add(self, off: u16, mask: u16) -> Result<Address, InvalidAddress> {
+        match self.0.checked_add(off) {
+            Some(addr) if addr <= mask => Ok(Address(addr)),
+            _ => Err(InvalidAddress(format!("Invalid Address: {self} + {off:#x} is outside of the address space")))
+        }
+    }
+
+    /// `self - off`, failing if the result falls outside the address space 
+    /// covered by `mask`.
+    pub fn checked_sub(self, off: u16, mask: u16) -> Result<Address, InvalidAddress> {
+        match self.0.checked_sub(off) {
+            Some(addr) if addr <= mask => Ok(Address(addr)),
+            _ => Err(InvalidAddress(format!("Invalid Address: {self} - {off:#x} is outside of the address space")))
+        }
     }
 }
 
@@ -48,32 +81,49 @@ impl Index<Address> for [u8] {
 
 impl Add for Address {
     type Output = Address;
-    fn add(self, decrement: Address) -> Self::Output { 
-        Address(self.0 + decrement.0)
+    fn add(self, increment: Address) -> Self::Output { 
+        self.offset(increment.0)
     }
 }
 
 impl Sub for Address {
     type Output = Address;
     fn sub(self, decrement: Address) -> Self::Output { 
-        Address(self.0 - decrement.0)
+        self.wrapping_sub(decrement.0, Self::MASK)
     }
 }
 
 impl AddAssign for Address {
     fn add_assign(&mut self, increment: Address) { 
-        self.0 += increment.0;
+        *self = *self + increment;
     }
 }
 
 impl Display for Address {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> { 
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> { 
         write!(f, "0x{:x}", self.0)
     }
 }
 
 impl Debug for Address {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> { 
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> { 
         write!(f, "{self}")
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapping_and_checked() {
+        assert_eq!(Address(0xffe) + Address(4), Address(0x002));
+        assert_eq!(Address(0x001) - Address(2), Address(0xfff));
+        assert_eq!(Address(0xffe).wrapping_add(4, 0xffff), Address(0x1002));
+        assert_eq!(Address(0xffff).wrapping_add(1, 0xffff), Address(0));
+
+        assert!(Address(0xffe).checked_add(1, Address::MASK).is_ok());
+        assert!(Address(0xffe).checked_add(2, Address::MASK).is_err());
+        assert!(Address(0xffff).checked_add(1, 0xffff).is_err());
+        assert!(Address(0x001).checked_sub(2, Address::MASK).is_err());
+    }
+}
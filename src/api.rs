@@ -0,0 +1,299 @@
+use crate::{emulator::{Command, Inspection}, screenshot};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write}, net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Sender}, thread, time::Duration
+};
+
+/// How long a request waits for the emulation thread to answer, which it
+/// does between frames.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+/// The largest request body taken, comfortably more than any ROM.
+const MAX_BODY: usize = 1 << 16;
+/// The longest request line or header taken.
+const MAX_LINE: usize = 8 << 10;
+/// The most headers taken.
+const MAX_HEADERS: usize = 64;
+/// How long a client may take to send its request or read the response
+/// before it's dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Answer HTTP requests on `addr`, e.g. `127.0.0.1:8000`, each from a thread
+/// of its own, by sending `commands` to the emulation thread:
+///
+/// - `GET /registers`: the registers, and whether the machine is paused, as
+///   JSON.
+/// - `GET /memory?start=0x200&len=16`: memory, all of it by default, as raw
+///   bytes.
+/// - `GET /screen.png?scale=10`: the display as a PNG.
+/// - `POST /pause`, `/resume`, `/step`, and `/reset`; `/step` runs one
+///   instruction, pausing first if running.
+/// - `POST /keys/5/press` and `/keys/5/release`.
+/// - `POST /rom` with the ROM as the body, to run it from the beginning.
+///
+/// The `POST`s answer with the registers as they are afterwards. Returns the
+/// address listened on, which has the port chosen if `addr`'s is 0.
+pub fn serve<A: ToSocketAddrs>(addr: A, commands: Sender<Command>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(Result::ok) {
+            let commands = commands.clone();
+            thread::spawn(move || {
+                if let Err(e) = respond(stream, &commands) {
+                    tracing::warn!("Failed to answer an API request: {e}");
+                }
+            });
+        }
+    });
+    Ok(local)
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    body: Vec<u8>
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>
+}
+
+impl Response {
+    fn text(status: &'static str, text: impl Into<String>) -> Self {
+        Self { status, content_type: "text/plain; charset=utf-8", body: (text.into() + "\n").into_bytes() }
+    }
+
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self { status: "200 OK", content_type, body }
+    }
+}
+
+fn respond(stream: TcpStream, commands: &Sender<Command>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let response = match read_request(&mut reader)? {
+        Some(request) => route(&request, commands),
+        None => Response::text("400 Bad Request", "Malformed request")
+    };
+    let mut stream = &stream;
+    write!(
+        stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status, response.content_type, response.body.len()
+    )?;
+    stream.write_all(&response.body)
+}
+
+/// Read a request line, headers, and body, or `None` if they don't parse or
+/// are too long.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if !read_line(reader, &mut line)? {
+        return Ok(None);
+    }
+    let (method, target) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+        [method, target, _] => (method.to_string(), target),
+        _ => return Ok(None)
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    let mut len = 0;
+    for headers in 0.. {
+        if headers == MAX_HEADERS || !read_line(reader, &mut line)? {
+            return Ok(None);
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                match value.trim().parse() {
+                    Ok(n) if n <= MAX_BODY => len = n,
+                    _ => return Ok(None)
+                }
+            }
+        }
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    Ok(Some(Request { method, path, query, body }))
+}
+
+/// Read a line into `line` in place of what it held, returning false if it
+/// runs past `MAX_LINE` bytes.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<bool> {
+    line.clear();
+    let len = Read::take(reader, MAX_LINE as u64).read_line(line)?;
+    Ok(len < MAX_LINE || line.ends_with('\n'))
+}
+
+fn route(request: &Request, commands: &Sender<Command>) -> Response {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let control = |command| {
+        let _ = commands.send(command);
+        inspect(commands).map_or_else(|e| e, |machine| registers(&machine))
+    };
+
+    match (request.method.as_str(), &segments[..]) {
+        ("GET", ["registers"]) => inspect(commands).map_or_else(|e| e, |machine| registers(&machine)),
+        ("GET", ["memory"]) => {
+            let (Some(start), Some(len)) = (param(&request.query, "start"), param(&request.query, "len")) else {
+                return Response::text("400 Bad Request", "start and len must be numbers, e.g. 0x200 or 16");
+            };
+            inspect(commands).map_or_else(|e| e, |machine| {
+                let start = start.unwrap_or(0).min(machine.memory.len());
+                let end = len.map_or(machine.memory.len(), |len| start.saturating_add(len).min(machine.memory.len()));
+                Response::ok("application/octet-stream", machine.memory[start..end].to_vec())
+            })
+        },
+        ("GET", ["screen.png"]) => {
+            let Some(scale) = param(&request.query, "scale") else {
+                return Response::text("400 Bad Request", "scale must be a number");
+            };
+            inspect(commands).map_or_else(|e| e, |machine| {
+                let scale = scale.map_or(1, |scale| scale.clamp(1, 32) as u32);
+                let mut png = Vec::new();
+                match screenshot::write(&machine.screen, None, scale, &mut png) {
+                    Ok(()) => Response::ok("image/png", png),
+                    Err(e) => Response::text("500 Internal Server Error", e.to_string())
+                }
+            })
+        },
+        ("POST", ["pause"]) => control(Command::Pause),
+        ("POST", ["resume"]) => control(Command::Resume),
+        ("POST", ["step"]) => control(Command::Step),
+        ("POST", ["reset"]) => control(Command::Reset),
+        ("POST", ["keys", key, action @ ("press" | "release")]) => match number(key) {
+            Some(key @ 0..=0xF) => {
+                let key = key as u8;
+                control(if *action == "press" { Command::KeyDown(key) } else { Command::KeyUp(key) })
+            },
+            _ => Response::text("404 Not Found", format!("No key {key}; keys are 0 through 0xf"))
+        },
+        ("POST", ["rom"]) if request.body.is_empty() => Response::text("400 Bad Request", "Send the ROM as the body"),
+        ("POST", ["rom"]) => control(Command::LoadProgram(request.body.clone())),
+        (_, ["registers" | "memory" | "screen.png"]) => Response::text("405 Method Not Allowed", "Use GET"),
+        (_, ["pause" | "resume" | "step" | "reset" | "rom"] | ["keys", _, _]) => {
+            Response::text("405 Method Not Allowed", "Use POST")
+        },
+        _ => Response::text(
+            "404 Not Found",
+            "Not found; try GET /registers, /memory, or /screen.png, or POST /pause, /resume, /step, /reset, \
+             /keys/<key>/press, /keys/<key>/release, or /rom"
+        )
+    }
+}
+
+/// Ask the emulation thread for the machine, or the response to send if it
+/// doesn't answer.
+fn inspect(commands: &Sender<Command>) -> Result<Inspection, Response> {
+    let (reply, machine) = mpsc::channel();
+    let _ = commands.send(Command::Inspect(reply));
+    machine.recv_timeout(REPLY_TIMEOUT)
+        .map_err(|_| Response::text("503 Service Unavailable", "The emulator has stopped"))
+}
+
+fn registers(machine: &Inspection) -> Response {
+    let registers = &machine.registers;
+    let json = serde_json::json!({
+        "v": registers.v,
+        "i": registers.i.0,
+        "pc": registers.pc.0,
+        "dt": registers.dt,
+        "st": registers.st,
+        "paused": machine.paused
+    });
+    Response::ok("application/json", format!("{json}\n").into_bytes())
+}
+
+/// The query parameter `name`: `Some(None)` if it's absent, and `None` if it
+/// isn't a number.
+fn param(query: &str, name: &str) -> Option<Option<usize>> {
+    match query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('=')) {
+        Some(value) => number(value).map(Some),
+        None => Some(None)
+    }
+}
+
+/// A number in decimal or `0x` hex.
+fn number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cpu::Cpu, emulator::Emulator, replay::Script};
+
+    #[test]
+    fn test_api() {
+        // Count up in V0 forever.
+        let cpu = Cpu::with_program(&[0x7001, 0x1200]).unwrap();
+        let core = std::env::temp_dir().join(format!("chip8-api-core-{}", std::process::id()));
+        let emulator = Emulator::spawn(cpu, core.clone(), Script::default(), None);
+        let addr = serve("127.0.0.1:0", emulator.commands()).unwrap();
+
+        // A client that never sends its request doesn't hold up the others.
+        let _idle = TcpStream::connect(addr).unwrap();
+
+        let request = |method: &str, path: &str, body: &[u8]| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n", body.len()).unwrap();
+            stream.write_all(body).unwrap();
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+            let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+            (String::from_utf8_lossy(&response[..split]).into_owned(), response[split + 4..].to_vec())
+        };
+        let json = |(head, body): (String, Vec<u8>)| {
+            assert!(head.starts_with("HTTP/1.1 200 OK"), "{head}");
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let paused = json(request("POST", "/pause", b""));
+        assert_eq!(paused["paused"], true);
+        let stepped = json(request("POST", "/step", b""));
+        assert_eq!(stepped["pc"].as_u64().unwrap(), if paused["pc"] == 0x200 { 0x202 } else { 0x200 });
+
+        let (head, memory) = request("GET", "/memory?start=0x200&len=4", b"");
+        assert!(head.contains("application/octet-stream"));
+        assert_eq!(memory, [0x70, 0x01, 0x12, 0x00]);
+        let (head, png) = request("GET", "/screen.png?scale=2", b"");
+        assert!(head.contains("image/png") && png.starts_with(b"\x89PNG"));
+
+        json(request("POST", "/keys/0xa/press", b""));
+        assert!(request("POST", "/keys/16/press", b"").0.starts_with("HTTP/1.1 404"));
+        assert!(request("GET", "/step", b"").0.starts_with("HTTP/1.1 405"));
+
+        // Lines and headers that go on too long, sent without anything after
+        // so the server reads them all before closing.
+        let malformed = |request: String| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 400"), "{response}");
+        };
+        malformed(format!("GET /{}", "a".repeat(MAX_LINE - 5)));
+        malformed(format!("GET /registers HTTP/1.1\r\n{}", "X-Padding: 1\r\n".repeat(MAX_HEADERS)));
+
+        // Load V0 with 7, then wait.
+        let loaded = json(request("POST", "/rom", &[0x60, 0x07, 0x12, 0x02]));
+        assert_eq!((loaded["pc"].as_u64(), loaded["v"][0].as_u64()), (Some(0x200), Some(0)));
+        let stepped = json(request("POST", "/step", b""));
+        assert_eq!(stepped["v"][0], 7);
+
+        // A step that faults stops the emulator, leaving a core dump.
+        json(request("POST", "/rom", &[0xFF, 0xFF]));
+        assert!(request("POST", "/step", b"").0.starts_with("HTTP/1.1 503"));
+        assert!(std::fs::remove_file(&core).is_ok());
+        emulator.finish();
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{address::Address, cpu::Cpu, register::VRegister};
+use core::{fmt::{self, Display, Formatter}, str::FromStr};
+
+/// A check on the machine state at the end of a run, e.g. `V3 == 0x1F` or
+/// `mem[0x300] != 7`, for testing ROMs and the emulator from scripts.
+///
+/// The left side is a V register, `I`, `PC`, `DT`, `ST`, or `mem[ADDRESS]`
+/// for a byte of memory; the operator is one of `==`, `!=`, `<`, `<=`, `>`,
+/// or `>=`; numbers are decimal or `0x` hexadecimal. Case and spaces don't
+/// matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assertion {
+    target: Target,
+    operator: Operator,
+    value: u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    V(VRegister),
+    I,
+    Pc,
+    Dt,
+    St,
+    Memory(Address)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual
+}
+
+impl Assertion {
+    /// Check the assertion against `cpu`, describing the actual value if it
+    /// doesn't hold.
+    pub fn check(&self, cpu: &Cpu) -> Result<(), String> {
+        let (dt, st) = cpu.timers();
+        let actual = match self.target {
+            Target::V(reg) => cpu.registers()[reg as usize] as u16,
+            Target::I => cpu.i().0,
+            Target::Pc => cpu.pc().0,
+            Target::Dt => dt as u16,
+            Target::St => st as u16,
+            Target::Memory(addr) => match cpu.memory.get_byte(addr) {
+                Ok(byte) => byte as u16,
+                Err(_) => return Err(format!("{self}: {addr} is outside of memory"))
+            }
+        };
+
+        let holds = match self.operator {
+            Operator::Equal => actual == self.value,
+            Operator::NotEqual => actual != self.value,
+            Operator::Less => actual < self.value,
+            Operator::LessOrEqual => actual <= self.value,
+            Operator::Greater => actual > self.value,
+            Operator::GreaterOrEqual => actual >= self.value
+        };
+        if holds {
+            Ok(())
+        } else {
+            Err(format!("{self}: {} is {actual:#x}", self.target))
+        }
+    }
+}
+
+impl FromStr for Assertion {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+        // Two-character operators first, so `<=` isn't read as `<`.
+        let operators = [
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessOrEqual),
+            (">=", Operator::GreaterOrEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater)
+        ];
+        let (lhs, operator, rhs) = operators.iter()
+            .find_map(|&(symbol, operator)| s.split_once(symbol).map(|(lhs, rhs)| (lhs, operator, rhs)))
+            .ok_or(())?;
+
+        let number = |s: &str| match s.strip_prefix("0X") {
+            Some(hex) => u16::from_str_radix(hex, 16).map_err(|_| ()),
+            None => s.parse().map_err(|_| ())
+        };
+        let target = match lhs {
+            "I" => Target::I,
+            "PC" => Target::Pc,
+            "DT" => Target::Dt,
+            "ST" => Target::St,
+            reg if reg.len() == 2 && reg.starts_with('V') => {
+                let n = u8::from_str_radix(&reg[1..], 16).map_err(|_| ())?;
+                Target::V(n.try_into().map_err(|_| ())?)
+            },
+            mem => {
+                let addr = mem.strip_prefix("MEM[").and_then(|m| m.strip_suffix(']')).ok_or(())?;
+                Target::Memory(Address(number(addr)?))
+            }
+        };
+
+        Ok(Self { target, operator, value: number(rhs)? })
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V(reg) => write!(f, "{reg}"),
+            Self::I => write!(f, "I"),
+            Self::Pc => write!(f, "PC"),
+            Self::Dt => write!(f, "DT"),
+            Self::St => write!(f, "ST"),
+            Self::Memory(addr) => write!(f, "mem[{addr}]")
+        }
+    }
+}
+
+impl Display for Assertion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let operator = match self.operator {
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">="
+        };
+        write!(f, "{} {operator} {:#x}", self.target, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assertions() {
+        // `LD V3, 0x1F`, `LD I, 0x300`, `LD [I], V3`.
+        let mut cpu = Cpu::with_program(&[0x631F, 0xA300, 0xF355]).unwrap();
+        (0..3).for_each(|_| { cpu.step().unwrap(); });
+
+        let check = |s: &str| s.parse::<Assertion>().unwrap().check(&cpu);
+        assert_eq!(check("V3==0x1F"), Ok(()));
+        assert_eq!(check("mem[0x303] == 31"), Ok(()));
+        assert_eq!(check("i >= 768"), Ok(()));
+        assert_eq!(check("pc<0x206"), Err("PC < 0x206: PC is 0x206".to_string()));
+        assert_eq!(check("V0 != 0"), Err("V0 != 0x0: V0 is 0x0".to_string()));
+        assert!(check("mem[0xFFFF] == 0").unwrap_err().contains("outside of memory"));
+
+        assert!("V3 = 1".parse::<Assertion>().is_err());
+        assert!("VG == 1".parse::<Assertion>().is_err());
+        assert!("mem[0x300 == 1".parse::<Assertion>().is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use alloc::vec::Vec;
+use crate::{address::Address, isa::Instruction, register::VRegister};
+use core::str::FromStr;
+
+/// Where a boot program is loaded and starts, after the fonts and before the
+/// usual load address.
+pub const BOOT_ADDRESS: Address = Address(0x100);
+/// The letters of the splash, 4x5 like the digit sprites.
+const LOGO: [[u8; 5]; 6] = [
+    [0xF0, 0x80, 0x80, 0x80, 0xF0],
+    [0x90, 0x90, 0xF0, 0x90, 0x90],
+    [0xE0, 0x40, 0x40, 0x40, 0xE0],
+    [0xF0, 0x90, 0xF0, 0x80, 0x80],
+    [0x00, 0x00, 0xF0, 0x00, 0x00],
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0]
+];
+/// How long the splash is shown, in frames.
+const SPLASH_FRAMES: u8 = 60;
+
+/// What runs before the ROM.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Boot {
+    /// Start the ROM straight away.
+    #[default]
+    None,
+    /// Show `CHIP-8` for a second, then clear the display and start the ROM.
+    Splash,
+    /// A program of one's own, loaded at `BOOT_ADDRESS`, which jumps to the
+    /// ROM when it's done.
+    #[cfg(feature = "std")]
+    Program(std::path::PathBuf)
+}
+
+impl FromStr for Boot {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "splash" => Ok(Self::Splash),
+            #[cfg(feature = "std")]
+            "" => Err(()),
+            #[cfg(feature = "std")]
+            path => Ok(Self::Program(path.into())),
+            #[cfg(not(feature = "std"))]
+            _ => Err(())
+        }
+    }
+}
+
+/// The built-in splash program, loaded at `BOOT_ADDRESS`, which shows the
+/// logo in the middle of a display `rows` tall, waits a second, and jumps to
+/// `start` with the display, registers, and `I` cleared as the ROM expects.
+pub fn splash(start: Address, rows: usize) -> Vec<u8> {
+    use {Instruction::*, VRegister::*};
+
+    let width = 5 * LOGO.len() as u8;
+    let x = (64 - width) / 2;
+    // The logo comes after the 20 instructions.
+    let logo = BOOT_ADDRESS.wrapping_add(40, u16::MAX);
+    let code = [
+        LoadImm(V0, x),
+        LoadImm(V1, (rows.saturating_sub(5) / 2) as u8),
+        LoadImm(V2, 5),
+        LoadI(logo),
+        Draw(V0, V1, 5),
+        AddImm(V0, 5),
+        AddI(V2),
+        SkipIfEqualImm(V0, x + width),
+        Jump(BOOT_ADDRESS.wrapping_add(8, u16::MAX)),
+        LoadImm(V0, SPLASH_FRAMES),
+        StoreDT(V0),
+        LoadDT(V0),
+        SkipIfEqualImm(V0, 0),
+        Jump(BOOT_ADDRESS.wrapping_add(22, u16::MAX)),
+        ClearScreen,
+        LoadImm(V1, 0),
+        LoadImm(V2, 0),
+        LoadImm(VF, 0),
+        LoadI(Address(0)),
+        Jump(start)
+    ];
+
+    let mut program: Vec<u8> = code.iter()
+        .flat_map(|instruction| instruction.encode().unwrap_or_default().to_be_bytes())
+        .collect();
+    program.extend(LOGO.iter().flatten());
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Cpu, PC_START};
+    use alloc::vec;
+
+    #[test]
+    fn test_splash() {
+        // `LD V3, 1`, then counting up in V4 forever.
+        let mut cpu = Cpu::from_program(vec![0x63, 0x01, 0x74, 0x01, 0x12, 0x02]).unwrap();
+        cpu.set_boot(Some(splash(PC_START, 32))).unwrap();
+        assert_eq!(cpu.pc(), BOOT_ADDRESS);
+
+        (0..5).for_each(|_| { cpu.run_frame().unwrap(); });
+        // The `-` and the `8` after it.
+        assert!(cpu.screen().pixel(37, 15) && !cpu.screen().pixel(37, 14) && cpu.screen().pixel(42, 14));
+        (0..SPLASH_FRAMES).for_each(|_| { cpu.run_frame().unwrap(); });
+        assert_eq!(cpu.registers()[..4], [0, 0, 0, 1]);
+        assert!(cpu.registers()[4] > 0 && cpu.i() == Address(0));
+        assert!(cpu.screen().rows().iter().all(|&row| row == 0));
+
+        // It has to fit below the ROM.
+        assert!(cpu.set_load_address(Address(0x120)).is_err());
+        cpu.set_boot(None).unwrap();
+        assert_eq!(cpu.pc(), PC_START);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("splash".parse(), Ok(Boot::Splash));
+        #[cfg(feature = "std")]
+        assert_eq!("boot.ch8".parse(), Ok(Boot::Program("boot.ch8".into())));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{platform::Platform, rom::{self, PlayHistory}, romdb};
+use std::{fmt::Write, fs, io, path::{Path, PathBuf}, time::SystemTime};
+
+/// Number of ROMs listed at once; the list scrolls to keep the selection in
+/// view.
+const VISIBLE: usize = 30;
+/// Width of the file name column.
+const NAME_WIDTH: usize = 32;
+
+/// A ROM found by the browser, with what is known about it.
+#[derive(Debug, Clone)]
+pub struct RomEntry {
+    pub path: PathBuf,
+    /// The title of a well-known ROM, from `romdb`.
+    pub title: Option<String>,
+    pub size: u64,
+    pub platform: Platform,
+    pub last_played: Option<SystemTime>
+}
+
+/// A scrollable list of ROMs, for picking one to launch.
+pub struct Browser {
+    title: String,
+    /// Shown in place of the list when there is nothing in it.
+    empty: &'static str,
+    entries: Vec<RomEntry>,
+    selected: usize
+}
+
+impl Browser {
+    /// List the ROMs in `dir`, detecting each one's platform from its contents.
+    pub fn scan(dir: &Path, history: &PlayHistory) -> io::Result<Self> {
+        let entries = rom::list(dir)?
+            .into_iter()
+            .filter_map(|path| {
+                let last_played = history.last_played(&path);
+                entry(path, last_played)
+            })
+            .collect();
+
+        Ok(Self {
+            title: format!("ROMs in {}", dir.display()),
+            empty: "(no .ch8 or .sc8 files)",
+            entries,
+            selected: 0
+        })
+    }
+
+    /// List the ROMs in `history` that are still on disk, most recently
+    /// played first, to relaunch one quickly.
+    pub fn recent(history: &PlayHistory) -> Self {
+        let entries = history.recent()
+            .iter()
+            .filter_map(|played| entry(played.path.clone(), Some(played.time)))
+            .collect();
+
+        Self { title: "Recently played".to_string(), empty: "(nothing played yet)", entries, selected: 0 }
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1));
+    }
+
+    /// Select the ROM at `path`, if it is listed, e.g. the one just played.
+    pub fn select(&mut self, path: &Path) {
+        if let Some(i) = self.entries.iter().position(|e| e.path == path) {
+            self.selected = i;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&RomEntry> {
+        self.entries.get(self.selected)
+    }
+
+    /// Replace the contents of `out` with the list, clearing the terminal
+    /// first. Lines end in `\r\n` since the terminal is in raw mode.
+    pub fn render_into(&self, out: &mut String) {
+        out.clear();
+        let _ = write!(out, "\x1B[2J\x1B[H{}\r\n\r\n", self.title);
+        let _ = write!(out, "  {:<NAME_WIDTH$} {:>6}  {:<8} LAST PLAYED\r\n", "NAME", "SIZE", "PLATFORM");
+
+        if self.entries.is_empty() {
+            let _ = write!(out, "  {}\r\n", self.empty);
+        }
+
+        let first = self.selected.saturating_sub(VISIBLE - 1);
+        for (i, entry) in self.entries.iter().enumerate().skip(first).take(VISIBLE) {
+            let cursor = if i == self.selected { '>' } else { ' ' };
+            let name = match &entry.title {
+                Some(title) => title.into(),
+                None => entry.path.file_name().unwrap_or_default().to_string_lossy()
+            };
+            let name: String = name.chars().take(NAME_WIDTH).collect();
+            let played = entry.last_played.map_or_else(|| "never".to_string(), ago);
+            let _ = write!(
+                out, "{cursor} {name:<NAME_WIDTH$} {:>6}  {:<8} {played}\r\n",
+                entry.size, entry.platform.to_string()
+            );
+        }
+
+        out.push_str("\r\n↑/↓ select, Enter play, Esc quit\r\n");
+    }
+}
+
+/// Read the ROM at `path` to describe it, or `None` if it can't be read.
+fn entry(path: PathBuf, last_played: Option<SystemTime>) -> Option<RomEntry> {
+    let program = fs::read(&path).ok()?;
+    let known = romdb::lookup(&program);
+    Some(RomEntry {
+        title: known.map(|known| known.title.clone()),
+        size: program.len() as u64,
+        platform: known.and_then(|known| known.settings.platform).unwrap_or_else(|| Platform::detect(&program)),
+        last_played,
+        path
+    })
+}
+
+/// How long ago `time` was, roughly, e.g. "5 minutes ago".
+fn ago(time: SystemTime) -> String {
+    let secs = time.elapsed().unwrap_or_default().as_secs();
+    let (n, unit) = match secs {
+        0..60 => return "just now".to_string(),
+        60..3600 => (secs / 60, "minute"),
+        3600..86400 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day")
+    };
+
+    format!("{n} {unit}{} ago", if n == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_browser() {
+        let dir = std::env::temp_dir().join(format!("chip8-browse-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.ch8"), [0x00, 0xE0]).unwrap();
+        fs::write(dir.join("b.ch8"), [0xF0, 0x00, 0x12, 0x34]).unwrap();
+        fs::write(dir.join("notes.txt"), "not a rom").unwrap();
+        fs::write(dir.join("c.ch8"), include_bytes!("../rom/ibm.ch8")).unwrap();
+
+        let mut history = PlayHistory::load(dir.join("history"));
+        history.record(&dir.join("a.ch8"), &[0x00, 0xE0]).unwrap();
+        history.record(&dir.join("b.ch8"), &[0xF0, 0x00, 0x12, 0x34]).unwrap();
+        let history = PlayHistory::load(dir.join("history"));
+        assert_eq!(history.recent()[0].hash, rom::hash(&[0xF0, 0x00, 0x12, 0x34]));
+
+        let mut browser = Browser::scan(&dir, &history).unwrap();
+        assert_eq!(browser.entries.len(), 3);
+        assert_eq!(browser.entries[2].title.as_deref(), Some("IBM Logo"));
+        assert_eq!(browser.selected().unwrap().platform, Platform::Chip8);
+        browser.down();
+        let entry = browser.selected().unwrap();
+        assert_eq!((entry.size, entry.platform), (4, Platform::XoChip));
+        assert!(entry.last_played.is_some());
+
+        let mut out = String::new();
+        browser.render_into(&mut out);
+        assert!(out.contains("> b.ch8") && out.contains("just now") && out.contains("  IBM Logo "));
+
+        fs::remove_file(dir.join("b.ch8")).unwrap();
+        let recent = Browser::recent(&history);
+        assert_eq!(recent.entries.len(), 1);
+        assert_eq!(recent.selected().unwrap().path, fs::canonicalize(dir.join("a.ch8")).unwrap());
+        assert_eq!(ago(SystemTime::now() - Duration::from_secs(7200)), "2 hours ago");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
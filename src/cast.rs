@@ -0,0 +1,64 @@
+use std::{
+    fs::File, io::{self, BufWriter, Write}, path::Path, time::{Duration, Instant, SystemTime, UNIX_EPOCH}
+};
+
+/// Writes what the frontend draws to an asciinema v2 recording, so a session
+/// can be replayed in a terminal with `asciinema play` or embedded in a web
+/// page with its player.
+///
+/// The first line is a header with the terminal's size, and every frame after
+/// it a line of `[seconds since the start, "o", output]`.
+pub struct Cast<W: Write = BufWriter<File>> {
+    out: W,
+    start: Instant
+}
+
+impl Cast {
+    /// Start a recording of a `cols` by `rows` terminal.
+    pub fn create(path: &Path, cols: u16, rows: u16) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), cols, rows)
+    }
+}
+
+impl<W: Write> Cast<W> {
+    pub fn new(mut out: W, cols: u16, rows: u16) -> io::Result<Self> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let header = serde_json::json!({ "version": 2, "width": cols, "height": rows, "timestamp": timestamp });
+        writeln!(out, "{header}")?;
+        Ok(Self { out, start: Instant::now() })
+    }
+
+    /// Record `output` as written to the terminal now.
+    pub fn record(&mut self, output: &str) -> io::Result<()> {
+        self.record_at(self.start.elapsed(), output)
+    }
+
+    /// Record `output` as written `elapsed` after the recording started.
+    pub fn record_at(&mut self, elapsed: Duration, output: &str) -> io::Result<()> {
+        let output = serde_json::Value::from(output);
+        writeln!(self.out, "[{:.6}, \"o\", {output}]", elapsed.as_secs_f64())
+    }
+
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cast() {
+        let mut cast = Cast::new(Vec::new(), 66, 35).unwrap();
+        cast.record_at(Duration::from_millis(1500), "\x1B[H█\r\n\"").unwrap();
+        let text = String::from_utf8(cast.into_inner().unwrap()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+
+        let header: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!((&header["version"], &header["width"], &header["height"]), (&2.into(), &66.into(), &35.into()));
+        assert_eq!(lines[1], r#"[1.500000, "o", "\u001b[H█\r\n\""]"#);
+        assert_eq!(lines.len(), 2);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{address::Address, register::VRegister};
+use alloc::{format, string::String};
+use core::str::FromStr;
+use serde::{de::Error, Deserialize, Deserializer};
+#[cfg(feature = "std")]
+use {crate::{config::{Config, InvalidConfig}, rom}, std::{fs, io, path::{Path, PathBuf}, vec::Vec}};
+
+/// What a cheat changes: a byte of memory or a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Memory(Address),
+    Register(VRegister)
+}
+
+impl FromStr for Target {
+    type Err = ();
+
+    /// A hex address, e.g. `0x2f0`, or a register, e.g. `v5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(reg) = s.strip_prefix(['v', 'V']) {
+            let n = u8::from_str_radix(reg, 16).map_err(|_| ())?;
+            return VRegister::try_from(n).map(Self::Register).map_err(|_| ());
+        }
+        let addr = s.strip_prefix("0x").unwrap_or(s);
+        u16::from_str_radix(addr, 16).map(|addr| Self::Memory(Address(addr))).map_err(|_| ())
+    }
+}
+
+/// A byte of memory or a register held at `value`, written back after every
+/// instruction, e.g. to keep a lives counter from running out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Freeze {
+    pub target: Target,
+    pub value: u8
+}
+
+impl FromStr for Freeze {
+    type Err = ();
+
+    /// `<target>=<value>`, with the value in decimal or `0x` hex, e.g.
+    /// `0x2f0=3` or `v5=0x09`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, value) = s.split_once('=').ok_or(())?;
+        let value = value.trim();
+        let value = match value.strip_prefix("0x") {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => value.parse()
+        }.map_err(|_| ())?;
+        Ok(Self { target: target.trim().parse()?, value })
+    }
+}
+
+/// Whether a cheat holds its value, or writes it once each time it's picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Freeze,
+    Once
+}
+
+/// A cheat listed in a cheat file, to turn on and off while playing.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cheat {
+    pub name: String,
+    /// A hex address or a register, e.g. `"0x2f0"` or `"v5"`.
+    #[serde(rename = "address", deserialize_with = "target")]
+    pub target: Target,
+    pub value: u8,
+    #[serde(rename = "type", default)]
+    pub kind: Kind
+}
+
+impl Cheat {
+    pub fn freeze(&self) -> Freeze {
+        Freeze { target: self.target, value: self.value }
+    }
+}
+
+fn target<'de, D: Deserializer<'de>>(d: D) -> Result<Target, D::Error> {
+    let s = String::deserialize(d)?;
+    s.parse().map_err(|_| D::Error::custom(format!("invalid address `{s}`, expected e.g. `0x2f0` or `v5`")))
+}
+
+/// Where the cheats for `program` are looked for by default: a file named
+/// after its hash in the `cheats` directory beside the config file, e.g.
+/// `~/.config/chip8/cheats/0123....toml`.
+#[cfg(feature = "std")]
+pub fn default_path(program: &[u8]) -> PathBuf {
+    let hash = rom::hash(program);
+    let name = format!("{}.toml", hash.trim_start_matches("sha1:"));
+    Config::default_path().with_file_name("cheats").join(name)
+}
+
+/// Read the cheats in the file at `path`. A missing file has none.
+#[cfg(feature = "std")]
+pub fn load(path: &Path) -> Result<Vec<Cheat>, InvalidConfig> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(InvalidConfig(format!("Failed to read {}: {e}", path.display())))
+    };
+
+    parse(&text).map_err(|InvalidConfig(e)| InvalidConfig(format!("{}: {e}", path.display())))
+}
+
+/// Parse a cheat file: a `[[cheat]]` table per cheat, e.g.
+///
+/// ```toml
+/// [[cheat]]
+/// name = "Infinite lives"
+/// address = "0x2f0"
+/// value = 3
+/// # `freeze` to hold the value (the default), or `once` to write it once.
+/// type = "freeze"
+/// ```
+#[cfg(feature = "std")]
+pub fn parse(text: &str) -> Result<Vec<Cheat>, InvalidConfig> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct File {
+        #[serde(default)]
+        cheat: Vec<Cheat>
+    }
+
+    toml::from_str::<File>(text).map(|file| file.cheat).map_err(|e| InvalidConfig(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+
+    #[test]
+    fn test_freeze() {
+        assert_eq!("0x2f0=3".parse(), Ok(Freeze { target: Target::Memory(Address(0x2F0)), value: 3 }));
+        assert_eq!("vA = 0x10".parse(), Ok(Freeze { target: Target::Register(VRegister::VA), value: 0x10 }));
+        assert!("v10=1".parse::<Freeze>().is_err() && "v1=256".parse::<Freeze>().is_err());
+
+        // Count up in V0 and down in V1, storing V0 as the lives at 0x300,
+        // forever.
+        let mut cpu = Cpu::with_program(&[0x7001, 0x71FF, 0xA300, 0xF055, 0x1200]).unwrap();
+        cpu.freeze("0x300=3".parse().unwrap()).unwrap();
+        cpu.freeze("v1=9".parse().unwrap()).unwrap();
+        (0..10).for_each(|_| { cpu.step().unwrap(); });
+        assert_eq!((cpu.registers()[0], cpu.registers()[1]), (2, 9));
+        assert_eq!(cpu.memory.get_byte(Address(0x300)).unwrap(), 3);
+
+        // Freezing a target again replaces the value, and it's put back as
+        // soon as it's frozen.
+        cpu.freeze("v1=4".parse().unwrap()).unwrap();
+        assert_eq!((cpu.frozen().len(), cpu.registers()[1]), (2, 4));
+        assert!(cpu.unfreeze(Target::Register(VRegister::V1)));
+        (0..2).for_each(|_| { cpu.step().unwrap(); });
+        assert_eq!(cpu.registers()[1], 3);
+        assert!(cpu.freeze("0x1000=1".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_cheat_file() {
+        let cheats = parse(r#"
+            [[cheat]]
+            name = "Infinite lives"
+            address = "0x2f0"
+            value = 3
+
+            [[cheat]]
+            name = "Max score"
+            address = "vE"
+            value = 0xFF
+            type = "once"
+        "#).unwrap();
+        assert_eq!(cheats[0].freeze(), "0x2f0=3".parse().unwrap());
+        assert_eq!((cheats[1].target, cheats[1].kind), (Target::Register(VRegister::VE), Kind::Once));
+
+        assert!(parse("[[cheat]]\nname = \"x\"\naddress = \"v16\"\nvalue = 1").is_err());
+        assert!(parse("[[cheat]]\nname = \"x\"\naddress = \"0x200\"\nvalue = 1\ntype = \"always\"").is_err());
+        let hash = rom::hash(&[0x00, 0xE0]);
+        assert!(default_path(&[0x00, 0xE0]).ends_with(format!("cheats/{}.toml", &hash["sha1:".len()..])));
+    }
+}
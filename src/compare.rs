@@ -0,0 +1,184 @@
+use crate::{
+    address::Address, cpu::{Cpu, CpuError}, register::VRegister,
+    screen::{Screen, NCOLS}
+};
+use std::fmt::{self, Display, Formatter, Write};
+
+/// Frames compared without a display when no limit is given, a minute's play.
+pub const DEFAULT_FRAMES: u64 = 60 * 60;
+
+/// Two machines run in lockstep on identical input, e.g. with different
+/// quirks or one running a patched ROM, to find where they part ways.
+pub struct Comparison {
+    left: Cpu,
+    right: Cpu,
+    /// Addresses whose bytes differed before either machine ran, e.g. where a
+    /// ROM was patched, which aren't compared.
+    patched: Vec<bool>,
+    frame: u64,
+    /// The first frame after which the machines differed, and how.
+    diverged: Option<(u64, Difference)>
+}
+
+/// The first difference found between two machines, as `(left, right)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difference {
+    Register(VRegister, u8, u8),
+    I(Address, Address),
+    Pc(Address, Address),
+    Memory(Address, u8, u8),
+    Display
+}
+
+impl Comparison {
+    pub fn new(left: Cpu, right: Cpu) -> Self {
+        let (a, b) = (left.memory.to_bytes(), right.memory.to_bytes());
+        let patched = a.iter().zip(&b).map(|(a, b)| a != b).collect();
+        Self { left, right, patched, frame: 0, diverged: None }
+    }
+
+    pub fn press_key(&mut self, key: u8) {
+        self.left.press_key(key);
+        self.right.press_key(key);
+    }
+
+    pub fn release_key(&mut self, key: u8) {
+        self.left.release_key(key);
+        self.right.release_key(key);
+    }
+
+    /// Run one frame on both machines. Returns true if this is the frame
+    /// they first differ after.
+    pub fn run_frame(&mut self) -> Result<bool, CpuError> {
+        self.left.run_frame()?;
+        self.right.run_frame()?;
+        self.frame += 1;
+
+        if self.diverged.is_some() {
+            return Ok(false);
+        }
+        self.diverged = self.find_difference().map(|difference| (self.frame, difference));
+        Ok(self.diverged.is_some())
+    }
+
+    /// The first way the machines differ, checking the registers, then
+    /// memory, then the display.
+    fn find_difference(&self) -> Option<Difference> {
+        let (left, right) = (&self.left, &self.right);
+        let mut registers = left.registers().iter().zip(right.registers()).enumerate();
+        if let Some((n, (&a, &b))) = registers.find(|(_, (a, b))| a != b) {
+            return Some(Difference::Register(VRegister::try_from(n as u8).ok()?, a, b));
+        } else if left.i() != right.i() {
+            return Some(Difference::I(left.i(), right.i()));
+        } else if left.pc() != right.pc() {
+            return Some(Difference::Pc(left.pc(), right.pc()));
+        }
+
+        let (a, b) = (left.memory.to_bytes(), right.memory.to_bytes());
+        let mut bytes = a.iter().zip(&b).zip(&self.patched).enumerate();
+        if let Some((addr, ((&a, &b), _))) = bytes.find(|(_, ((a, b), &patched))| a != b && !patched) {
+            return Some(Difference::Memory(Address(addr as u16), a, b));
+        }
+
+        (left.screen() != right.screen()).then_some(Difference::Display)
+    }
+
+    /// Number of frames run so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn diverged(&self) -> Option<u64> {
+        self.diverged.map(|(frame, _)| frame)
+    }
+
+    /// How the machines first differed.
+    pub fn difference(&self) -> Option<Difference> {
+        self.diverged.map(|(_, difference)| difference)
+    }
+
+    /// Replace the contents of `out` with both displays next to each other,
+    /// labelled `labels`, and a line saying whether they have diverged.
+    /// Pixels lit on only one side are shaded so differences stand out.
+    pub fn render_into(&self, out: &mut String, labels: [&str; 2]) {
+        let (left, right) = (self.left.screen(), self.right.screen());
+        let border = "─".repeat(NCOLS);
+
+        out.clear();
+        out.push_str("\x1B[2J\x1B[H");
+        let _ = write!(out, "┌{:─<NCOLS$}┐ ┌{:─<NCOLS$}┐\r\n", labels[0], labels[1]);
+        for y in 0..left.rows().len() {
+            out.push('│');
+            row_into(out, left, right, y);
+            out.push_str("│ │");
+            row_into(out, right, left, y);
+            out.push_str("│\r\n");
+        }
+        let _ = write!(out, "└{border}┘ └{border}┘\r\n");
+
+        match self.diverged {
+            Some((frame, difference)) => { let _ = write!(out, "frame {} | diverged at frame {frame}: {difference}\r\n", self.frame); },
+            None => { let _ = write!(out, "frame {} | identical\r\n", self.frame); }
+        }
+    }
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register(reg, a, b) => write!(f, "{reg} is {a:#04x} vs {b:#04x}"),
+            Self::I(a, b) => write!(f, "I is {a} vs {b}"),
+            Self::Pc(a, b) => write!(f, "PC is {a} vs {b}"),
+            Self::Memory(addr, a, b) => write!(f, "{addr} holds {a:#04x} vs {b:#04x}"),
+            Self::Display => write!(f, "the displays differ")
+        }
+    }
+}
+
+/// Append row `y` of `screen`, shading the pixels that differ from `other`.
+fn row_into(out: &mut String, screen: &Screen, other: &Screen, y: usize) {
+    for x in 0..NCOLS {
+        out.push(match (screen.pixel(x, y), other.pixel(x, y)) {
+            (true, true) => '█',
+            (true, false) => '▓',
+            (false, true) => '░',
+            (false, false) => ' '
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divergence() {
+        // Draw the font digit in V0 and wait for a key.
+        let program = |digit| vec![0x60, digit, 0xF0, 0x29, 0xD0, 0x05, 0xF1, 0x0A];
+        let cpu = |program| Cpu::from_program(program).unwrap();
+
+        let mut same = Comparison::new(cpu(program(0)), cpu(program(0)));
+        assert!(!same.run_frame().unwrap());
+        assert_eq!(same.diverged(), None);
+
+        let mut patched = Comparison::new(cpu(program(0)), cpu(program(1)));
+        assert!(patched.run_frame().unwrap());
+        assert!(!patched.run_frame().unwrap());
+        assert_eq!((patched.frame(), patched.diverged()), (2, Some(1)));
+        // The patched byte itself doesn't count.
+        assert_eq!(patched.difference(), Some(Difference::Register(VRegister::V0, 0, 1)));
+
+        let mut out = String::new();
+        patched.render_into(&mut out, ["quirks off", "quirks on"]);
+        assert!(out.contains("┌quirks off─") && out.contains("diverged at frame 1: V0 is 0x00 vs 0x01"));
+        assert!(out.contains('▓') && out.contains('░'));
+
+        // `LD I, 0xFFF`, `LD V1, 2`, `ADD I, V1`, then wait for a key.
+        let program = vec![0xAF, 0xFF, 0x61, 0x02, 0xF1, 0x1E, 0xF1, 0x0A];
+        let mut quirky = cpu(program.clone());
+        quirky.quirks().add_i_overflow = true;
+        let mut quirks = Comparison::new(cpu(program), quirky);
+        assert!(quirks.run_frame().unwrap());
+        assert_eq!(quirks.difference(), Some(Difference::Register(VRegister::VF, 0, 1)));
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{
+    boot::Boot, input::Keymap, memory::WriteProtection, platform::Platform, quirks::IndexOverflow, rom, romdb,
+    screen::{ColorDepth, Palette, Rgb}, cheat::Freeze
+};
+use serde::{de::Error, Deserialize, Deserializer};
+use std::{collections::HashMap, env, fs, io, path::{Path, PathBuf}, str::FromStr};
+
+/// The fastest `ips` taken, since a frame's instructions all run before the
+/// display is presented.
+pub const MAX_IPS: u32 = 1_000_000;
+
+#[derive(Debug)]
+pub struct InvalidConfig(pub String);
+
+/// Settings read from `~/.config/chip8/config.toml` or `--config`.
+/// Command-line flags take precedence over a game's settings, which take
+/// precedence over the top-level defaults:
+///
+/// ```toml
+/// ips = 1000
+/// palette = { on = "#33ff66", off = "#002200" }
+///
+/// [game."sha1:0123..."]
+/// add-i-overflow = true
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Defaults for every game.
+    pub defaults: Settings,
+    /// Overrides for specific ROMs, keyed by `rom::hash`. The right speed and
+    /// quirks differ between games, so they are applied whenever that ROM is
+    /// opened.
+    pub game: HashMap<String, Settings>
+}
+
+/// Settings that can be given on the command line, in the config file, or
+/// for a single game. Anything left out falls through to the next layer.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    /// Emulation speed in instructions per second.
+    pub ips: Option<u32>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub platform: Option<Platform>,
+    /// Where the ROM is loaded, if not the platform's usual address.
+    pub load_address: Option<u16>,
+    /// Rows in the display, if not the platform's usual number.
+    pub display_rows: Option<usize>,
+    /// A file of digit sprites to use in place of the built-in ones.
+    pub font: Option<PathBuf>,
+    /// Where the font is loaded, if not at 0.
+    pub font_address: Option<u16>,
+    /// A table of the ROM's addresses to move when it's loaded somewhere
+    /// other than 0x200, as the assembler writes them.
+    pub relocations: Option<PathBuf>,
+    /// What to run before the ROM: `none`, `splash`, or a boot program's
+    /// path.
+    #[serde(default, deserialize_with = "parsed")]
+    pub boot: Option<Boot>,
+    /// Memory and registers to hold at a value, e.g.
+    /// `freeze = ["0x2f0=3", "v5=9"]`, usually for a single game.
+    #[serde(default, deserialize_with = "parsed_list")]
+    pub freeze: Option<Vec<Freeze>>,
+    /// A cheat file to use in place of the ROM's in the cheats directory.
+    pub cheats: Option<PathBuf>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub protect: Option<WriteProtection>,
+    pub add_i_overflow: Option<bool>,
+    #[serde(default, deserialize_with = "parsed")]
+    pub index_overflow: Option<IndexOverflow>,
+    /// Start with the display scaled to fill the terminal.
+    pub fullscreen: Option<bool>,
+    /// Start with the keys pressed shown over the display.
+    pub show_keys: Option<bool>,
+    /// Pause while the terminal is in the background, in terminals that say
+    /// when it is. On unless set to false.
+    pub auto_pause: Option<bool>,
+    /// E.g. `palette = { on = "#33ff66", off = "#002200" }`.
+    #[serde(default, deserialize_with = "palette")]
+    pub palette: Option<Palette>,
+    /// Use `Palette::HIGH_CONTRAST` in place of `palette`.
+    pub high_contrast: Option<bool>,
+    /// Swap the colors of lit and unlit pixels.
+    pub invert: Option<bool>,
+    /// `truecolor`, or `256` for terminals without 24-bit color.
+    #[serde(default, deserialize_with = "parsed")]
+    pub color_depth: Option<ColorDepth>,
+    /// Hold back frames that would flash most of the display more than three
+    /// times a second, for photosensitive players.
+    pub reduce_flashing: Option<bool>,
+    /// Seed `RND` with 0 unless a seed is given, and turn off the hotkeys that
+    /// reset, rewind, reload, or change the speed, so a run with the same
+    /// input always plays out the same.
+    pub deterministic: Option<bool>,
+    /// Host keys mapped to keypad keys, e.g. `keymap = { i = 0x5, k = 0x8 }`,
+    /// on top of the usual layout.
+    #[serde(default)]
+    pub keymap: HashMap<char, u8>
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chip8/config.toml`, falling back to `~/.config`.
+    pub fn default_path() -> PathBuf {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_default()
+            .join("chip8")
+            .join("config.toml")
+    }
+
+    /// Read the configuration at `path`. A missing file is the default
+    /// configuration, but a malformed one is an error so typos aren't
+    /// silently ignored.
+    pub fn load(path: &Path) -> Result<Self, InvalidConfig> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(InvalidConfig(format!("Failed to read {}: {e}", path.display())))
+        };
+
+        Self::parse(&text).map_err(|InvalidConfig(e)| InvalidConfig(format!("{}: {e}", path.display())))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, InvalidConfig> {
+        let invalid = |e: toml::de::Error| InvalidConfig(e.to_string());
+
+        // The defaults sit at the top level next to the `game` tables.
+        let mut table: toml::Table = toml::from_str(text).map_err(invalid)?;
+        let game: HashMap<String, Settings> = match table.remove("game") {
+            Some(game) => game.try_into().map_err(invalid)?,
+            None => HashMap::new()
+        };
+        let defaults: Settings = toml::Value::Table(table).try_into().map_err(invalid)?;
+
+        defaults.check().map_err(InvalidConfig)?;
+        for (hash, settings) in &game {
+            settings.check().map_err(|e| InvalidConfig(format!("game {hash}: {e}")))?;
+        }
+
+        Ok(Self { defaults, game })
+    }
+
+    /// The settings for `program`: its game's overrides, over the settings
+    /// `romdb` has for it if it's a well-known ROM, over the defaults.
+    pub fn settings(&self, program: &[u8]) -> Settings {
+        let known = match romdb::lookup(program) {
+            Some(known) => known.settings.clone().or(&self.defaults),
+            None => self.defaults.clone()
+        };
+        match self.game.get(&rom::hash(program)) {
+            Some(game) => game.clone().or(&known),
+            None => known
+        }
+    }
+}
+
+impl Settings {
+    /// Fill in whatever these settings leave out from `base`. Key mappings
+    /// are combined, with these taking precedence.
+    pub fn or(self, base: &Settings) -> Settings {
+        let mut keymap = base.keymap.clone();
+        keymap.extend(self.keymap);
+
+        Settings {
+            ips: self.ips.or(base.ips),
+            platform: self.platform.or(base.platform),
+            load_address: self.load_address.or(base.load_address),
+            display_rows: self.display_rows.or(base.display_rows),
+            font: self.font.or_else(|| base.font.clone()),
+            font_address: self.font_address.or(base.font_address),
+            relocations: self.relocations.or_else(|| base.relocations.clone()),
+            boot: self.boot.or_else(|| base.boot.clone()),
+            freeze: self.freeze.or_else(|| base.freeze.clone()),
+            cheats: self.cheats.or_else(|| base.cheats.clone()),
+            protect: self.protect.or(base.protect),
+            add_i_overflow: self.add_i_overflow.or(base.add_i_overflow),
+            index_overflow: self.index_overflow.or(base.index_overflow),
+            fullscreen: self.fullscreen.or(base.fullscreen),
+            show_keys: self.show_keys.or(base.show_keys),
+            auto_pause: self.auto_pause.or(base.auto_pause),
+            palette: self.palette.or(base.palette),
+            high_contrast: self.high_contrast.or(base.high_contrast),
+            invert: self.invert.or(base.invert),
+            color_depth: self.color_depth.or(base.color_depth),
+            reduce_flashing: self.reduce_flashing.or(base.reduce_flashing),
+            deterministic: self.deterministic.or(base.deterministic),
+            keymap
+        }
+    }
+
+    /// The colors to draw with, after the accessibility options, or `None`
+    /// for the terminal's own.
+    pub fn colors(&self) -> Option<Palette> {
+        let palette = match self.high_contrast {
+            Some(true) => Some(Palette::HIGH_CONTRAST),
+            _ => self.palette
+        };
+        match self.invert {
+            Some(true) => Some(palette.unwrap_or(Palette::DEFAULT).inverted()),
+            _ => palette
+        }
+    }
+
+    /// The usual key layout with these mappings applied.
+    pub fn keymap(&self) -> Keymap {
+        let mut keymap = Keymap::default();
+        self.keymap.iter().for_each(|(&c, &key)| keymap.set(c, key));
+        keymap
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if let Some(ips) = self.ips.filter(|ips| !(1..=MAX_IPS).contains(ips)) {
+            return Err(format!("ips is {ips}, not from 1 to {MAX_IPS}"));
+        }
+        if let Some(rows) = self.display_rows.filter(|rows| ![32, 48, 64].contains(rows)) {
+            return Err(format!("display-rows is {rows}, not 32, 48, or 64"));
+        }
+        match self.keymap.iter().find(|(_, &key)| key > 0xF) {
+            Some((c, key)) => Err(format!("`{c}` is mapped to {key:#x}, past keypad key 0xf")),
+            None => Ok(())
+        }
+    }
+}
+
+/// Deserialize a string with the type's `FromStr`, as used for CLI flags.
+fn parsed<'de, D: Deserializer<'de>, T: FromStr>(d: D) -> Result<Option<T>, D::Error> {
+    let s = String::deserialize(d)?;
+    s.parse().map(Some).map_err(|_| D::Error::custom(format!("invalid value `{s}`")))
+}
+
+/// Deserialize a list of strings with the type's `FromStr`.
+fn parsed_list<'de, D: Deserializer<'de>, T: FromStr>(d: D) -> Result<Option<Vec<T>>, D::Error> {
+    Vec::<String>::deserialize(d)?.iter()
+        .map(|s| s.parse().map_err(|_| D::Error::custom(format!("invalid value `{s}`"))))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+fn palette<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Palette>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Colors {
+        on: String,
+        off: String
+    }
+
+    let colors = Colors::deserialize(d)?;
+    let rgb = |s: &str| s.parse::<Rgb>().map_err(|_| D::Error::custom(format!("invalid color `{s}`")));
+    Ok(Some(Palette { on: rgb(&colors.on)?, off: rgb(&colors.off)? }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_overrides() {
+        let program = [0x00, 0xE0];
+        let text = format!(r##"
+            ips = 1000
+            add-i-overflow = true
+            color-depth = "256"
+            keymap = {{ j = 0x4 }}
+
+            [game."{}"]
+            ips = 1500
+            platform = "xo-chip"
+            index-overflow = "fault"
+            freeze = ["0x2f0=3"]
+            palette = {{ on = "#33ff66", off = "#000000" }}
+            keymap = {{ i = 0x5 }}
+        "##, rom::hash(&program));
+
+        let config = Config::parse(&text).unwrap();
+        let game = config.settings(&program);
+        assert_eq!(game.ips, Some(1500));
+        assert_eq!(game.platform, Some(Platform::XoChip));
+        assert_eq!(game.index_overflow, Some(IndexOverflow::Fault));
+        assert_eq!(game.add_i_overflow, Some(true));
+        assert_eq!(game.color_depth, Some(ColorDepth::Ansi256));
+        assert_eq!(game.freeze, Some(vec!["0x2f0=3".parse().unwrap()]));
+        assert_eq!(game.palette.unwrap().on, Rgb(0x33, 0xff, 0x66));
+        assert_eq!(game.keymap().get('i'), Some(0x5));
+        assert_eq!(game.keymap().get('j'), Some(0x4));
+        assert_eq!(game.keymap().get('w'), Some(0x5));
+
+        let other = config.settings(&[0x12, 0x00]);
+        assert_eq!((other.ips, other.platform), (Some(1000), None));
+        let cli = Settings { ips: Some(700), ..Settings::default() };
+        assert_eq!(cli.or(&game).ips, Some(700));
+
+        assert!(Config::parse("[game.x]\nplatform = \"nes\"").is_err());
+        assert!(Config::parse("[game.x]\nkeymap = { i = 16 }").is_err());
+        assert!(Config::parse("speed = 1").is_err());
+        assert!(Config::parse("ips = 0").is_err());
+        assert_eq!(Config::parse("deterministic = true").unwrap().defaults.deterministic, Some(true));
+        assert_eq!(Config::parse("auto-pause = false").unwrap().defaults.auto_pause, Some(false));
+
+        let accessible = Config::parse("high-contrast = true\ninvert = true").unwrap().defaults;
+        assert_eq!(accessible.colors(), Some(Palette::HIGH_CONTRAST.inverted()));
+        assert_eq!(game.colors(), game.palette);
+    }
+}
@@ -1,20 +1,52 @@
 use crate::{
-    memory::{Memory, SegmentationFault}, address::{Address, InvalidAddress},
-    register::{InvalidRegisterNumber, VRegister}, screen::{self, Screen},
-    ticker::Ticker, isa::Instruction, 
+    memory::{Memory, Ram, SegmentationFault, WriteProtection}, address::{Address, InvalidAddress},
+    register::{InvalidRegisterNumber, VRegister}, screen::{self, Colors, Screen},
+    isa::{self, Decoding, Instruction}, snapshot::{InvalidSnapshot, Snapshot},
+    hooks::{Hooks, MachineHook}, observer::Observer, policy::{ErrorKind, ErrorPolicies, ErrorPolicy},
+    quirks::{IndexOverflow, Quirks}, relocation::Relocations, boot::BOOT_ADDRESS,
+    cheat::{Freeze, Target}
 };
-use std::{
-    sync::{Arc, atomic::{AtomicU8, Ordering}}, fs::File, 
-    path::PathBuf, io::{self, Read, Write}, fmt::{Display, Formatter}
-};
-use rand::random;
+use core::fmt::{Display, Formatter};
+use jit::Jit;
+use alloc::{boxed::Box, collections::VecDeque, format, string::String, vec, vec::Vec};
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+#[cfg(feature = "std")]
+use crate::{config::InvalidConfig, replay::InvalidReplay, rom, trace::InvalidTrace};
+#[cfg(feature = "std")]
+use std::{fs::File, path::{Path, PathBuf}, io::{self, Write}};
+
+mod jit;
 
 const PC_INCREMENT: Address = Address(2);
-const PC_START: Address = Address(0x200);
+/// Where programs are loaded and start executing, unless the platform says
+/// otherwise.
+pub const PC_START: Address = Address(0x200);
 const NUM_REGISTERS: usize = 0x10;
 const STACK_SIZE: usize = 0x10;
+const NUM_KEYS: usize = 0x10;
+/// Rate at which frames are run and the timers count down.
+pub const FRAMES_PER_SECOND: u32 = 60;
+/// Default emulation speed, which suits most classic CHIP-8 games.
+pub const DEFAULT_IPS: u32 = 700;
+/// Number of recently executed instructions kept to report with a fault.
+const HISTORY_SIZE: usize = 256;
+/// Number of those shown in an error message. Core dumps show them all.
+const BACKTRACE_SIZE: usize = 8;
+/// Bytes either side of the PC disassembled in a core dump.
+#[cfg(feature = "std")]
+const DISASSEMBLY_WINDOW: u16 = 16;
+/// Without `std` there is no entropy source to seed from, so runs start from a 
+/// fixed seed unless the embedder calls `Cpu::set_seed`.
+#[cfg(not(feature = "std"))]
+const DEFAULT_SEED: u64 = 0xC8;
 
-const SPRITES: [u8; 80] = [
+/// Bytes in a font: 5 rows for each hex digit.
+pub const FONT_SIZE: usize = 80;
+/// Bytes in a hi-res font, which may follow the usual one in a font file: 10
+/// rows for each hex digit.
+pub const BIG_FONT_SIZE: usize = 160;
+
+const SPRITES: [u8; FONT_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0,
     0x20, 0x60, 0x20, 0x20, 0x70,
     0xF0, 0x10, 0xF0, 0x80, 0xF0,
@@ -37,191 +69,1416 @@ const SPRITES: [u8; 80] = [
 pub struct Cpu {
     v: [u8; NUM_REGISTERS],
     i: Address,
-    dt: Arc<AtomicU8>,
-    st: Arc<AtomicU8>,
+    dt: u8,
+    st: u8,
     pc: Address,
     sp: usize,
+    /// The deepest the stack has been since the machine was created or reset.
+    max_depth: usize,
     stack: [Address; STACK_SIZE],
-    pub memory: Memory,
+    pub memory: Box<dyn Memory + Send>,
     display: Screen,
-    ticker: Ticker
+    paused: bool,
+    ips: u32,
+    /// Instructions owed to the next frame when `ips` isn't a multiple of 60.
+    budget: u32,
+    /// Whether the display changed since the end of the last frame.
+    dirty: bool,
+    policies: ErrorPolicies,
+    protection: WriteProtection,
+    quirks: Quirks,
+    /// Whether the last frame ended in a trap.
+    trapped: bool,
+    /// `DRW`s executed, and errors handled by a policy other than halting,
+    /// since the end of the last frame.
+    draws: u32,
+    errors: u32,
+    /// The `DRW`s executed since the last `take_sprites`, while recorded.
+    sprites: Option<Vec<SpriteDraw>>,
+    /// The most recently fetched instructions, with the registers before
+    /// each, for error reports.
+    recent: VecDeque<(Address, u16, [u8; NUM_REGISTERS])>,
+    /// Which addresses have been fetched as instructions, to detect 
+    /// self-modifying code.
+    executed: Vec<bool>,
+    /// Whether self-modifying code has already been warned about.
+    warned_self_modify: bool,
+    /// Previously decoded instructions by address, along with their opcodes.
+    decoded: Vec<Option<(u16, Instruction)>>,
+    /// The experimental JIT backend, when enabled.
+    jit: Option<Jit>,
+    /// Where the program is loaded and starts executing.
+    load_address: Address,
+    program: Vec<u8>,
+    /// The program's addresses to move when it isn't loaded at `PC_START`.
+    relocations: Relocations,
+    /// A program run from `BOOT_ADDRESS` before this one, if any.
+    boot: Option<Vec<u8>>,
+    /// The digit sprites `LD F, Vx` points into, and where they're loaded.
+    font: Vec<u8>,
+    font_address: Address,
+    seed: u64,
+    rng: Box<dyn RngCore + Send>,
+    keys: [bool; NUM_KEYS],
+    waiting: bool,
+    /// Whether the program has executed `00FD`.
+    halted: bool,
+    /// Frames run since the machine was created or reset, which numbers the
+    /// `frame` spans.
+    frames: u64,
+    /// Whether `PASS` and `FAIL` are decoded, and which was executed.
+    test_oracle: bool,
+    verdict: Option<Verdict>,
+    /// Whether the CHIP-8X instructions are decoded.
+    chip8x: bool,
+    /// Memory and registers held at a value by cheats.
+    frozen: Vec<Freeze>,
+    hooks: Hooks,
+    observers: Vec<Box<dyn Observer + Send>>
 }
 
 impl Display for Cpu {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
+        self.state().fmt(f)
+    }
+}
+
+/// The registers, stack, and timers of a `Cpu` at one moment, from
+/// `Cpu::state`, for debuggers and tests to read without the memory and
+/// display a `Snapshot` carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuState {
+    v: [u8; NUM_REGISTERS],
+    i: Address,
+    pc: Address,
+    sp: usize,
+    stack: [Address; STACK_SIZE],
+    dt: u8,
+    st: u8
+}
+
+impl CpuState {
+    pub fn v(&self, reg: VRegister) -> u8 {
+        self.v[reg]
+    }
+
+    /// V0 through VF.
+    pub fn registers(&self) -> &[u8; NUM_REGISTERS] {
+        &self.v
+    }
+
+    pub fn i(&self) -> Address {
+        self.i
+    }
+
+    pub fn pc(&self) -> Address {
+        self.pc
+    }
+
+    /// How many return addresses are on the stack.
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// The return addresses on the stack, the most recent call last.
+    pub fn stack(&self) -> &[Address] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn dt(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn st(&self) -> u8 {
+        self.st
+    }
+}
+
+impl Display for CpuState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
         for reg in 0..16 {
             let reg: VRegister = reg.try_into().unwrap();
             writeln!(f, "{reg:?} = {}", self.v[reg])?;
         }
-        writeln!(f, "DT = {}", self.dt.load(Ordering::SeqCst))?;
-        writeln!(f, "ST = {}", self.st.load(Ordering::SeqCst))?;
-        writeln!(f, "PC = {}", self.pc)?;
-        writeln!(f, "I  = {}", self.i)?;
-        writeln!(f, "SP = {}", self.sp)?;
-        writeln!(f, "STACK = {:?}", self.stack)?;
+        writeln!(f, "DT = {}", self.dt)?;
+        writeln!(f, "ST = {}", self.st)?;
+        writeln!(f, "PC = {}", self.pc)?;
+        writeln!(f, "I  = {}", self.i)?;
+        writeln!(f, "SP = {}", self.sp)?;
+        writeln!(f, "STACK = {:?}", self.stack)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum CpuError {
+    StackOverflow,
+    StackUnderflow,
+    InfiniteLoop,
+    InvalidAddress(String),
+    InvalidRegister(String),
+    SegmentationFault(Address),
+    InvalidInstruction(u16),
+    InvalidSnapshot(String),
+    #[cfg(feature = "std")]
+    ProgramLoadError(io::Error),
+    /// A program wrote to memory forbidden by the `WriteProtection` mode.
+    WriteProtected(Address),
+    /// The configuration file couldn't be read or parsed.
+    InvalidConfig(String),
+    /// An input replay file couldn't be parsed.
+    InvalidReplay(String),
+    /// An instruction trace couldn't be parsed.
+    InvalidTrace(String),
+    /// A test ROM run with `Cpu::set_test_oracle` failed, or stopped without
+    /// passing.
+    TestFailed(String),
+    /// A script run with `--script` couldn't be compiled or failed.
+    ScriptError(String),
+    /// An error raised while executing an instruction, along with where and 
+    /// how the machine got there.
+    Fault(Box<CpuError>, Box<ErrorContext>)
+}
+
+/// The machine state surrounding a faulting instruction.
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    /// Address of the faulting instruction.
+    pub pc: Address,
+    /// The raw opcode, unless the fault happened while fetching it.
+    pub opcode: Option<u16>,
+    /// The decoded instruction, unless the fault happened before decoding.
+    pub instruction: Option<Instruction>,
+    /// Up to `HISTORY_SIZE` of the most recently fetched instructions, oldest
+    /// first, ending with the faulting one.
+    pub backtrace: Vec<Executed>
+}
+
+/// An instruction in the history kept for fault reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Executed {
+    pub pc: Address,
+    pub opcode: u16,
+    /// The decoded instruction, unless the opcode is invalid.
+    pub instruction: Option<Instruction>,
+    /// The V registers it changed, and their new values.
+    pub changed: Vec<(VRegister, u8)>
+}
+
+impl Display for Executed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "{}: {:04x}", self.pc, self.opcode)?;
+        if let Some(i) = self.instruction {
+            write!(f, "  {i}")?;
+        }
+        for (n, (reg, value)) in self.changed.iter().enumerate() {
+            let separator = if n == 0 { "  ; " } else { ", " };
+            write!(f, "{separator}{reg} = {value:#04x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for CpuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
+        match self {
+            Self::StackOverflow => write!(f, "stack overflow: more than {STACK_SIZE} nested calls"),
+            Self::StackUnderflow => write!(f, "stack underflow: return with an empty stack"),
+            Self::InfiniteLoop => write!(f, "infinite loop: the program jumped to its own address"),
+            Self::InvalidAddress(msg) => write!(f, "{msg}"),
+            Self::InvalidRegister(msg) => write!(f, "{msg}"),
+            Self::SegmentationFault(addr) => write!(f, "segmentation fault: {addr} is outside of memory"),
+            Self::InvalidInstruction(op) => write!(f, "invalid instruction: {op:04x}"),
+            Self::InvalidSnapshot(msg) => write!(f, "{msg}"),
+            #[cfg(feature = "std")]
+            Self::ProgramLoadError(e) => write!(f, "failed to load program: {e}"),
+            Self::WriteProtected(addr) => write!(f, "write protection: {addr} is read-only"),
+            Self::InvalidConfig(msg) => write!(f, "{msg}"),
+            Self::InvalidReplay(msg) => write!(f, "{msg}"),
+            Self::InvalidTrace(msg) => write!(f, "{msg}"),
+            Self::TestFailed(msg) => write!(f, "{msg}"),
+            Self::ScriptError(msg) => write!(f, "{msg}"),
+            Self::Fault(e, ctx) => {
+                write!(f, "{e} at {}", ctx.pc)?;
+                match (ctx.opcode, ctx.instruction) {
+                    (Some(op), Some(i)) => write!(f, " ({op:04x}: {i})")?,
+                    (Some(op), None) => write!(f, " ({op:04x})")?,
+                    _ => ()
+                }
+
+                if !ctx.backtrace.is_empty() {
+                    write!(f, "\nrecent instructions:")?;
+                }
+                for executed in ctx.backtrace.iter().rev().take(BACKTRACE_SIZE).rev() {
+                    write!(f, "\n    {executed}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl core::error::Error for CpuError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::ProgramLoadError(e) => Some(e),
+            Self::Fault(e, _) => Some(e.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl CpuError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::StackOverflow => ErrorKind::StackOverflow,
+            Self::StackUnderflow => ErrorKind::StackUnderflow,
+            Self::InfiniteLoop => ErrorKind::InfiniteLoop,
+            Self::InvalidAddress(_) => ErrorKind::InvalidAddress,
+            Self::InvalidRegister(_) => ErrorKind::InvalidRegister,
+            Self::SegmentationFault(_) => ErrorKind::SegmentationFault,
+            Self::InvalidInstruction(_) => ErrorKind::InvalidInstruction,
+            Self::InvalidSnapshot(_) => ErrorKind::InvalidSnapshot,
+            #[cfg(feature = "std")]
+            Self::ProgramLoadError(_) => ErrorKind::ProgramLoadError,
+            Self::WriteProtected(_) => ErrorKind::WriteProtected,
+            Self::InvalidConfig(_) => ErrorKind::InvalidConfig,
+            Self::InvalidReplay(_) => ErrorKind::InvalidReplay,
+            Self::InvalidTrace(_) => ErrorKind::InvalidTrace,
+            Self::TestFailed(_) => ErrorKind::TestFailed,
+            Self::ScriptError(_) => ErrorKind::ScriptError,
+            Self::Fault(e, _) => e.kind()
+        }
+    }
+}
+
+impl From<InvalidAddress> for CpuError {
+    fn from(e: InvalidAddress) -> Self {
+        Self::InvalidAddress(e.0)
+    }
+}
+
+impl From<InvalidRegisterNumber> for CpuError {
+    fn from(e: InvalidRegisterNumber) -> Self {
+        Self::InvalidRegister(e.0)
+    }
+}
+
+impl From<SegmentationFault> for CpuError {
+    fn from(e: SegmentationFault) -> Self {
+        Self::SegmentationFault(e.0)
+    }
+}
+
+impl From<InvalidSnapshot> for CpuError {
+    fn from(e: InvalidSnapshot) -> Self {
+        Self::InvalidSnapshot(e.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<InvalidConfig> for CpuError {
+    fn from(e: InvalidConfig) -> Self {
+        Self::InvalidConfig(e.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<InvalidReplay> for CpuError {
+    fn from(e: InvalidReplay) -> Self {
+        Self::InvalidReplay(e.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<InvalidTrace> for CpuError {
+    fn from(e: InvalidTrace) -> Self {
+        Self::InvalidTrace(e.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for CpuError {
+    fn from(e: io::Error) -> Self {
+        Self::ProgramLoadError(e)
+    }
+}
+
+/// The side effects of one call to `Cpu::run_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Frame {
+    pub instructions: u32,
+    /// The display changed and should be presented.
+    pub drawn: bool,
+    /// The sound timer is non-zero, so the buzzer should be on.
+    pub sound: bool,
+    /// The program is blocked on `Fx0A` until a key is pressed.
+    pub waiting_for_key: bool,
+    /// An instruction faulted under `ErrorPolicy::Trap`, pausing the machine.
+    pub trapped: bool,
+    /// `DRW` instructions executed.
+    pub draws: u32,
+    /// Errors that were skipped, logged, or trapped on rather than halting.
+    pub errors: u32,
+    /// The program exited with `00FD`, or with `PASS` or `FAIL`.
+    pub halted: bool,
+    /// The frame ended early because the program was polling the delay timer
+    /// or keypad in a loop.
+    pub idle: bool
+}
+
+/// Where a `DRW` drew, recorded while `Cpu::record_sprites` is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteDraw {
+    /// The top left corner, after wrapping onto the display.
+    pub x: usize,
+    pub y: usize,
+    /// Rows drawn. Sprites are 8 pixels wide, and clipped at the edges.
+    pub height: usize,
+    /// The pixels the sprite turned off, setting VF, as `(x, y)`.
+    pub collisions: Vec<(usize, usize)>
+}
+
+/// What a test ROM reported with the `PASS` and `FAIL` pseudo-instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    /// The test failed at this `FAIL`.
+    Fail(Address)
+}
+
+/// What happened as a result of executing a single instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// Nothing a frontend needs to act on.
+    Continue,
+    /// The display changed and should be presented.
+    DrewFrame,
+    /// The program is blocked on `Fx0A` until a key is pressed.
+    WaitingForKey,
+    /// The program exited with `00FD`; further steps do nothing.
+    Halted,
+    /// The program jumped back to the start of a loop that only polls the 
+    /// delay timer or keypad. Neither changes until the frame ends, so the 
+    /// rest of the frame would be spent spinning.
+    Idle
+}
+
+pub(crate) fn split_into_nibbles(i: u16) -> [u8; 4] {
+    [
+        ((i & 0xF000) >> 12) as u8, 
+        ((i & 0x0F00) >> 8)  as u8, 
+        ((i & 0x00F0) >> 4)  as u8, 
+         (i & 0x000F)        as u8
+    ]
+}
+
+impl Cpu {
+    #[cfg(feature = "std")]
+    pub fn new(path: PathBuf) -> Result<Self, CpuError> {
+        Self::from_program(rom::read(&path)?)
+    }
+
+    pub fn from_program(program: Vec<u8>) -> Result<Self, CpuError> {
+        Self::with_memory(program, Box::new(Ram::new()))
+    }
+
+    /// Build a machine running `opcodes`, e.g. `&[0x6005, 0x7001]`, which is
+    /// easier to read and write than the bytes for short test programs.
+    pub fn with_program(opcodes: &[u16]) -> Result<Self, CpuError> {
+        Self::from_program(opcodes.iter().flat_map(|op| op.to_be_bytes()).collect())
+    }
+
+    /// Like `from_program`, but backed by a custom `Memory` implementation.
+    pub fn with_memory(program: Vec<u8>, mut memory: Box<dyn Memory + Send>) -> Result<Self, CpuError> {
+        #[cfg(feature = "std")]
+        let seed = rand::random();
+        #[cfg(not(feature = "std"))]
+        let seed = DEFAULT_SEED;
+        Self::load_memory(memory.as_mut(), (&SPRITES, Address(0)), &program, PC_START)?;
+        let len = memory.len();
+
+        Ok(Self {
+            v: [0; NUM_REGISTERS],
+            i: Address(0),
+            dt: 0,
+            st: 0,
+            pc: PC_START,
+            sp: 0,
+            max_depth: 0,
+            stack: [Address(0); STACK_SIZE],
+            memory,
+            display: Screen::new(),
+            paused: false,
+            ips: DEFAULT_IPS,
+            budget: 0,
+            dirty: false,
+            policies: ErrorPolicies::default(),
+            protection: WriteProtection::default(),
+            quirks: Quirks::default(),
+            trapped: false,
+            draws: 0,
+            errors: 0,
+            sprites: None,
+            recent: VecDeque::with_capacity(HISTORY_SIZE),
+            executed: vec![false; len],
+            warned_self_modify: false,
+            decoded: vec![None; len],
+            jit: None,
+            load_address: PC_START,
+            program,
+            relocations: Relocations::default(),
+            boot: None,
+            font: SPRITES.to_vec(),
+            font_address: Address(0),
+            seed,
+            rng: Box::new(SmallRng::seed_from_u64(seed)),
+            keys: [false; NUM_KEYS],
+            waiting: false,
+            halted: false,
+            frames: 0,
+            test_oracle: false,
+            verdict: None,
+            chip8x: false,
+            frozen: Vec::new(),
+            hooks: Hooks::default(),
+            observers: Vec::new()
+        })
+    }
+
+    fn load_memory(memory: &mut dyn Memory, font: (&[u8], Address), program: &[u8], start: Address) -> Result<(), CpuError> {
+        memory.clear();
+        memory.load_slice(font.1, font.0)?;
+        memory.load_slice(start, program)?;
+
+        Ok(())
+    }
+
+    /// Soft-reset the machine: clear the registers, stack, timers, and display,
+    /// then reload the original program bytes so the ROM starts over as if it 
+    /// had just been loaded.
+    pub fn reset(&mut self) -> Result<(), CpuError> {
+        let program = self.relocations.apply(&self.program, self.load_address)?;
+        Self::load_memory(self.memory.as_mut(), (&self.font, self.font_address), &program, self.load_address)?;
+        if let Some(boot) = &self.boot {
+            self.memory.load_slice(BOOT_ADDRESS, boot)?;
+        }
+        self.v = [0; NUM_REGISTERS];
+        self.i = Address(0);
+        self.dt = 0;
+        self.st = 0;
+        self.pc = match self.boot {
+            Some(_) => BOOT_ADDRESS,
+            None => self.load_address
+        };
+        self.sp = 0;
+        self.max_depth = 0;
+        self.stack = [Address(0); STACK_SIZE];
+        self.display.clear();
+        if self.chip8x {
+            self.display.set_colors(Some(Colors::new()));
+        }
+        self.drew();
+        self.rng = Box::new(SmallRng::seed_from_u64(self.seed));
+        self.keys = [false; NUM_KEYS];
+        self.waiting = false;
+        self.halted = false;
+        self.verdict = None;
+        self.frames = 0;
+        self.recent.clear();
+        self.executed.fill(false);
+        self.decoded.fill(None);
+        if let Some(jit) = self.jit.as_mut() {
+            jit.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Decrement the delay and sound timers. The execution loop calls this 
+    /// once per 60Hz frame, after that frame's instructions, so timing depends
+    /// only on the number of frames run rather than on wall-clock time.
+    pub fn tick_timers(&mut self) {
+        if self.paused {
+            return;
+        }
+
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
+        tracing::trace!(target: "timer", dt = self.dt, st = self.st);
+        self.hooks.timer_tick(self.dt, self.st);
+    }
+
+    /// Run one 60Hz frame: execute this frame's share of the instructions per 
+    /// second, then decrement the timers once. A frontend only needs to call 
+    /// this at 60Hz and act on the returned summary.
+    pub fn run_frame(&mut self) -> Result<Frame, CpuError> {
+        let mut frame = Frame::default();
+        if self.paused {
+            return Ok(frame);
+        }
+
+        // Events from the frame's instructions are grouped under its span.
+        let span = tracing::trace_span!(target: "frame", "frame", n = self.frames, instructions = tracing::field::Empty);
+        let _entered = span.enter();
+        self.frames += 1;
+
+        self.budget = self.budget.saturating_add(self.ips);
+        let mut remaining = self.budget / FRAMES_PER_SECOND;
+        while remaining > 0 {
+            let (count, outcome) = match self.jit {
+                Some(_) => self.run_block(remaining)?,
+                None => self.step().map(|o| ((o != StepOutcome::Halted) as u32, o))?
+            };
+            remaining = remaining.saturating_sub(count.max(1));
+            frame.instructions += count;
+
+            if outcome == StepOutcome::Halted {
+                frame.halted = true;
+                break;
+            }
+
+            if outcome == StepOutcome::Idle {
+                frame.idle = true;
+                break;
+            }
+
+            // The rest of the frame would only re-execute the `Fx0A`.
+            if outcome == StepOutcome::WaitingForKey || self.paused {
+                break;
+            }
+        }
+        self.budget %= FRAMES_PER_SECOND;
+        self.tick_timers();
+        self.run_hooks(|cpu| &mut cpu.hooks.frame)?;
+
+        frame.drawn = core::mem::take(&mut self.dirty);
+        frame.sound = self.st > 0;
+        frame.waiting_for_key = self.waiting;
+        frame.trapped = core::mem::take(&mut self.trapped);
+        frame.draws = core::mem::take(&mut self.draws);
+        frame.errors = core::mem::take(&mut self.errors);
+        span.record("instructions", frame.instructions);
+
+        Ok(frame)
+    }
+
+    /// Whether the display changed since the last call or `run_frame`.
+    pub fn take_drawn(&mut self) -> bool {
+        core::mem::take(&mut self.dirty)
+    }
+
+    /// Fetch, decode, and execute a single instruction. Errors are handled 
+    /// according to the configured `ErrorPolicy`, so only those the policy 
+    /// halts on are returned.
+    pub fn step(&mut self) -> Result<StepOutcome, CpuError> {
+        if self.halted {
+            return Ok(StepOutcome::Halted);
+        }
+
+        let pc = self.pc;
+        let (mut opcode, mut instruction) = (None, None);
+        let result = self.fetch()
+            .inspect(|&op| opcode = Some(op))
+            .and_then(|op| self.decode_cached(pc, op))
+            .inspect(|&decoded| instruction = Some(decoded))
+            .and_then(|decoded| self.execute(decoded));
+
+        match result {
+            Err(e) => {
+                let context = self.error_context(pc, opcode, instruction);
+                self.handle_error(CpuError::Fault(Box::new(e), Box::new(context)), pc)
+            },
+            ok => ok
+        }
+    }
+
+    /// Decode the instruction at `addr`, reusing the last decoding if the 
+    /// opcode there hasn't changed.
+    fn decode_cached(&mut self, addr: Address, opcode: u16) -> Result<Instruction, CpuError> {
+        // Writes through the Cpu invalidate entries, but `memory` is public and
+        // may be changed behind its back, so check the opcode too.
+        if let Some((op, instruction)) = self.decoded[addr.0 as usize] {
+            if op == opcode {
+                return Ok(instruction);
+            }
+        }
+
+        let instruction = self.decode(opcode)?;
+        tracing::trace!(target: "decode", "{addr}: {opcode:04x} => {instruction}");
+        self.decoded[addr.0 as usize] = Some((opcode, instruction));
+        Ok(instruction)
+    }
+
+    fn error_context(&self, pc: Address, opcode: Option<u16>, instruction: Option<Instruction>) -> ErrorContext {
+        ErrorContext { pc, opcode, instruction, backtrace: self.history() }
+    }
+
+    /// The recently fetched instructions, oldest first, with the registers 
+    /// each changed: the difference from the registers before the next one,
+    /// or from the current ones for the last.
+    fn history(&self) -> Vec<Executed> {
+        let after = self.recent.iter().skip(1).map(|&(_, _, v)| v).chain([self.v]);
+        self.recent.iter().zip(after).map(|(&(pc, opcode, before), after)| {
+            let changed = (0..NUM_REGISTERS)
+                .filter(|&n| before[n] != after[n])
+                .filter_map(|n| Some((VRegister::try_from(n as u8).ok()?, after[n])))
+                .collect();
+            Executed { pc, opcode, instruction: self.decode(opcode).ok(), changed }
+        }).collect()
+    }
+
+    fn handle_error(&mut self, e: CpuError, pc: Address) -> Result<StepOutcome, CpuError> {
+        let policy = self.policies.get(e.kind());
+        if policy == ErrorPolicy::Halt {
+            return Err(e);
+        }
+        self.errors += 1;
+
+        // Move past an instruction that faulted before it could be fetched, 
+        // or it would fault again immediately.
+        if self.pc == pc {
+            self.advance_pc();
+        }
+
+        match policy {
+            ErrorPolicy::Log => tracing::warn!("{e}"),
+            ErrorPolicy::Trap => {
+                self.pause();
+                self.trapped = true;
+            },
+            _ => ()
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+
+    pub fn error_policies(&mut self) -> &mut ErrorPolicies {
+        &mut self.policies
+    }
+
+    pub fn quirks(&mut self) -> &mut Quirks {
+        &mut self.quirks
+    }
+
+    /// Switch between the interpreter and the experimental JIT backend, which
+    /// `run_frame` uses to execute whole basic blocks at a time. Instructions
+    /// run by the JIT still go into the history and are checked for being
+    /// overwritten, but observers aren't notified of them, and it doesn't see
+    /// writes made directly through `memory`.
+    pub fn set_jit(&mut self, enabled: bool) {
+        self.jit = enabled.then(|| Jit::new(self.memory.len()));
+    }
+
+    /// Decode `00F1` and `00F2` as `PASS` and `FAIL`, which end the program
+    /// with a `Verdict`, for test ROMs. Otherwise they are invalid.
+    pub fn set_test_oracle(&mut self, enabled: bool) {
+        self.test_oracle = enabled;
+        self.decoded.fill(None);
+        if let Some(jit) = self.jit.as_mut() {
+            jit.clear();
+        }
+    }
+
+    /// Run CHIP-8X programs: decode its color, second keypad, and tone
+    /// instructions (with `Bxyn` in place of `JP V0, addr`) and give the
+    /// display colors.
+    pub fn set_chip8x(&mut self, enabled: bool) {
+        self.chip8x = enabled;
+        self.display.set_colors(enabled.then(Colors::new));
+        self.drew();
+        self.decoded.fill(None);
+        if let Some(jit) = self.jit.as_mut() {
+            jit.clear();
+        }
+    }
+
+    /// Give the display `rows` rows, e.g. 48 or 64 for the ETI-660, and clear
+    /// it. The 64 row display also decodes `0230`, which programs for it
+    /// clear the screen with, as `CLS`.
+    pub fn set_display_rows(&mut self, rows: usize) {
+        self.display.set_height(rows);
+        self.drew();
+        self.decoded.fill(None);
+        if let Some(jit) = self.jit.as_mut() {
+            jit.clear();
+        }
+    }
+
+    /// Record where each `DRW` draws and what it collides with, to be taken 
+    /// with `take_sprites`, for visualizing sprites.
+    pub fn record_sprites(&mut self, enabled: bool) {
+        self.sprites = enabled.then(Vec::new);
+    }
+
+    /// The `DRW`s recorded since the last call.
+    pub fn take_sprites(&mut self) -> Vec<SpriteDraw> {
+        self.sprites.as_mut().map(core::mem::take).unwrap_or_default()
+    }
+
+    /// What a test ROM reported, once it has executed `PASS` or `FAIL`.
+    pub fn verdict(&self) -> Option<Verdict> {
+        self.verdict
+    }
+
+    /// Choose which regions of memory the program may not write to. Writes 
+    /// there raise `CpuError::WriteProtected`. Under `ErrorPolicy::Log` the
+    /// write is logged and skipped rather than made.
+    pub fn set_write_protection(&mut self, protection: WriteProtection) {
+        self.protection = protection;
+    }
+
+    /// Fail if any of the `len` bytes starting at `addr` are write-protected.
+    fn check_writable(&self, addr: Address, len: u16) -> Result<(), CpuError> {
+        let end = match self.protection {
+            WriteProtection::Off => return Ok(()),
+            WriteProtection::Interpreter => self.load_address.0 as usize,
+            WriteProtection::Program => self.load_address.0 as usize + self.program.len()
+        };
+
+        let mask = self.mask();
+        match (0..len).map(|off| addr.wrapping_add(off, mask)).find(|a| (a.0 as usize) < end) {
+            Some(a) => Err(CpuError::WriteProtected(a)),
+            None => Ok(())
+        }
+    }
+
+    pub fn ips(&self) -> u32 {
+        self.ips
+    }
+
+    /// Set the emulation speed used by `run_frame`, in instructions per second.
+    pub fn set_ips(&mut self, ips: u32) {
+        self.ips = ips;
+    }
+
+    fn drew(&mut self) {
+        self.dirty = true;
+        tracing::trace!(target: "draw", "display changed");
+        self.hooks.draw(&self.display);
+    }
+
+    /// Freeze the machine: while paused the timers stop counting down, and the
+    /// execution loop should not run any instructions.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The seed of the default random number generator used by `Cxkk`.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Reseed the default random number generator, so that runs with the same 
+    /// seed produce the same sequence of random bytes. The seed is kept across 
+    /// resets.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Box::new(SmallRng::seed_from_u64(seed));
+    }
+
+    /// Replace the random number generator used by `Cxkk` entirely. Note that 
+    /// a reset restores the default generator seeded with `seed()`.
+    pub fn set_rng<R: RngCore + Send + 'static>(&mut self, rng: R) {
+        self.rng = Box::new(rng);
+    }
+
+    /// Mark a key on the hexadecimal keypad as held down. Keys outside of 
+    /// `0x0..=0xF` are ignored.
+    pub fn press_key(&mut self, key: u8) {
+        if let Some(k) = self.keys.get_mut(key as usize) {
+            tracing::debug!(target: "input", key, "pressed");
+            *k = true;
+        }
+    }
+
+    pub fn release_key(&mut self, key: u8) {
+        if let Some(k) = self.keys.get_mut(key as usize) {
+            tracing::debug!(target: "input", key, "released");
+            *k = false;
+        }
+    }
+
+    /// Whether a key on the keypad is held down.
+    pub fn key_pressed(&self, key: u8) -> bool {
+        self.keys.get(key as usize).copied().unwrap_or_default()
+    }
+
+    /// Called with the display after every instruction that modifies it.
+    pub fn on_draw<F: FnMut(&Screen) + Send + 'static>(&mut self, f: F) {
+        self.hooks.draw.push(Box::new(f));
+    }
+
+    /// Called with the destination register when `Fx0A` starts waiting for a 
+    /// key press.
+    pub fn on_key_wait<F: FnMut(VRegister) + Send + 'static>(&mut self, f: F) {
+        self.hooks.key_wait.push(Box::new(f));
+    }
+
+    /// Called with the new `DT` and `ST` values on every 60Hz timer tick.
+    pub fn on_timer_tick<F: FnMut(u8, u8) + Send + 'static>(&mut self, f: F) {
+        self.hooks.timer_tick.push(Box::new(f));
+    }
+
+    /// Called with the current PC when the program halts, either by jumping to 
+    /// itself or by executing `00FD`.
+    pub fn on_halt<F: FnMut(Address) + Send + 'static>(&mut self, f: F) {
+        self.hooks.halt.push(Box::new(f));
+    }
+
+    /// Called with the first overwritten address when `Fx55` or `Fx33` writes 
+    /// over code that has already been executed.
+    pub fn on_self_modify<F: FnMut(Address) + Send + 'static>(&mut self, f: F) {
+        self.hooks.self_modify.push(Box::new(f));
+    }
+
+    /// Called with the machine at the end of every frame, after the timers
+    /// tick, to read or change it. An error stops the frame with it.
+    pub fn on_frame<F: FnMut(&mut Cpu) -> Result<(), CpuError> + Send + 'static>(&mut self, f: F) {
+        self.hooks.frame.push(Box::new(f));
+    }
+
+    /// Called with the machine after every instruction, to read or change it.
+    /// An error is handled like one the instruction raised.
+    pub fn on_instruction<F: FnMut(&mut Cpu) -> Result<(), CpuError> + Send + 'static>(&mut self, f: F) {
+        self.hooks.instruction.push(Box::new(f));
+    }
+
+    /// Attach an observer that is notified before and after every executed 
+    /// instruction.
+    pub fn attach<O: Observer + Send + 'static>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.display
+    }
+
+    /// The address of the next instruction to be fetched.
+    pub fn pc(&self) -> Address {
+        self.pc
+    }
+
+    /// V0 through VF.
+    pub fn registers(&self) -> &[u8; NUM_REGISTERS] {
+        &self.v
+    }
+
+    pub fn i(&self) -> Address {
+        self.i
+    }
+
+    /// The delay and sound timers.
+    pub fn timers(&self) -> (u8, u8) {
+        (self.dt, self.st)
+    }
+
+    /// The registers, stack, and timers as they are now.
+    pub fn state(&self) -> CpuState {
+        CpuState { v: self.v, i: self.i, pc: self.pc, sp: self.sp, stack: self.stack, dt: self.dt, st: self.st }
+    }
+
+    pub fn set_v(&mut self, reg: VRegister, value: u8) {
+        self.v[reg] = value;
+    }
+
+    /// Continue from `pc`, e.g. to skip over a stuck loop. Where it isn't in
+    /// memory, the next fetch faults.
+    pub fn set_pc(&mut self, pc: Address) {
+        self.pc = pc;
+    }
+
+    pub fn set_i(&mut self, i: Address) {
+        self.i = i;
+    }
+
+    pub fn set_dt(&mut self, dt: u8) {
+        self.dt = dt;
+    }
+
+    pub fn set_st(&mut self, st: u8) {
+        self.st = st;
+    }
+
+    /// Hold a byte of memory or a register at a value from now on, putting
+    /// it back after every instruction, in place of any value it was already
+    /// frozen at.
+    pub fn freeze(&mut self, freeze: Freeze) -> Result<(), CpuError> {
+        if let Target::Memory(addr) = freeze.target {
+            if addr.0 as usize >= self.memory.len() {
+                return Err(CpuError::InvalidAddress(format!("Can't freeze {addr}: past the end of memory")));
+            }
+        }
+        self.unfreeze(freeze.target);
+        self.frozen.push(freeze);
+        self.apply_frozen()
+    }
+
+    /// Stop holding `target` at a value, returning whether it was frozen.
+    pub fn unfreeze(&mut self, target: Target) -> bool {
+        let before = self.frozen.len();
+        self.frozen.retain(|freeze| freeze.target != target);
+        self.frozen.len() != before
+    }
+
+    pub fn frozen(&self) -> &[Freeze] {
+        &self.frozen
+    }
+
+    /// Write `value` to a byte of memory or a register, once. Memory is only
+    /// written where it differs, so frozen bytes near code don't keep
+    /// throwing away decoded and translated instructions.
+    pub fn poke(&mut self, target: Target, value: u8) -> Result<(), CpuError> {
+        match target {
+            Target::Register(reg) => self.v[reg] = value,
+            Target::Memory(addr) => {
+                if self.memory.get_byte(addr)? != value {
+                    self.write_wrapping(addr, &[value])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Put back the frozen values the program changed.
+    fn apply_frozen(&mut self) -> Result<(), CpuError> {
+        for i in 0..self.frozen.len() {
+            let Freeze { target, value } = self.frozen[i];
+            self.poke(target, value)?;
+        }
+        Ok(())
+    }
+
+    /// Put back frozen values and run the `on_instruction` hooks, after each
+    /// instruction.
+    pub(crate) fn after_instruction(&mut self) -> Result<(), CpuError> {
+        self.apply_frozen()?;
+        self.run_hooks(|cpu| &mut cpu.hooks.instruction)
+    }
+
+    /// Call the hooks `select` picks with the machine, detaching them while
+    /// they borrow it.
+    fn run_hooks(&mut self, select: fn(&mut Cpu) -> &mut Vec<MachineHook>) -> Result<(), CpuError> {
+        if select(self).is_empty() {
+            return Ok(());
+        }
+        let mut hooks = core::mem::take(select(self));
+        let result = hooks.iter_mut().try_for_each(|hook| hook(self));
+        // Put back any added by the hooks too.
+        let added = core::mem::replace(select(self), hooks);
+        select(self).extend(added);
+        result
+    }
+
+    /// The deepest the call stack has been since the machine was created or
+    /// reset.
+    pub fn max_stack_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Replace the running program with a different ROM image and reset the 
+    /// machine so execution starts from the beginning of the new program.
+    /// Any relocations are applied to it too, as when a rebuilt ROM is
+    /// reloaded.
+    pub fn load_program(&mut self, program: Vec<u8>) -> Result<(), CpuError> {
+        // Validate the image fits before discarding the running program.
+        self.check_fits(program.len(), self.load_address)?;
+        self.relocations.apply(&program, self.load_address)?;
+        self.program = program;
+        self.reset()
+    }
+
+    /// Load the program at `address` instead of `PC_START` and reset the 
+    /// machine so execution starts there, e.g. 0x600 for ETI-660 programs.
+    pub fn set_load_address(&mut self, address: Address) -> Result<(), CpuError> {
+        self.check_fits(self.program.len(), address)?;
+        self.relocations.apply(&self.program, address)?;
+        Self::check_boot(self.boot.as_deref(), address)?;
+        self.load_address = address;
+        self.reset()
+    }
+
+    /// Move the program's own addresses by how far its load address is from
+    /// `PC_START`, from now on, and reset the machine.
+    pub fn set_relocations(&mut self, relocations: Relocations) -> Result<(), CpuError> {
+        relocations.apply(&self.program, self.load_address)?;
+        self.relocations = relocations;
+        self.reset()
+    }
+
+    pub fn relocations(&self) -> &Relocations {
+        &self.relocations
+    }
+
+    /// Run `boot`, e.g. `boot::splash`, from `BOOT_ADDRESS` before the
+    /// program on every reset, or stop running one, and reset the machine.
+    /// It's loaded after the font, and has to end before the program starts.
+    pub fn set_boot(&mut self, boot: Option<Vec<u8>>) -> Result<(), CpuError> {
+        Self::check_boot(boot.as_deref(), self.load_address)?;
+        self.boot = boot;
+        self.reset()
+    }
+
+    fn check_boot(boot: Option<&[u8]>, load_address: Address) -> Result<(), CpuError> {
+        match boot {
+            Some(boot) if BOOT_ADDRESS.0 as usize + boot.len() > load_address.0 as usize => {
+                Err(CpuError::InvalidConfig(format!(
+                    "A {} byte boot program doesn't fit between {BOOT_ADDRESS} and the ROM at {load_address}", boot.len()
+                )))
+            },
+            _ => Ok(())
+        }
+    }
+
+    pub fn load_address(&self) -> Address {
+        self.load_address
+    }
+
+    /// The digit sprites, followed by the hi-res ones if they were given.
+    pub fn font(&self) -> &[u8] {
+        &self.font
+    }
+
+    /// Replace the digit sprites with `font`, loaded at `address` instead of
+    /// 0, and reset the machine. A font is `FONT_SIZE` bytes, optionally
+    /// followed by a `BIG_FONT_SIZE` byte hi-res font. The program is loaded
+    /// after the font, so it wins where they overlap.
+    pub fn set_font(&mut self, font: Vec<u8>, address: Address) -> Result<(), CpuError> {
+        if ![FONT_SIZE, FONT_SIZE + BIG_FONT_SIZE].contains(&font.len()) {
+            return Err(CpuError::InvalidConfig(format!(
+                "A font is {FONT_SIZE} bytes, or {} with a hi-res font, not {}", FONT_SIZE + BIG_FONT_SIZE, font.len()
+            )));
+        }
+        self.check_fits(font.len(), address)?;
+        self.font = font;
+        self.font_address = address;
+        self.reset()
+    }
+
+    fn check_fits(&self, len: usize, start: Address) -> Result<(), CpuError> {
+        let capacity = self.memory.len().saturating_sub(start.0 as usize);
+        if len > capacity {
+            return Err(CpuError::SegmentationFault(start.wrapping_add(capacity as u16, u16::MAX)));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load_rom(&mut self, path: &Path) -> Result<(), CpuError> {
+        self.load_program(rom::read(path)?)
+    }
+
+    /// Capture the full machine state.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack,
+            dt: self.dt,
+            st: self.st,
+            memory: self.memory.to_bytes(),
+            display: self.display.rows().to_vec()
+        }
+    }
+
+    /// The memory pages written since the last call, for recording 
+    /// incremental snapshots with `Rewind`.
+    pub fn take_dirty_pages(&mut self) -> Vec<usize> {
+        self.memory.take_dirty_pages()
+    }
+
+    /// Restore a machine state previously captured with `snapshot`.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), CpuError> {
+        if snapshot.memory.len() != self.memory.len() {
+            return Err(CpuError::InvalidSnapshot(format!(
+                "Invalid Snapshot: expected {} bytes of memory, found {}", 
+                self.memory.len(), snapshot.memory.len()
+            )));
+        } else if snapshot.sp > STACK_SIZE {
+            return Err(CpuError::InvalidSnapshot(format!(
+                "Invalid Snapshot: stack pointer out of range: {}", snapshot.sp
+            )));
+        }
+
+        self.memory.load_slice(Address(0), &snapshot.memory)?;
+        self.decoded.fill(None);
+        if let Some(jit) = self.jit.as_mut() {
+            jit.clear();
+        }
+        self.v = snapshot.v;
+        self.i = snapshot.i;
+        self.pc = snapshot.pc;
+        self.sp = snapshot.sp;
+        self.stack = snapshot.stack;
+        self.dt = snapshot.dt;
+        self.st = snapshot.st;
+        self.display.set_rows(&snapshot.display);
+        self.drew();
+
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save_state(&self, path: &Path) -> Result<(), CpuError> {
+        Ok(self.snapshot().save(path, &crate::rom::hash(&self.program))?)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load_state(&mut self, path: &Path) -> Result<(), CpuError> {
+        self.restore(&Snapshot::load(path, &crate::rom::hash(&self.program))?)
+    }
+
+    /// Like `save_state`, but returning the file's bytes.
+    #[cfg(feature = "std")]
+    pub fn to_state(&self) -> Result<Vec<u8>, CpuError> {
+        Ok(self.snapshot().to_state(&crate::rom::hash(&self.program))?)
+    }
+
+    /// Like `load_state`, but from the file's bytes.
+    #[cfg(feature = "std")]
+    pub fn restore_state(&mut self, state: &[u8]) -> Result<(), CpuError> {
+        self.restore(&Snapshot::from_state(state, &crate::rom::hash(&self.program))?)
+    }
+
+    /// Write the registers, the call stack, a disassembly of the code around 
+    /// the PC, the recently executed instructions, and a hexdump of memory to
+    /// `path`, for post-mortem debugging.
+    #[cfg(feature = "std")]
+    pub fn dump_core(&self, path: &Path) -> io::Result<()> {
+        let mut f = io::BufWriter::new(File::create(path)?);
+
+        writeln!(f, "registers:\n{self}")?;
+
+        writeln!(f, "call stack:")?;
+        for (depth, addr) in self.stack[..self.sp].iter().rev().enumerate() {
+            writeln!(f, "    #{depth} return to {addr}")?;
+        }
+        if self.sp == 0 {
+            writeln!(f, "    (empty)")?;
+        }
+
+        writeln!(f, "\ndisassembly:")?;
+        let start = self.pc.0.saturating_sub(DISASSEMBLY_WINDOW) as usize;
+        let end = self.pc.0.saturating_add(DISASSEMBLY_WINDOW) as usize + 2;
+        for (addr, instruction) in self.memory.disassemble(start..end, self.decoding()) {
+            let marker = if addr == self.pc { "=>" } else { "  " };
+            match instruction {
+                Ok(instruction) => writeln!(f, "{marker} {addr}: {:04x}  {instruction}", instruction.encode().unwrap_or_default())?,
+                Err(op) => writeln!(f, "{marker} {addr}: {op:04x}")?
+            }
+        }
+
+        writeln!(f, "\nrecent instructions:")?;
+        for executed in self.history() {
+            writeln!(f, "    {executed}")?;
+        }
+        if self.recent.is_empty() {
+            writeln!(f, "    (none)")?;
+        }
+
+        writeln!(f, "\nmemory:\n{}", self.memory)?;
+        f.flush()
+    }
+
+    pub fn fetch(&mut self) -> Result<u16, CpuError> {
+        let instruction = self.memory
+            .get_short(self.pc)?;
+
+        self.record_fetch(instruction);
+        self.advance_pc();
+        Ok(instruction)
+    }
+
+    /// Note that `opcode`, at the PC, is about to run: add it to the history
+    /// and mark its bytes executed, for noticing self-modifying code.
+    fn record_fetch(&mut self, opcode: u16) {
+        if self.recent.len() == HISTORY_SIZE {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((self.pc, opcode, self.v));
+        tracing::trace!(target: "fetch", "{} => {opcode:#06x}", self.pc);
+        let next = self.pc.wrapping_add(1, self.mask());
+        self.executed[self.pc.0 as usize] = true;
+        self.executed[next.0 as usize] = true;
+    }
+
+    /// Read `len` bytes starting at `addr`, wrapping around the end of memory.
+    fn read_wrapping(&self, addr: Address, len: usize) -> Result<Vec<u8>, CpuError> {
+        let start = addr.0 as usize;
+        let head = len.min(self.memory.len().saturating_sub(start));
+        let mut bytes = self.memory.read_slice(start..start + head)?;
+        bytes.extend(self.memory.read_slice(0..len - head)?);
+
+        Ok(bytes)
+    }
+
+    /// Write `data` starting at `addr`, wrapping around the end of memory.
+    fn write_wrapping(&mut self, addr: Address, data: &[u8]) -> Result<(), CpuError> {
+        let head = data.len().min(self.memory.len().saturating_sub(addr.0 as usize));
+        self.memory.load_slice(addr, &data[..head])?;
+        self.memory.load_slice(Address(0), &data[head..])?;
+        self.check_self_modify(addr, data.len());
+        if let Some(jit) = self.jit.as_mut() {
+            jit.invalidate(addr, data.len(), self.memory.len().saturating_sub(1) as u16);
+        }
+
+        // Instructions starting at the byte before `addr` overlap the write too.
+        let mask = self.mask();
+        for off in 0..=data.len() as u16 {
+            let a = addr.wrapping_sub(1, mask).wrapping_add(off, mask);
+            self.decoded[a.0 as usize] = None;
+        }
 
         Ok(())
     }
-}
 
-#[derive(Debug)]
-pub enum CpuError {
-    StackOverflow,
-    InfiniteLoop,
-    InvalidAddress(String),
-    InvalidRegister(String),
-    SegmentationFault(Address),
-    InvalidInstruction(u16),
-    ProgramLoadError(io::Error)
-}
+    /// Report writes over code that has already run. Such code is only 
+    /// flagged again once it has run again.
+    fn check_self_modify(&mut self, addr: Address, len: usize) {
+        let mask = self.mask();
+        let modified = (0..len as u16)
+            .map(|off| addr.wrapping_add(off, mask))
+            .find(|a| self.executed[a.0 as usize]);
 
-impl From<InvalidAddress> for CpuError {
-    fn from(e: InvalidAddress) -> Self {
-        Self::InvalidAddress(e.0)
+        if let Some(modified) = modified {
+            if !self.warned_self_modify {
+                tracing::warn!("self-modifying code: {modified} was overwritten after being executed");
+            }
+            self.warned_self_modify = true;
+            (0..len as u16).for_each(|off| self.executed[addr.wrapping_add(off, mask).0 as usize] = false);
+            self.hooks.self_modify(modified);
+        }
     }
-}
 
-impl From<InvalidRegisterNumber> for CpuError {
-    fn from(e: InvalidRegisterNumber) -> Self {
-        Self::InvalidRegister(e.0)
+    /// The mask covering the whole of memory, which addresses wrap around at.
+    fn mask(&self) -> u16 {
+        self.memory.len().saturating_sub(1) as u16
     }
-}
 
-impl From<SegmentationFault> for CpuError {
-    fn from(e: SegmentationFault) -> Self {
-        Self::SegmentationFault(e.0)
+    fn advance_pc(&mut self) {
+        self.pc = self.pc.wrapping_add(PC_INCREMENT.0, self.mask());
     }
-}
 
-impl From<io::Error> for CpuError {
-    fn from(e: io::Error) -> Self {
-        Self::ProgramLoadError(e)
+    /// The address of the instruction currently executing.
+    fn current_pc(&self) -> Address {
+        self.pc.wrapping_sub(PC_INCREMENT.0, self.mask())
     }
-}
 
-fn split_into_nibbles(i: u16) -> [u8; 4] {
-    [
-        ((i & 0xF000) >> 12) as u8, 
-        ((i & 0x0F00) >> 8)  as u8, 
-        ((i & 0x00F0) >> 4)  as u8, 
-         (i & 0x000F)        as u8
-    ]
-}
+    /// Whether the instructions from `start` up to the jump back at `end` 
+    /// only poll the delay timer or keypad, like `LD Vx, DT; SE Vx, 0; JP` or
+    /// `SKP Vx; JP`. Since the timers tick and keys change between frames, 
+    /// such a loop can't exit before the frame ends.
+    fn is_wait_loop(&self, start: Address, end: Address) -> bool {
+        use Instruction::*;
 
-impl Cpu {
-    pub fn new(path: PathBuf) -> Result<Self, CpuError> {
-        let mut program = Vec::new();
-        let mut f = File::open(path)?;
-        f.read_to_end(&mut program)?;
-
-        let mut memory = Memory::new();
-        memory.copy_to_offset(&SPRITES, SPRITES.len(), Address(0))?;
-        memory.copy_to_offset(&program, program.len(), PC_START)?;
-
-        let dt: Arc<AtomicU8> = Arc::new(0.into());
-        let dtc: Arc<AtomicU8> = dt.clone();
-        let ticker: Ticker = Ticker::new(move || {
-            if dtc.load(Ordering::SeqCst) > 0 {
-                dtc.fetch_sub(1, Ordering::SeqCst);
-            }
-        });
-        
-        Ok(Self {
-            v: [0; NUM_REGISTERS],
-            i: Address(0),
-            dt,
-            st: Arc::new(0.into()),
-            pc: PC_START,
-            sp: 0,
-            stack: [Address(0); STACK_SIZE],
-            memory,
-            display: Screen::new(),
-            ticker
-        })
-    }
+        let len = end.0.wrapping_sub(start.0) / PC_INCREMENT.0;
+        if end.0 < start.0 || !(1..=2).contains(&len) {
+            return false;
+        }
 
-    pub fn dump_core(&self) {
-        let mut f = std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open("core")
-            .unwrap();
+        let mask = self.mask();
+        let body: Option<Vec<Instruction>> = (0..len)
+            .map(|n| start.wrapping_add(n * PC_INCREMENT.0, mask))
+            .map(|addr| self.memory.get_short(addr).ok().and_then(|op| self.decode(op).ok()))
+            .collect();
 
-        f.write(format!("{}", self.memory).as_bytes()).unwrap();
+        match body.as_deref() {
+            Some([SkipIfKey(_) | SkipIfNotKey(_)]) => true,
+            Some([LoadDT(x), SkipIfEqualImm(y, _) | SkipIfNotEqualImm(y, _)]) => x == y,
+            _ => false
+        }
     }
 
-    pub fn fetch(&mut self) -> Result<u16, CpuError> {
-        let instruction = self.memory
-            .get_short(self.pc)?;
-
-        self.pc += PC_INCREMENT;
-        Ok(instruction)
+    /// Which optional instructions `decode` accepts, as set up now.
+    pub fn decoding(&self) -> Decoding {
+        Decoding {
+            chip8x: self.chip8x,
+            test_oracle: self.test_oracle,
+            tall_display: self.display.height() == screen::MAX_ROWS
+        }
     }
 
     pub fn decode(&self, instruction: u16) -> Result<Instruction, CpuError> {
-        use Instruction::*;
+        isa::decode(instruction, self.decoding()).map_err(CpuError::InvalidInstruction)
+    }
+
+    /// Skip the next instruction, which is twice as long if it is `F000 nnnn`.
+    fn skip(&mut self) {
+        if self.memory.get_short(self.pc).ok() == Some(0xF000) {
+            self.advance_pc();
+        }
+        self.advance_pc();
+    }
+
+    pub fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome, CpuError> {
+        // Observers borrow the Cpu immutably, so detach them while notifying.
+        let mut observers = core::mem::take(&mut self.observers);
+        observers.iter_mut().for_each(|o| o.before_execute(self, &instruction));
+        let result = self.execute_instruction(instruction)
+            .and_then(|outcome| self.after_instruction().map(|()| outcome));
+        observers.iter_mut().for_each(|o| o.after_execute(self, &instruction));
+        self.observers = observers;
+
+        result
+    }
 
-        let nibbles: [u8; 4] = split_into_nibbles(instruction);
-        let vx = nibbles[1].try_into();
-        let vy = nibbles[2].try_into();
-        let addr: Address = (instruction & Address::MASK).into();
-        let lsb = (instruction & 0xFF) as u8;
-        let lsn = (instruction & 0xF) as u8;
-
-        match nibbles {
-            [0x0, 0x0, 0xE, 0x0] => Ok(ClearScreen),
-            [0x0, 0x0, 0xE, 0xE] => Ok(Return),
-            [0x1, ..]            => Ok(Jump(addr)),
-            [0x2, ..]            => Ok(Call(addr)),
-            [0x3, ..]            => Ok(SkipIfEqualImm(vx?, lsb)),
-            [0x4, ..]            => Ok(SkipIfNotEqualImm(vx?, lsb)),
-            [0x5, .., 0x0]       => Ok(SkipIfEqual(vx?, vy?)),
-            [0x6, ..]            => Ok(LoadImm(vx?, lsb)),
-            [0x7, ..]            => Ok(AddImm(vx?, lsb)),
-            [0x8, .., 0x0]       => Ok(Move(vx?, vy?)),
-            [0x8, .., 0x1]       => Ok(Or(vx?, vy?)),
-            [0x8, .., 0x2]       => Ok(And(vx?, vy?)),
-            [0x8, .., 0x3]       => Ok(Xor(vx?, vy?)),
-            [0x8, .., 0x4]       => Ok(Add(vx?, vy?)),
-            [0x8, .., 0x5]       => Ok(Subtract(vx?, vy?)),
-            [0x8, .., 0x6]       => Ok(ShiftRight(vx?)),
-            [0x8, .., 0x7]       => Ok(SubtractN(vx?, vy?)),
-            [0x8, .., 0xE]       => Ok(ShiftLeft(vx?)),
-            [0x9, .., 0x0]       => Ok(SkipIfNotEqual(vx?, vy?)),
-            [0xA, ..]            => Ok(LoadI(addr)),
-            [0xB, ..]            => Ok(JumpOffset(addr)),
-            [0xC, ..]            => Ok(AndRandom(vx?, lsb)),
-            [0xD, ..]            => Ok(Draw(vx?, vy?, lsn)),
-            [0xF, _, 0x0, 0x7]   => Ok(LoadDT(vx?)),
-            [0xF, _, 0x1, 0x5]   => Ok(StoreDT(vx?)),
-            [0xF, _, 0x1, 0x8]   => Ok(Nop),
-            [0xF, _, 0x1, 0xE]   => Ok(AddI(vx?)),
-            [0xF, _, 0x2, 0x9]   => Ok(LoadSprite(vx?)),
-            [0xF, _, 0x3, 0x3]   => Ok(StoreBCD(vx?)),
-            [0xF, _, 0x5, 0x5]   => Ok(Store(vx?)),
-            [0xF, _, 0x6, 0x5]   => Ok(Load(vx?)),
-            _ => Err(CpuError::InvalidInstruction(instruction))
-        }
-    }
-
-    pub fn execute(&mut self, instruction: Instruction) -> Result<(), CpuError> {
+    fn execute_instruction(&mut self, instruction: Instruction) -> Result<StepOutcome, CpuError> {
         use Instruction::*;
         match instruction {
             Nop => (),
-            ClearScreen => self.display.clear(),
+            ClearScreen => {
+                self.display.clear();
+                self.drew();
+                return Ok(StepOutcome::DrewFrame);
+            },
             Return => {
+                if self.sp == 0 {
+                    return Err(CpuError::StackUnderflow);
+                }
+
                 self.sp -= 1;
                 self.pc = self.stack[self.sp];
+            },
+            Exit => {
+                self.halted = true;
+                self.hooks.halt(self.current_pc());
+                return Ok(StepOutcome::Halted);
+            }
+            Pass | Fail => {
+                let pc = self.current_pc();
+                self.verdict = Some(if instruction == Pass { Verdict::Pass } else { Verdict::Fail(pc) });
+                self.halted = true;
+                self.hooks.halt(pc);
+                return Ok(StepOutcome::Halted);
             }
             Jump(addr) => {
-                if self.pc - PC_INCREMENT == addr {
+                if self.current_pc() == addr {
+                    self.hooks.halt(addr);
                     return Err(CpuError::InfiniteLoop)
                 }
-                self.pc = addr
+
+                let from = self.current_pc();
+                self.pc = addr;
+                if self.is_wait_loop(addr, from) {
+                    return Ok(StepOutcome::Idle);
+                }
             },
             JumpOffset(addr) => {
-                self.pc = addr.offset(self.v[VRegister::V0] as u16);
+                self.pc = addr.checked_add(self.v[VRegister::V0] as u16, self.mask())?;
             }
             Call(addr) => {
                 if self.sp >= STACK_SIZE {
@@ -231,25 +1488,26 @@ impl Cpu {
                 self.stack[self.sp] = self.pc;
                 self.pc = addr;
                 self.sp += 1;
+                self.max_depth = self.max_depth.max(self.sp);
             },
             SkipIfEqualImm(reg, imm) => {
                 if self.v[reg] == imm {
-                    self.pc += PC_INCREMENT;
+                    self.skip();
                 }
             },
             SkipIfNotEqualImm(reg, imm) => {
                 if self.v[reg] != imm {
-                    self.pc += PC_INCREMENT;
+                    self.skip();
                 }
             },
             SkipIfEqual(regx, regy) => {
                 if self.v[regx] == self.v[regy] {
-                    self.pc += PC_INCREMENT;
+                    self.skip();
                 }
             },
             SkipIfNotEqual(regx, regy) => {
                 if self.v[regx] != self.v[regy] {
-                    self.pc += PC_INCREMENT;
+                    self.skip();
                 }
             },
             LoadImm(reg, imm) => {
@@ -271,10 +1529,23 @@ impl Cpu {
                 self.v[regx] ^= self.v[regy]
             },
             AndRandom(reg, byte) => {
-                self.v[reg] = byte & random::<u8>()
+                self.v[reg] = byte & (self.rng.next_u32() as u8)
+            },
+            LoadLongI => {
+                self.i = Address(self.memory.get_short(self.pc)?);
+                self.advance_pc();
             },
             AddI(reg) => {
-                self.i += self.v[reg].into()
+                let (vx, mask) = (self.v[reg] as u16, self.mask());
+                let i = match self.quirks.index_overflow {
+                    IndexOverflow::Wrap => self.i.wrapping_add(vx, mask),
+                    IndexOverflow::Fault => self.i.checked_add(vx, mask)?
+                };
+
+                if self.quirks.add_i_overflow {
+                    self.v[VRegister::VF] = (self.i.0 as u32 + vx as u32 > Address::MASK as u32) as u8;
+                }
+                self.i = i;
             },
             Add(regx, regy) => {
                 let vf = &mut false;
@@ -301,75 +1572,717 @@ impl Cpu {
             },
             LoadI(addr) => self.i = addr,
             LoadDT(reg) => {
-                self.v[reg] = self.dt.load(Ordering::SeqCst);
+                self.v[reg] = self.dt;
             },
             StoreDT(reg) => {
-                self.dt.store(self.v[reg], Ordering::SeqCst)
+                self.dt = self.v[reg]
+            },
+            StoreST(reg) => {
+                self.st = self.v[reg]
+            },
+            SkipIfKey(reg) => {
+                if self.keys[(self.v[reg] & 0xF) as usize] {
+                    self.skip();
+                }
+            },
+            SkipIfNotKey(reg) => {
+                if !self.keys[(self.v[reg] & 0xF) as usize] {
+                    self.skip();
+                }
+            },
+            // There is no second keypad, so none of its keys are ever down.
+            SkipIfKey2(_) => (),
+            SkipIfNotKey2(_) => self.skip(),
+            // The buzzer only has the one pitch.
+            Tone(_) => (),
+            CycleBackground => {
+                if let Some(colors) = self.display.colors_mut() {
+                    colors.cycle_background();
+                }
+                self.drew();
+                return Ok(StepOutcome::DrewFrame);
+            },
+            AddNibbles(regx, regy) => {
+                let (x, y) = (self.v[regx], self.v[regy]);
+                let high = ((x >> 4) + (y >> 4)) & 0x7;
+                let low = ((x & 0xF) + (y & 0xF)) & 0x7;
+                self.v[regx] = high << 4 | low;
+            },
+            Color(regx, regy, n) => {
+                // The coordinates are in `Vx` and the register after it.
+                let (x, y, color) = (self.v[regx], self.v[(regx as usize + 1) % NUM_REGISTERS], self.v[regy]);
+                if let Some(colors) = self.display.colors_mut() {
+                    match n {
+                        0 => colors.fill_zones(x, y, color),
+                        n => colors.fill_rows(x, y, n, color)
+                    }
+                }
+                self.drew();
+                return Ok(StepOutcome::DrewFrame);
+            },
+            WaitKey(reg) => {
+                match self.keys.iter().position(|&k| k) {
+                    Some(key) => {
+                        self.v[reg] = key as u8;
+                        self.waiting = false;
+                    },
+                    None => {
+                        // Re-execute this instruction until a key is pressed.
+                        self.pc = self.current_pc();
+                        if !self.waiting {
+                            self.waiting = true;
+                            self.hooks.key_wait(reg);
+                        }
+                        return Ok(StepOutcome::WaitingForKey);
+                    }
+                }
             },
             LoadSprite(reg) => {
-                self.i = ((self.v[reg] & 0xF) * 5).into()
+                let offset = (self.v[reg] & 0xF) as u16 * 5;
+                self.i = self.font_address.wrapping_add(offset, self.mask());
             },
             Load(reg) => {
-                for r in 0u8..((reg as u8) + 1) {
-                    let addr = self.i.offset(r as u16);
-                    let reg: VRegister = r.try_into()?;
-                    self.v[reg] = self.memory
-                        .get_byte(addr)?;
-                }
+                let n = reg as usize + 1;
+                let bytes = self.read_wrapping(self.i, n)?;
+                self.v[..n].copy_from_slice(&bytes);
             },
             Store(reg) => {
-                for r in 0u8..((reg as u8) + 1) {
-                    let addr = self.i.offset(r as u16);
-                    let reg: VRegister = r.try_into()?;
-                    self.memory
-                        .set_byte(addr, self.v[reg])?; 
-                }
+                let n = reg as usize + 1;
+                self.check_writable(self.i, n as u16)?;
+                let v = self.v;
+                self.write_wrapping(self.i, &v[..n])?;
             },
             StoreBCD(reg) => {
                 let val = self.v[reg];
-                self.memory.set_byte(self.i, val / 100)?; 
-                self.memory.set_byte(self.i.offset(1), (val / 10) % 10)?; 
-                self.memory.set_byte(self.i.offset(2), val % 10)?;
+                self.check_writable(self.i, 3)?;
+                self.write_wrapping(self.i, &[val / 100, (val / 10) % 10, val % 10])?;
             },
             Draw(regx, regy, n) => {
                 let x = self.v[regx] & (screen::NCOLS as u8 - 1);
-                let mut y = self.v[regy] & (screen::NROWS as u8 - 1);
+                let y = self.v[regy] as usize % self.display.height();
 
-                for offset in 0..(n.into()) {
-                    let addr = self.i.offset(offset);
+                let mut collision = false;
+                let mut collisions = Vec::new();
+                for (offset, yy) in (0..n.into()).zip(y..) {
+                    let addr = self.i.wrapping_add(offset, self.mask());
                     let data = self.memory.get_byte(addr)?;
-                    let mut xx = x;
-
-                    for i in (0u8..8).rev() {
-                        if (1 << i) & data > 0 {
-                            match self.display.flip(xx as usize, y as usize) {
-                                Some(res) => {
-                                    self.v[VRegister::VF] = res as u8
-                                },
-                                None => break
-                            };
-                        }
-                        xx += 1;
-                    }
 
-                    y += 1;
+                    let before = self.display.rows().get(yy).copied().unwrap_or_default();
+                    match self.display.draw_row(x as usize, yy, data) {
+                        Some(hit) => collision |= hit,
+                        None => break
+                    }
+                    if self.sprites.is_some() {
+                        let cleared = before & !self.display.rows()[yy];
+                        // The leftmost pixel is in the MSB.
+                        collisions.extend((0..screen::NCOLS).filter(|xx| cleared << xx >> 63 != 0).map(|xx| (xx, yy)));
+                    }
+                }
+                self.v[VRegister::VF] = collision as u8;
+                if let Some(sprites) = &mut self.sprites {
+                    let height = (n as usize).min(self.display.height() - y);
+                    sprites.push(SpriteDraw { x: x as usize, y, height, collisions });
                 }
 
-                self.display.show();
+                self.draws += 1;
+                self.drew();
+                return Ok(StepOutcome::DrewFrame);
             }
         }
 
-        Ok(())
+        Ok(StepOutcome::Continue)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::platform::Platform;
+    use alloc::string::ToString;
+
+    /// Execute each of `opcodes` in turn, as `Cpu::with_program`.
+    fn run(opcodes: &[u16]) -> Cpu {
+        let mut cpu = Cpu::with_program(opcodes).unwrap();
+        for _ in opcodes {
+            cpu.step().unwrap();
+        }
+        cpu
+    }
+
+    /// Assert the listed V registers, by number, hold the given values.
+    #[track_caller]
+    fn assert_v(cpu: &Cpu, expected: &[(usize, u8)]) {
+        for &(reg, value) in expected {
+            assert_eq!(cpu.v[reg], value, "V{reg:X}");
+        }
+    }
+
+    /// Assert memory from `addr` holds `bytes`.
+    #[track_caller]
+    fn assert_memory(cpu: &Cpu, addr: u16, bytes: &[u8]) {
+        let end = addr as usize + bytes.len();
+        assert_eq!(cpu.memory.read_slice(addr as usize..end).unwrap(), bytes, "memory at {addr:#x}");
+    }
 
     #[test]
     fn test_split_into_nibbles() {
         assert_eq!(split_into_nibbles(0x1234), [0x1, 0x2, 0x3, 0x4]);
         assert_eq!(split_into_nibbles(0xabcd), [0xa, 0xb, 0xc, 0xd]);
     }
+
+    #[test]
+    fn test_arithmetic_flags() {
+        // ADD sets VF on carry.
+        assert_v(&run(&[0x60FF, 0x6102, 0x8014]), &[(0x0, 0x01), (0xF, 1)]);
+        assert_v(&run(&[0x6010, 0x6102, 0x8014]), &[(0x0, 0x12), (0xF, 0)]);
+        // SUB and SUBN set VF when there is no borrow.
+        assert_v(&run(&[0x6005, 0x6103, 0x8015]), &[(0x0, 0x02), (0xF, 1)]);
+        assert_v(&run(&[0x6003, 0x6105, 0x8015]), &[(0x0, 0xFE), (0xF, 0)]);
+        assert_v(&run(&[0x6003, 0x6105, 0x8017]), &[(0x0, 0x02), (0xF, 1)]);
+        assert_v(&run(&[0x6005, 0x6103, 0x8017]), &[(0x0, 0xFE), (0xF, 0)]);
+        // Shifts put the bit shifted out in VF.
+        assert_v(&run(&[0x6081, 0x8006]), &[(0x0, 0x40), (0xF, 1)]);
+        assert_v(&run(&[0x6081, 0x800E]), &[(0x0, 0x02), (0xF, 1)]);
+        assert_v(&run(&[0x6040, 0x800E]), &[(0x0, 0x80), (0xF, 0)]);
+        // ADD with an immediate wraps without touching VF.
+        assert_v(&run(&[0x6FAA, 0x60FF, 0x7002]), &[(0x0, 0x01), (0xF, 0xAA)]);
+        // The flag wins when the result register is VF itself.
+        assert_v(&run(&[0x6FFF, 0x6101, 0x8F14]), &[(0xF, 1)]);
+    }
+
+    #[test]
+    fn test_logic_and_moves() {
+        assert_v(&run(&[0x600C, 0x610A, 0x8011]), &[(0x0, 0x0E)]);
+        assert_v(&run(&[0x600C, 0x610A, 0x8012]), &[(0x0, 0x08)]);
+        assert_v(&run(&[0x600C, 0x610A, 0x8013]), &[(0x0, 0x06)]);
+        assert_v(&run(&[0x610A, 0x8010]), &[(0x0, 0x0A), (0x1, 0x0A)]);
+    }
+
+    #[test]
+    fn test_bcd_and_register_transfers() {
+        let cpu = run(&[0x60FE, 0xA300, 0xF033]);
+        assert_memory(&cpu, 0x300, &[2, 5, 4]);
+        let cpu = run(&[0x6007, 0xA300, 0xF033]);
+        assert_memory(&cpu, 0x300, &[0, 0, 7]);
+
+        let cpu = run(&[0x6001, 0x6102, 0x6203, 0xA300, 0xF255, 0xA300, 0xF365]);
+        assert_memory(&cpu, 0x300, &[1, 2, 3]);
+        assert_v(&cpu, &[(0x0, 1), (0x1, 2), (0x2, 3), (0x3, 0)]);
+    }
+
+    #[test]
+    fn test_skips() {
+        // Each taken skip jumps over the `LD V1, 1` after it.
+        for skip in [0x3005, 0x4006, 0x5020, 0x9030] {
+            let mut cpu = Cpu::with_program(&[0x6005, 0x6205, 0x6306, skip, 0x6101, 0x6102]).unwrap();
+            (0..5).for_each(|_| { cpu.step().unwrap(); });
+            assert_v(&cpu, &[(0x1, 2)]);
+            assert_eq!(cpu.pc, PC_START.offset(12));
+        }
+        for skip in [0x3006, 0x4005, 0x5030, 0x9020] {
+            let cpu = run(&[0x6005, 0x6205, 0x6306, skip, 0x6101]);
+            assert_v(&cpu, &[(0x1, 1)]);
+        }
+
+        let mut cpu = Cpu::with_program(&[0x600A, 0xE09E, 0x6101, 0xE0A1, 0x6102]).unwrap();
+        cpu.press_key(0xA);
+        (0..4).for_each(|_| { cpu.step().unwrap(); });
+        assert_v(&cpu, &[(0x1, 2)]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut a = Cpu::from_program(vec![0x12, 0x34]).unwrap();
+        a.execute(Instruction::LoadImm(VRegister::V3, 0x42)).unwrap();
+        a.execute(Instruction::LoadI(Address(0x300))).unwrap();
+        a.execute(Instruction::StoreBCD(VRegister::V3)).unwrap();
+        a.execute(Instruction::Call(Address(0x400))).unwrap();
+        a.display.flip(3, 5);
+
+        let bytes = a.snapshot().to_bytes().unwrap();
+        let mut b = Cpu::from_program(Vec::new()).unwrap();
+        b.restore(&Snapshot::from_bytes(&bytes).unwrap()).unwrap();
+
+        assert_eq!(a.snapshot(), b.snapshot());
+    }
+
+    #[test]
+    fn test_step_outcomes() {
+        let mut cpu = Cpu::from_program(vec![0x00, 0xE0, 0xF0, 0x0A, 0x00, 0xFD]).unwrap();
+        assert_eq!(cpu.step().unwrap(), StepOutcome::DrewFrame);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::WaitingForKey);
+
+        cpu.press_key(0x1);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::Continue);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::Halted);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::Halted);
+        assert_eq!(cpu.pc, PC_START.offset(6));
+        assert!(cpu.run_frame().unwrap().halted);
+    }
+
+    #[test]
+    fn test_font() {
+        // `LD V0, 2`, `LD F, V0`, `LD V0, [I]`.
+        let mut cpu = Cpu::with_program(&[0x6002, 0xF029, 0xF065]).unwrap();
+        let font: Vec<u8> = (0..(FONT_SIZE + BIG_FONT_SIZE) as u8).collect();
+        cpu.set_font(font, Address(0x50)).unwrap();
+        (0..3).for_each(|_| { cpu.step().unwrap(); });
+        assert_eq!((cpu.i(), cpu.registers()[0]), (Address(0x5A), 10));
+        assert_eq!(cpu.memory.get_byte(Address(0x50 + 239)).unwrap(), 239);
+
+        assert!(matches!(cpu.set_font(vec![0; 100], Address(0)), Err(CpuError::InvalidConfig(_))));
+        assert!(cpu.set_font(vec![0; FONT_SIZE], Address(0xFC0)).is_err());
+        assert_eq!(cpu.font().len(), FONT_SIZE + BIG_FONT_SIZE);
+    }
+
+    #[test]
+    fn test_display_rows() {
+        // `LD V1, 40`, `LD F, V0`, `DRW V0, V1, 5`, then at 50, past the
+        // bottom of a 48 row display, which wraps to row 2.
+        let program = [0x6128, 0xF029, 0xD015, 0x6132, 0xD015, 0x0230];
+        let mut cpu = Cpu::with_program(&program).unwrap();
+        cpu.set_display_rows(48);
+        (0..5).for_each(|_| { cpu.step().unwrap(); });
+        assert_eq!(cpu.screen().rows().len(), 48);
+        assert!(cpu.screen().pixel(0, 44) && !cpu.screen().pixel(0, 45));
+        assert!(cpu.screen().pixel(0, 2));
+        assert!(cpu.decode(0x0230).is_err());
+
+        let snapshot = cpu.snapshot();
+        cpu.set_display_rows(64);
+        assert_eq!(cpu.decode(0x0230).unwrap(), Instruction::ClearScreen);
+        cpu.restore(&snapshot).unwrap();
+        assert_eq!(cpu.screen().height(), 48);
+    }
+
+    #[test]
+    fn test_chip8x() {
+        // `LD V0, 0x35`, `LD V1, 0x14`, `LD V2, 4`, `ADDN V0, V1`, 
+        // `COL V0, V2, 0`, `BGC`, then `SKNP2 V0` over an invalid opcode.
+        let program = [0x6035, 0x6114, 0x6204, 0x5011, 0xB020, 0x02A0, 0xE0F5, 0xFFFF, 0x00FD];
+        let mut cpu = Cpu::with_program(&program).unwrap();
+        assert!(matches!(cpu.decode(0xB020), Ok(Instruction::JumpOffset(_))));
+        cpu.set_chip8x(true);
+        while cpu.step().unwrap() != StepOutcome::Halted {}
+
+        // 3 + 1 and 5 + 4 modulo 8.
+        assert_v(&cpu, &[(0, 0x41)]);
+        let colors = cpu.screen().colors().unwrap();
+        assert_eq!(colors.background(), screen::Rgb(0, 0, 0));
+        // Zones 1 to 5 across and 4 to 5 down, or rows 16 to 23, in green.
+        assert_eq!(colors.foreground(8, 16), screen::Rgb(0, 0xff, 0));
+        assert_eq!(colors.foreground(47, 23), screen::Rgb(0, 0xff, 0));
+        assert_eq!(colors.foreground(48, 23), screen::Rgb(0xff, 0, 0));
+        assert_eq!(colors.foreground(8, 24), screen::Rgb(0xff, 0, 0));
+
+        cpu.set_chip8x(false);
+        assert!(cpu.screen().colors().is_none());
+    }
+
+    #[test]
+    fn test_test_oracle() {
+        let mut cpu = Cpu::with_program(&[0x6001, 0x00F1]).unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.step().is_err());
+
+        cpu.reset().unwrap();
+        cpu.set_test_oracle(true);
+        assert!(cpu.run_frame().unwrap().halted);
+        assert_eq!(cpu.verdict(), Some(Verdict::Pass));
+
+        let mut cpu = Cpu::with_program(&[0x00F2]).unwrap();
+        cpu.set_test_oracle(true);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::Halted);
+        assert_eq!(cpu.verdict(), Some(Verdict::Fail(PC_START)));
+        cpu.reset().unwrap();
+        assert_eq!(cpu.verdict(), None);
+    }
+
+    #[test]
+    fn test_extended_memory() {
+        let mut program = vec![0; 0x2000];
+        program[..6].copy_from_slice(&[0xF0, 0x00, 0x20, 0x00, 0x00, 0xFD]);
+        program[0x1E00] = 0xAB;
+
+        assert!(Cpu::from_program(program.clone()).is_err());
+        let mut cpu = Cpu::with_memory(program, Platform::XoChip.memory()).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.i, Address(0x2000));
+        assert_eq!(cpu.pc, PC_START.offset(4));
+        assert_eq!(cpu.memory.get_byte(cpu.i).unwrap(), 0xAB);
+    }
+
+    #[test]
+    fn test_write_protection() {
+        let mut cpu = Cpu::from_program(vec![0xA2, 0x00, 0xF0, 0x33]).unwrap();
+        cpu.set_write_protection(WriteProtection::Interpreter);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.memory.get_byte(PC_START).unwrap(), 0);
+
+        cpu.reset().unwrap();
+        cpu.set_write_protection(WriteProtection::Program);
+        cpu.step().unwrap();
+        let e = cpu.step().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WriteProtected);
+        assert_eq!(cpu.memory.get_byte(PC_START).unwrap(), 0xA2);
+
+        // Logging carries on past the write without making it.
+        cpu.reset().unwrap();
+        cpu.error_policies().set(ErrorKind::WriteProtected, ErrorPolicy::Log);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.memory.get_byte(PC_START).unwrap(), 0xA2);
+        assert_eq!(cpu.pc, Address(0x204));
+    }
+
+    #[test]
+    fn test_add_i_quirks() {
+        let mut cpu = Cpu::from_program(vec![0xF0, 0x1E, 0xF0, 0x1E]).unwrap();
+        cpu.quirks().add_i_overflow = true;
+        cpu.i = Address(0xFFE);
+        cpu.v[VRegister::V0] = 1;
+        cpu.step().unwrap();
+        assert_eq!((cpu.i, cpu.v[VRegister::VF]), (Address(0xFFF), 0));
+        cpu.step().unwrap();
+        assert_eq!((cpu.i, cpu.v[VRegister::VF]), (Address(0x000), 1));
+
+        cpu.reset().unwrap();
+        cpu.quirks().index_overflow = IndexOverflow::Fault;
+        cpu.i = Address(0xFFF);
+        cpu.v[VRegister::V0] = 1;
+        assert_eq!(cpu.step().unwrap_err().kind(), ErrorKind::InvalidAddress);
+    }
+
+    #[test]
+    fn test_store_load_wrap() {
+        let mut cpu = Cpu::from_program(vec![0xF2, 0x55, 0xF2, 0x65]).unwrap();
+        cpu.i = Address(0xFFE);
+        cpu.v[..3].copy_from_slice(&[1, 2, 3]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.memory.read_slice(0xFFE..0x1000).unwrap(), vec![1, 2]);
+        assert_eq!(cpu.memory.get_byte(Address(0)).unwrap(), 3);
+
+        cpu.v[..3].copy_from_slice(&[0, 0, 0]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[..3], [1, 2, 3]);
+        assert!(cpu.memory.load_slice(Address(0xFFF), &[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut cpu = Cpu::from_program(vec![0x00, 0xEE]).unwrap();
+        let e = cpu.step().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::StackUnderflow);
+        assert!(e.to_string().starts_with("stack underflow: return with an empty stack at 0x200"));
+    }
+
+    #[test]
+    fn test_state() {
+        // `LD V3, 7`, `LD DT, V3`, `CALL 0x208`, then `LD I, 0x123` there.
+        let mut cpu = Cpu::with_program(&[0x6307, 0xF315, 0x2208, 0x0000, 0xA123]).unwrap();
+        (0..4).for_each(|_| { cpu.step().unwrap(); });
+
+        let state = cpu.state();
+        assert_eq!((state.v(VRegister::V3), state.dt(), state.st()), (7, 7, 0));
+        assert_eq!((state.pc(), state.i(), state.sp()), (Address(0x20A), Address(0x123), 1));
+        assert_eq!(state.stack(), &[Address(0x206)]);
+        assert_eq!(state.to_string(), cpu.to_string());
+    }
+
+    #[test]
+    fn test_setters() {
+        // `ADD V0, V1`, then `LD V2, DT` at 0x300.
+        let mut cpu = Cpu::with_program(&[0x8014]).unwrap();
+        cpu.set_v(VRegister::V0, 0xF0);
+        cpu.set_v(VRegister::V1, 0x20);
+        cpu.step().unwrap();
+        assert_eq!(cpu.state().registers()[..2], [0x10, 0x20]);
+        assert_eq!(cpu.state().v(VRegister::VF), 1);
+
+        cpu.memory.load_slice(Address(0x300), &[0xF2, 0x07]).unwrap();
+        cpu.set_pc(Address(0x300));
+        cpu.set_i(Address(0x123));
+        cpu.set_dt(9);
+        cpu.set_st(3);
+        cpu.step().unwrap();
+        let state = cpu.state();
+        assert_eq!((state.pc(), state.i(), state.v(VRegister::V2), state.st()), (Address(0x302), Address(0x123), 9, 3));
+    }
+
+    #[test]
+    fn test_instruction_history() {
+        // `LD V0, 5`, `ADD V0, 1` 299 times, then a return with an empty stack.
+        let mut program = vec![0x60, 0x05];
+        (0..299).for_each(|_| program.extend([0x70, 0x01]));
+        program.extend([0x00, 0xEE]);
+        let mut cpu = Cpu::from_program(program).unwrap();
+        let e = loop {
+            if let Err(e) = cpu.step() {
+                break e;
+            }
+        };
+
+        let CpuError::Fault(_, ctx) = &e else { panic!("{e:?}") };
+        assert_eq!(ctx.backtrace.len(), HISTORY_SIZE);
+        assert_eq!(ctx.backtrace[HISTORY_SIZE - 2].changed, vec![(VRegister::V0, 0x30)]);
+        assert!(ctx.backtrace.last().unwrap().changed.is_empty());
+        let message = e.to_string();
+        assert!(message.contains("0x456: 7001  ADD V0, 1  ; V0 = 0x30"));
+        assert_eq!(message.lines().count(), BACKTRACE_SIZE + 2);
+    }
+
+    #[test]
+    fn test_self_modify() {
+        use alloc::sync::Arc;
+        use core::sync::atomic::{AtomicU16, Ordering};
+
+        let modified = Arc::new(AtomicU16::new(0));
+        let m = modified.clone();
+        let mut cpu = Cpu::from_program(vec![0xA3, 0x00, 0xF0, 0x33, 0xA2, 0x00, 0xF0, 0x55]).unwrap();
+        cpu.on_self_modify(move |addr| m.store(addr.0, Ordering::SeqCst));
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(modified.load(Ordering::SeqCst), 0);
+        cpu.step().unwrap();
+        assert_eq!(modified.load(Ordering::SeqCst), 0x200);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_dump_core() {
+        let mut cpu = Cpu::from_program(vec![0x22, 0x04, 0x00, 0x00, 0x60, 0x05, 0x60, 0x06]).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        let path = std::env::temp_dir().join(format!("chip8-core-{}", std::process::id()));
+        cpu.dump_core(&path).unwrap();
+        let dump = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(dump.contains("PC = 0x206"));
+        assert!(dump.contains("#0 return to 0x202"));
+        assert!(dump.contains("=> 0x206: 6006  LD V0, 6"));
+        assert!(dump.contains("recent instructions:\n    0x200: 2204  CALL 0x204\n    0x204: 6005  LD V0, 5  ; V0 = 0x05\n"));
+        assert!(dump.contains("00000200: 2204"));
+    }
+
+    #[test]
+    fn test_decode_cache_invalidation() {
+        let program = vec![0x60, 0x60, 0x61, 0x2A, 0xA2, 0x00, 0xF1, 0x55, 0x12, 0x00];
+        let mut cpu = Cpu::from_program(program).unwrap();
+        for _ in 0..5 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.decoded[0x200], None);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[VRegister::V0], 0x2A);
+    }
+
+    #[test]
+    fn test_jit_matches_interpreter() {
+        let programs = [
+            vec![0x60, 0x05, 0xF0, 0x15, 0x00, 0xE0, 0x70, 0x01, 0x12, 0x06],
+            // Rewrites the instruction at 0x20A on every pass through the loop.
+            vec![
+                0x60, 0x60, 0x61, 0x02, 0xA2, 0x0A, 0xF1, 0x55, 0x12, 0x0A, 
+                0x60, 0x07, 0x72, 0x01, 0x12, 0x00
+            ]
+        ];
+
+        for program in programs {
+            let mut interpreted = Cpu::from_program(program.clone()).unwrap();
+            let mut jitted = Cpu::from_program(program).unwrap();
+            jitted.set_jit(true);
+
+            for _ in 0..5 {
+                let a = interpreted.run_frame().unwrap();
+                let b = jitted.run_frame().unwrap();
+                assert_eq!(a, b);
+                assert_eq!(interpreted.snapshot(), jitted.snapshot());
+            }
+        }
+    }
+
+    #[test]
+    fn test_jit_records_history() {
+        use alloc::sync::Arc;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        // Rewrites the instruction at 0x20A on every pass through the loop.
+        let program = vec![
+            0x60, 0x60, 0x61, 0x02, 0xA2, 0x0A, 0xF1, 0x55, 0x12, 0x0A,
+            0x60, 0x07, 0x72, 0x01, 0x12, 0x00
+        ];
+        let modified = Arc::new(AtomicUsize::new(0));
+        let m = modified.clone();
+        let mut cpu = Cpu::from_program(program).unwrap();
+        cpu.set_jit(true);
+        cpu.set_ips(70 * FRAMES_PER_SECOND);
+        cpu.on_self_modify(move |_| { m.fetch_add(1, Ordering::SeqCst); });
+        cpu.run_frame().unwrap();
+        let passes = usize::from(cpu.v[VRegister::V2]);
+        assert!(passes > 1);
+        assert_eq!(modified.load(Ordering::SeqCst), passes);
+
+        // `LD V0, 5`, `ADD V0, 1`, then a return with an empty stack.
+        let mut cpu = Cpu::with_program(&[0x6005, 0x7001, 0x00EE]).unwrap();
+        cpu.set_jit(true);
+        let e = cpu.run_frame().unwrap_err();
+        let CpuError::Fault(_, ctx) = &e else { panic!("{e:?}") };
+        let backtrace: Vec<_> = ctx.backtrace.iter().map(|executed| executed.pc.0).collect();
+        assert_eq!(backtrace, [0x200, 0x202, 0x204]);
+        assert_eq!(ctx.backtrace[1].changed, vec![(VRegister::V0, 6)]);
+    }
+
+    #[test]
+    fn test_wait_key_hooks() {
+        use alloc::sync::Arc;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        let waits = Arc::new(AtomicUsize::new(0));
+        let w = waits.clone();
+        let mut cpu = Cpu::from_program(vec![0xF3, 0x0A]).unwrap();
+        cpu.on_key_wait(move |_| { w.fetch_add(1, Ordering::SeqCst); });
+
+        for _ in 0..3 {
+            let instruction = cpu.fetch().unwrap();
+            cpu.execute(cpu.decode(instruction).unwrap()).unwrap();
+            assert_eq!(cpu.pc, PC_START);
+        }
+        assert_eq!(waits.load(Ordering::SeqCst), 1);
+
+        cpu.press_key(0xB);
+        let instruction = cpu.fetch().unwrap();
+        cpu.execute(cpu.decode(instruction).unwrap()).unwrap();
+        assert_eq!(cpu.pc, PC_START + PC_INCREMENT);
+        assert_eq!(cpu.v[VRegister::V3], 0xB);
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut cpu = Cpu::from_program(vec![
+            0x60, 0x05, 0xF0, 0x15, 0x00, 0xE0, 0x70, 0x01, 0x12, 0x06
+        ]).unwrap();
+        cpu.set_ips(3 * FRAMES_PER_SECOND);
+
+        let frame = cpu.run_frame().unwrap();
+        assert_eq!(frame, Frame { instructions: 3, drawn: true, ..Frame::default() });
+        assert_eq!(cpu.dt, 4);
+
+        let frame = cpu.run_frame().unwrap();
+        assert_eq!(frame, Frame { instructions: 3, ..Frame::default() });
+        assert_eq!(cpu.dt, 3);
+    }
+
+    #[test]
+    fn test_reset_clears_machine() {
+        let mut cpu = Cpu::from_program(vec![0x00, 0xE0]).unwrap();
+        cpu.press_key(0xA);
+        cpu.dt = 30;
+        cpu.st = 5;
+        cpu.display.flip(1, 1);
+        cpu.take_drawn();
+
+        cpu.reset().unwrap();
+        assert_eq!((cpu.dt, cpu.st), (0, 0));
+        assert!(!cpu.keys[0xA]);
+        assert!(!cpu.screen().pixel(1, 1));
+        assert!(cpu.take_drawn());
+    }
+
+    #[test]
+    fn test_load_address() {
+        let mut cpu = Cpu::from_program(vec![0x60, 0x2A]).unwrap();
+        cpu.set_load_address(Address(0x600)).unwrap();
+        assert_eq!(cpu.pc, Address(0x600));
+        assert_eq!(cpu.memory.get_byte(Address(0x600)).unwrap(), 0x60);
+        assert_eq!(cpu.memory.get_byte(PC_START).unwrap(), 0);
+
+        cpu.step().unwrap();
+        cpu.reset().unwrap();
+        assert_eq!((cpu.pc, cpu.v[VRegister::V0]), (Address(0x600), 0));
+        assert!(cpu.set_load_address(Address(0xfff)).is_err());
+        assert_eq!(cpu.load_address(), Address(0x600));
+    }
+
+    #[test]
+    fn test_relocations() {
+        // `JP 0x204`, an unreachable `LD V0, 1`, then `LD I, 0x204`.
+        let mut cpu = Cpu::from_program(vec![0x12, 0x04, 0x60, 0x01, 0xA2, 0x04]).unwrap();
+        cpu.set_relocations(Relocations::new(vec![0, 4])).unwrap();
+        cpu.set_load_address(Address(0x400)).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!((cpu.pc, cpu.i, cpu.v[VRegister::V0]), (Address(0x406), Address(0x404), 0));
+
+        // The original bytes are kept, to relocate again.
+        cpu.set_load_address(PC_START).unwrap();
+        assert_eq!(cpu.memory.get_byte(Address(0x205)).unwrap(), 0x04);
+        assert!(cpu.load_program(vec![0x00, 0xE0]).is_err());
+        assert_eq!(cpu.load_address(), PC_START);
+    }
+
+    #[test]
+    fn test_idle_loops() {
+        // LD V0, DT; SE V0, 0; JP 0x200; then CLS once the timer runs out.
+        let mut cpu = Cpu::from_program(vec![
+            0xF0, 0x07, 0x30, 0x00, 0x12, 0x00, 0x00, 0xE0
+        ]).unwrap();
+        cpu.set_ips(3 * FRAMES_PER_SECOND);
+        cpu.dt = 2;
+
+        let frame = cpu.run_frame().unwrap();
+        assert_eq!(frame, Frame { instructions: 3, idle: true, ..Frame::default() });
+        cpu.run_frame().unwrap();
+        let frame = cpu.run_frame().unwrap();
+        assert!(frame.drawn && !frame.idle);
+
+        // SKP V1; JP 0x200 waits for key 0, but a loop that does work isn't idle.
+        let mut cpu = Cpu::from_program(vec![0xE1, 0x9E, 0x12, 0x00]).unwrap();
+        assert!(cpu.run_frame().unwrap().idle);
+        let mut cpu = Cpu::from_program(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        assert!(!cpu.run_frame().unwrap().idle);
+    }
+
+    #[test]
+    fn test_error_policies() {
+        let mut cpu = Cpu::from_program(vec![0xFF, 0xFF, 0x60, 0x01]).unwrap();
+        let e = cpu.step().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInstruction);
+        assert!(e.to_string().starts_with("invalid instruction: ffff at 0x200"));
+
+        cpu.reset().unwrap();
+        cpu.error_policies().set(ErrorKind::InvalidInstruction, ErrorPolicy::Skip);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[VRegister::V0], 1);
+
+        cpu.reset().unwrap();
+        cpu.error_policies().set_default(ErrorPolicy::Trap);
+        cpu.error_policies().set(ErrorKind::InvalidInstruction, ErrorPolicy::Trap);
+        cpu.set_ips(2 * FRAMES_PER_SECOND);
+        let frame = cpu.run_frame().unwrap();
+        assert!(frame.trapped && cpu.is_paused());
+        assert_eq!(frame.instructions, 1);
+        assert_eq!(cpu.pc, PC_START + PC_INCREMENT);
+    }
+
+    #[test]
+    fn test_seeded_random_is_reproducible() {
+        let mut a = Cpu::from_program(Vec::new()).unwrap();
+        let mut b = Cpu::from_program(Vec::new()).unwrap();
+        a.set_seed(0x5eed);
+        b.set_seed(0x5eed);
+
+        for _ in 0..32 {
+            a.execute(Instruction::AndRandom(VRegister::V0, 0xFF)).unwrap();
+            b.execute(Instruction::AndRandom(VRegister::V0, 0xFF)).unwrap();
+            assert_eq!(a.v[VRegister::V0], b.v[VRegister::V0]);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,193 @@
+//! An experimental execution backend that translates straight-line runs of 
+//! instructions (basic blocks) into closures the first time they are reached,
+//! then runs the whole block per dispatch without fetching or decoding.
+//!
+//! Code the program overwrites after running it falls back to the 
+//! interpreter for good. Translated instructions are recorded in the history
+//! like fetched ones, but observers are not notified of them, and writes made
+//! directly through `Cpu::memory` are not seen.
+
+use super::{Cpu, CpuError, StepOutcome};
+use crate::{address::Address, isa::Instruction};
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+
+/// The longest run of instructions translated into a single block.
+const MAX_BLOCK_LEN: usize = 64;
+
+type Op = Box<dyn Fn(&mut Cpu) -> Result<StepOutcome, CpuError> + Send + Sync>;
+
+struct Translated {
+    addr: Address,
+    opcode: u16,
+    instruction: Instruction,
+    op: Op
+}
+
+pub(super) struct Jit {
+    /// Translated blocks by start address.
+    blocks: Vec<Option<Arc<[Translated]>>>,
+    /// Addresses covered by some translated block.
+    covered: Vec<bool>,
+    /// Addresses overwritten after being translated, which are interpreted 
+    /// from then on.
+    interpreted: Vec<bool>,
+    /// Set when a write discards the translated blocks, so that the running
+    /// block stops before executing stale code.
+    invalidated: bool
+}
+
+impl Jit {
+    pub(super) fn new(len: usize) -> Self {
+        Self { 
+            blocks: vec![None; len], 
+            covered: vec![false; len], 
+            interpreted: vec![false; len], 
+            invalidated: false 
+        }
+    }
+
+    /// Forget everything, e.g. because a different program was loaded.
+    pub(super) fn clear(&mut self) {
+        self.blocks.fill(None);
+        self.covered.fill(false);
+        self.interpreted.fill(false);
+        self.invalidated = true;
+    }
+
+    /// Note a write of `len` bytes at `addr`. Writing over translated code 
+    /// discards every block and leaves the overwritten bytes to the 
+    /// interpreter.
+    pub(super) fn invalidate(&mut self, addr: Address, len: usize, mask: u16) {
+        let written = || (0..len as u16).map(|off| addr.wrapping_add(off, mask).0 as usize);
+        if !written().any(|a| self.covered[a]) {
+            return;
+        }
+
+        written().for_each(|a| self.interpreted[a] = true);
+        self.blocks.fill(None);
+        self.covered.fill(false);
+        self.invalidated = true;
+    }
+}
+
+/// Whether `instruction` may change the PC (or stop execution), which ends a
+/// block.
+fn ends_block(instruction: Instruction) -> bool {
+    use Instruction::*;
+    matches!(instruction, 
+        Jump(_) | JumpOffset(_) | Call(_) | Return | Exit | Pass | Fail | LoadLongI | WaitKey(_) | 
+        SkipIfEqualImm(..) | SkipIfNotEqualImm(..) | SkipIfEqual(..) | SkipIfNotEqual(..) | 
+        SkipIfKey(_) | SkipIfNotKey(_) | SkipIfKey2(_) | SkipIfNotKey2(_)
+    )
+}
+
+/// Translate a single instruction. The most common simple instructions get a 
+/// specialised closure; everything else defers to the interpreter's 
+/// implementation, which still skips fetching and decoding.
+fn compile(instruction: Instruction) -> Op {
+    use Instruction::*;
+    match instruction {
+        LoadImm(reg, imm) => Box::new(move |cpu| {
+            cpu.v[reg] = imm;
+            Ok(StepOutcome::Continue)
+        }),
+        AddImm(reg, imm) => Box::new(move |cpu| {
+            cpu.v[reg] = cpu.v[reg].wrapping_add(imm);
+            Ok(StepOutcome::Continue)
+        }),
+        Move(regx, regy) => Box::new(move |cpu| {
+            cpu.v[regx] = cpu.v[regy];
+            Ok(StepOutcome::Continue)
+        }),
+        LoadI(addr) => Box::new(move |cpu| {
+            cpu.i = addr;
+            Ok(StepOutcome::Continue)
+        }),
+        _ => Box::new(move |cpu| cpu.execute_instruction(instruction))
+    }
+}
+
+impl Cpu {
+    /// Run up to `max` instructions of the block starting at the PC, 
+    /// translating it first if needed. Returns how many instructions ran, not
+    /// counting one that halted, and the outcome of the last one.
+    pub(super) fn run_block(&mut self, max: u32) -> Result<(u32, StepOutcome), CpuError> {
+        if self.halted {
+            return Ok((0, StepOutcome::Halted));
+        }
+
+        let Some(block) = self.block_at(self.pc) else {
+            return self.step().map(|o| ((o != StepOutcome::Halted) as u32, o));
+        };
+
+        if let Some(jit) = self.jit.as_mut() {
+            jit.invalidated = false;
+        }
+
+        let mut count = 0;
+        for t in block.iter().take(max as usize) {
+            self.pc = t.addr;
+            self.record_fetch(t.opcode);
+            self.advance_pc();
+            count += 1;
+
+            match (t.op)(self).and_then(|outcome| self.after_instruction().map(|()| outcome)) {
+                Err(e) => {
+                    let context = self.error_context(t.addr, Some(t.opcode), Some(t.instruction));
+                    let outcome = self.handle_error(CpuError::Fault(Box::new(e), Box::new(context)), t.addr)?;
+                    return Ok((count, outcome));
+                },
+                Ok(StepOutcome::Halted) => return Ok((count - 1, StepOutcome::Halted)),
+                Ok(outcome @ (StepOutcome::WaitingForKey | StepOutcome::Idle)) => return Ok((count, outcome)),
+                Ok(_) => ()
+            }
+
+            if self.paused || self.jit.as_ref().is_some_and(|jit| jit.invalidated) {
+                break;
+            }
+        }
+
+        Ok((count, StepOutcome::Continue))
+    }
+
+    fn block_at(&mut self, start: Address) -> Option<Arc<[Translated]>> {
+        let jit = self.jit.as_ref()?;
+        if let Some(block) = &jit.blocks[start.0 as usize] {
+            return Some(block.clone());
+        }
+
+        let mask = self.mask();
+        let mut block = Vec::new();
+        let mut addr = start;
+        while block.len() < MAX_BLOCK_LEN {
+            let next = addr.wrapping_add(1, mask);
+            if jit.interpreted[addr.0 as usize] || jit.interpreted[next.0 as usize] {
+                break;
+            }
+
+            let Ok(opcode) = self.memory.get_short(addr) else { break };
+            let Ok(instruction) = self.decode(opcode) else { break };
+            block.push(Translated { addr, opcode, instruction, op: compile(instruction) });
+
+            addr = addr.wrapping_add(2, mask);
+            if ends_block(instruction) {
+                break;
+            }
+        }
+
+        if block.is_empty() {
+            return None;
+        }
+
+        let block: Arc<[Translated]> = block.into();
+        for t in block.iter() {
+            let bytes = [t.addr.0 as usize, t.addr.wrapping_add(1, mask).0 as usize];
+            for a in bytes {
+                self.jit.as_mut()?.covered[a] = true;
+            }
+        }
+        self.jit.as_mut()?.blocks[start.0 as usize] = Some(block.clone());
+
+        Some(block)
+    }
+}
@@ -0,0 +1,27 @@
+use crate::screen::{Screen, NCOLS};
+use embedded_graphics_core::{
+    draw_target::DrawTarget, geometry::{Point, Size}, pixelcolor::PixelColor,
+    primitives::Rectangle
+};
+
+/// Draw the display onto any embedded-graphics target, e.g. an SSD1306 or 
+/// ST7789 driver. Each CHIP-8 pixel becomes a `scale` x `scale` block in the 
+/// top left corner of the target, so a 128x64 OLED is filled exactly with a 
+/// scale of 2.
+pub fn present<D, C>(screen: &Screen, target: &mut D, scale: u32, on: C, off: C) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor
+{
+    let scale = scale.max(1);
+    let size = Size::new(NCOLS as u32 * scale, screen.height() as u32 * scale);
+    let area = Rectangle::new(Point::zero(), size);
+    let colors = (0..size.height).flat_map(move |y| (0..size.width).map(move |x| {
+        match screen.pixel((x / scale) as usize, (y / scale) as usize) {
+            true => on,
+            false => off
+        }
+    }));
+
+    target.fill_contiguous(&area, colors)
+}
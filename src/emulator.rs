@@ -0,0 +1,849 @@
+use crate::{
+    cpu::{Cpu, CpuError, Frame, SpriteDraw, StepOutcome, FRAMES_PER_SECOND}, memory::PAGE_SIZE, metrics::Metrics, pacing::{IntervalStats, Intervals},
+    overlay::Registers, replay::{KeyEvent, Replay, Script}, rewind::Rewind, screen::Screen,
+    cheat::{Freeze, Target}
+};
+use std::{
+    collections::BTreeSet, fmt::{self, Display, Formatter}, path::{Path, PathBuf}, sync::{Arc, mpsc::{self, Receiver, Sender, TryRecvError}}, 
+    thread::{self, JoinHandle}, time::{Duration, Instant}
+};
+
+/// Time between 60Hz frames.
+pub const FRAME_DURATION: Duration = Duration::from_micros(1_000_000 / FRAMES_PER_SECOND as u64);
+/// How close to a deadline the pacer stops sleeping and starts spinning, since
+/// the OS may oversleep by about this much.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(1);
+/// How often `Event::Stats` is sent.
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+/// How far behind schedule the loop may fall before it gives up running the
+/// missed frames, e.g. after the host was suspended or under heavy load.
+const MAX_LAG: Duration = Duration::from_millis(100);
+/// Number of frames between rewind snapshots.
+const REWIND_INTERVAL: u32 = 4;
+/// Number of snapshots kept for rewinding (roughly 40 seconds).
+const REWIND_CAPACITY: usize = 600;
+/// In turbo mode, only every this many frames is presented.
+const TURBO_FRAME_SKIP: u32 = 8;
+/// The speed hotkeys step through powers of two up to this many doublings or
+/// halvings of the configured speed.
+const MAX_SPEED_SHIFT: i32 = 3;
+/// How many times faster than the current speed fast-forward runs.
+const FAST_FORWARD: f64 = 4.0;
+
+/// Control messages sent from the frontend to the emulation thread.
+#[derive(Debug, Clone)]
+pub enum Command {
+    KeyDown(u8),
+    KeyUp(u8),
+    /// Change the emulation speed, in instructions per second.
+    SetIps(u32),
+    /// Halve the emulation speed, down to 1/8x.
+    SlowDown,
+    /// Double the emulation speed, up to 8x.
+    SpeedUp,
+    /// Start or stop running at `FAST_FORWARD` times the current speed.
+    FastForward(bool),
+    /// Restart the program as if it had just been loaded, unpausing it.
+    Reset,
+    LoadRom(PathBuf),
+    /// Like `LoadRom`, with the program's bytes rather than its path.
+    LoadProgram(Vec<u8>),
+    SaveState(PathBuf),
+    LoadState(PathBuf),
+    /// Step back to the previous rewind snapshot.
+    Rewind,
+    Pause,
+    Resume,
+    TogglePause,
+    /// The terminal went into the background, or came back. Going into it
+    /// pauses the machine if it's running, and coming back resumes it only if
+    /// going into it paused it, so a game already paused or trapped stays so.
+    Background(bool),
+    /// Pause, if running, and run one instruction.
+    Step,
+    /// Send the machine as it is between frames back on the channel, for
+    /// answering queries from other threads.
+    Inspect(Sender<Inspection>),
+    /// Toggle turbo mode, which runs frames back to back instead of at 60Hz 
+    /// and only presents every `TURBO_FRAME_SKIP`th one.
+    ToggleTurbo,
+    /// Start or stop sending `Event::Registers` after every frame.
+    WatchRegisters(bool),
+    /// Start or stop sending `Event::Memory` after every frame.
+    WatchMemory(bool),
+    /// Start or stop sending `Event::Sprites` after every frame.
+    WatchSprites(bool),
+    /// Start or stop sending `Event::Keys` after frames with key presses.
+    WatchKeys(bool),
+    /// Hold a byte of memory or a register at a value, as a cheat.
+    Freeze(Freeze),
+    Unfreeze(Target),
+    /// Write a value to a byte of memory or a register once, as a cheat.
+    Poke(Target, u8),
+    /// Whether the timers make up for frames skipped when the loop falls 
+    /// too far behind, as they do by default. Deterministic runs turn this
+    /// off so the timers depend only on the frames run.
+    CatchUpTimers(bool),
+    Quit
+}
+
+/// Messages sent from the emulation thread back to the frontend.
+pub enum Event {
+    /// The display changed and should be presented.
+    Frame(Box<Screen>),
+    /// Measured emulation speed, sent every `STATS_INTERVAL`.
+    Stats(Stats),
+    /// The registers at the end of a frame, while watched.
+    Registers(Registers),
+    /// The memory pages written over a frame while memory is watched, as
+    /// `(index, bytes)`. The first after watching starts has every page.
+    Memory(Vec<(usize, Vec<u8>)>),
+    /// The sprites drawn over a frame, while watched.
+    Sprites(Vec<SpriteDraw>),
+    /// The keys pressed before a frame, from the keyboard or a replay, while
+    /// watched.
+    Keys(Vec<u8>),
+    /// Something failed that doesn't stop the game, for the status line.
+    Notice(String),
+    /// The emulation thread has stopped, either because it was asked to quit
+    /// or because the program faulted.
+    Stopped(Result<(), CpuError>)
+}
+
+/// The machine as `Command::Inspect` found it.
+#[derive(Clone)]
+pub struct Inspection {
+    pub registers: Registers,
+    pub memory: Vec<u8>,
+    pub screen: Box<Screen>,
+    pub paused: bool
+}
+
+/// How fast the emulation thread actually ran over the last `STATS_INTERVAL`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    /// Frames run per second, which should be 60 unless paused or in turbo
+    /// mode.
+    pub fps: u32,
+    /// Instructions executed per second.
+    pub ips: u32,
+    /// Frames sent to the frontend per second. Only frames that changed the 
+    /// display are sent, and turbo mode skips most of them.
+    pub rendered: u32,
+    pub paused: bool,
+    pub turbo: bool,
+    /// The multiplier applied to the configured speed by the speed hotkeys 
+    /// and fast-forward.
+    pub speed: f64,
+    /// The time between frames, which is when the timers tick. Frames run
+    /// in turbo mode aren't counted.
+    pub ticks: IntervalStats,
+    /// How often the timers actually counted down, which should be 60 unless
+    /// paused or in turbo mode. This includes the ticks caught up.
+    pub timer_hz: u32
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            fps: 0, ips: 0, rendered: 0, paused: false, turbo: false, speed: 1.0, ticks: IntervalStats::default(),
+            timer_hz: 0
+        }
+    }
+}
+
+/// Totals for a whole run, reported when it ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunStats {
+    pub instructions: u64,
+    pub wall_time: Duration,
+    /// `DRW` instructions executed.
+    pub draws: u64,
+    /// Frames sent to the frontend. Headless runs present none.
+    pub presented: u64,
+    /// Keys pressed, whether on the keyboard, by a replay, or over the network.
+    pub keys: u64,
+    pub max_stack_depth: usize,
+    /// The time between frames, as in `Stats::ticks`. Headless runs don't
+    /// wait between frames, so they have none.
+    pub ticks: IntervalStats,
+    /// Timer ticks made up for frames skipped after falling behind.
+    pub caught_up: u64
+}
+
+impl RunStats {
+    fn record_frame(&mut self, frame: &Frame, cpu: &Cpu) {
+        self.instructions += u64::from(frame.instructions);
+        self.draws += u64::from(frame.draws);
+        self.max_stack_depth = self.max_stack_depth.max(cpu.max_stack_depth());
+    }
+
+    /// Instructions executed per second of wall time.
+    pub fn ips(&self) -> f64 {
+        match self.wall_time.as_secs_f64() {
+            0.0 => 0.0,
+            secs => self.instructions as f64 / secs
+        }
+    }
+}
+
+impl Display for RunStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "instructions:     {}", self.instructions)?;
+        writeln!(f, "wall time:        {:.2}s", self.wall_time.as_secs_f64())?;
+        writeln!(f, "effective IPS:    {:.0}", self.ips())?;
+        writeln!(f, "draw calls:       {}", self.draws)?;
+        writeln!(f, "frames presented: {}", self.presented)?;
+        writeln!(f, "keys pressed:     {}", self.keys)?;
+        write!(f, "max stack depth:  {}", self.max_stack_depth)
+    }
+}
+
+/// Runs a `Cpu` on its own thread so that presenting frames never stalls the 
+/// instruction loop. The frontend drives it purely through `Command`s and 
+/// `Event`s.
+pub struct Emulator {
+    commands: Sender<Command>,
+    events: Receiver<Event>,
+    thread: Option<JoinHandle<RunStats>>
+}
+
+impl Emulator {
+    /// Run `cpu` in 60Hz frames at its configured instructions per second,
+    /// playing back and recording input through `script` and counting what
+    /// it does in `metrics`. If the program faults, a core dump is written to
+    /// `core`.
+    pub fn spawn(cpu: Cpu, core: PathBuf, script: Script, metrics: Option<Arc<Metrics>>) -> Self {
+        let (commands, command_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+
+        let _ = event_tx.send(Event::Frame(Box::new(cpu.screen().clone())));
+        let frames = event_tx.clone();
+
+        let thread = thread::spawn(move || {
+            let mut stats = RunStats::default();
+            let started = Instant::now();
+            let result = run(cpu, command_rx, frames, &core, script, metrics, &mut stats);
+            stats.wall_time = started.elapsed();
+            let _ = event_tx.send(Event::Stopped(result));
+            stats
+        });
+
+        Self { commands, events, thread: Some(thread) }
+    }
+
+    pub fn send(&self, command: Command) {
+        // If the thread has stopped, the frontend learns so through `Stopped`.
+        let _ = self.commands.send(command);
+    }
+
+    /// A handle for sending commands from other threads.
+    pub fn commands(&self) -> Sender<Command> {
+        self.commands.clone()
+    }
+
+    pub fn try_recv(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    /// Stop the emulation thread if it's still running, and return the 
+    /// totals for the run.
+    pub fn finish(mut self) -> RunStats {
+        self.send(Command::Quit);
+        self.thread.take().and_then(|thread| thread.join().ok()).unwrap_or_default()
+    }
+}
+
+impl Drop for Emulator {
+    fn drop(&mut self) {
+        self.send(Command::Quit);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(
+    mut cpu: Cpu, commands: Receiver<Command>, events: Sender<Event>, core: &Path, mut script: Script,
+    metrics: Option<Arc<Metrics>>, stats: &mut RunStats
+) -> Result<(), CpuError> {
+    let mut pacer = Pacer::new(FRAME_DURATION);
+    let mut rewind = Rewind::new(REWIND_CAPACITY);
+
+    let mut frame: u32 = 0;
+    // Frames emulated so far, which times recorded and replayed input. Unlike
+    // `frame`, this doesn't count frames that only presented the display.
+    let mut emulated: u64 = 0;
+    let mut turbo = false;
+    let mut watch_registers = false;
+    let mut watch_memory = false;
+    let mut watch_sprites = false;
+    let mut watch_keys = false;
+    // Keys pressed since the last `Event::Keys`, while watched.
+    let mut pressed = Vec::new();
+    let mut catch_up = true;
+    // Whether `Background` paused the machine, so coming back should resume it.
+    let mut backgrounded = false;
+    // Pages written since the last rewind snapshot, when the memory watch
+    // has taken them first.
+    let mut dirty = BTreeSet::new();
+    // The configured speed, and the multiplier the hotkeys apply to it.
+    let mut ips = cpu.ips();
+    let mut speed = Speed::default();
+    // Whether the display changed since the last frame was presented.
+    let mut drawn = false;
+    // Frames run, instructions run, frames sent, and timer ticks caught up 
+    // since the last `Stats`.
+    let (mut frames, mut instructions, mut rendered, mut caught_up) = (0u32, 0u32, 0u32, 0u32);
+    let mut stats_since = Instant::now();
+    // The time between frames over the whole run, and since the last `Stats`.
+    let mut ticks = Intervals::new(Some(FRAME_DURATION));
+    let mut window = Intervals::new(Some(FRAME_DURATION));
+    loop {
+        let mut rewinding = false;
+        let mut ran = false;
+        // The program exited, so this frame is the last one.
+        let mut halted = false;
+        loop {
+            let command = match commands.try_recv() {
+                Ok(command) => command,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(())
+            };
+
+            match command {
+                Command::Quit => return Ok(()),
+                Command::KeyDown(key) => {
+                    cpu.press_key(key);
+                    stats.keys += 1;
+                    script.record(KeyEvent { frame: emulated, key, pressed: true });
+                    if watch_keys {
+                        pressed.push(key);
+                    }
+                },
+                Command::KeyUp(key) => {
+                    cpu.release_key(key);
+                    script.record(KeyEvent { frame: emulated, key, pressed: false });
+                },
+                Command::SetIps(n) => {
+                    ips = n;
+                    cpu.set_ips(speed.apply(ips));
+                },
+                Command::SlowDown => {
+                    speed.shift = (speed.shift - 1).max(-MAX_SPEED_SHIFT);
+                    cpu.set_ips(speed.apply(ips));
+                },
+                Command::SpeedUp => {
+                    speed.shift = (speed.shift + 1).min(MAX_SPEED_SHIFT);
+                    cpu.set_ips(speed.apply(ips));
+                },
+                Command::FastForward(on) => {
+                    speed.fast_forward = on;
+                    cpu.set_ips(speed.apply(ips));
+                },
+                Command::Pause => {
+                    cpu.pause();
+                    backgrounded = false;
+                },
+                Command::Resume => {
+                    cpu.resume();
+                    backgrounded = false;
+                },
+                Command::Background(true) => {
+                    if !cpu.is_paused() {
+                        cpu.pause();
+                        backgrounded = true;
+                    }
+                },
+                Command::Background(false) => {
+                    if std::mem::take(&mut backgrounded) {
+                        cpu.resume();
+                    }
+                },
+                Command::ToggleTurbo => turbo = !turbo,
+                Command::CatchUpTimers(on) => catch_up = on,
+                Command::WatchSprites(on) => {
+                    watch_sprites = on;
+                    cpu.record_sprites(on);
+                },
+                Command::WatchKeys(on) => {
+                    watch_keys = on;
+                    pressed.clear();
+                },
+                // A cheat that doesn't fit this ROM shouldn't end the game.
+                Command::Freeze(freeze) => {
+                    if let Err(e) = cpu.freeze(freeze) {
+                        tracing::warn!("Failed to apply a cheat: {e}");
+                    }
+                },
+                Command::Unfreeze(target) => {
+                    cpu.unfreeze(target);
+                },
+                Command::Poke(target, value) => {
+                    if let Err(e) = cpu.poke(target, value) {
+                        tracing::warn!("Failed to apply a cheat: {e}");
+                    }
+                },
+                Command::WatchMemory(on) => {
+                    watch_memory = on;
+                    if on {
+                        let memory = cpu.memory.to_bytes();
+                        let pages = memory.chunks(PAGE_SIZE).map(<[u8]>::to_vec).enumerate().collect();
+                        let _ = events.send(Event::Memory(pages));
+                    }
+                },
+                Command::WatchRegisters(on) => {
+                    watch_registers = on;
+                    // Show them straight away, even while paused.
+                    if on {
+                        let _ = events.send(Event::Registers(Registers::of(&cpu)));
+                    }
+                },
+                Command::TogglePause => {
+                    backgrounded = false;
+                    if cpu.is_paused() {
+                        cpu.resume();
+                    } else {
+                        cpu.pause();
+                    }
+                },
+                Command::Reset => {
+                    cpu.reset()?;
+                    // A game stuck in a trap should start running again too.
+                    cpu.resume();
+                    rewind.clear();
+                },
+                // Nor should a ROM that's missing or half written, which keeps
+                // the current one running.
+                Command::LoadRom(path) => match cpu.load_rom(&path) {
+                    Ok(()) => rewind.clear(),
+                    Err(e) => {
+                        tracing::warn!("Failed to load {}: {e}", path.display());
+                        let _ = events.send(Event::Notice(format!("failed to load {}: {e}", path.display())));
+                    }
+                },
+                // A bad upload shouldn't end the game.
+                Command::LoadProgram(program) => match cpu.load_program(program) {
+                    Ok(()) => rewind.clear(),
+                    Err(e) => tracing::warn!("Failed to load a program: {e}")
+                },
+                Command::Step => {
+                    cpu.pause();
+                    cpu.step().inspect_err(|_| report_fault(&cpu, core, metrics.as_deref()))?;
+                    if watch_registers {
+                        let _ = events.send(Event::Registers(Registers::of(&cpu)));
+                    }
+                },
+                Command::Inspect(reply) => {
+                    let _ = reply.send(Inspection {
+                        registers: Registers::of(&cpu),
+                        memory: cpu.memory.to_bytes(),
+                        screen: Box::new(cpu.screen().clone()),
+                        paused: cpu.is_paused()
+                    });
+                },
+                // Neither should a full disk or a missing directory.
+                Command::SaveState(path) => {
+                    if let Err(e) = cpu.save_state(&path) {
+                        tracing::warn!("Failed to save state to {}: {e}", path.display());
+                        let _ = events.send(Event::Notice(format!("failed to save state: {e}")));
+                    }
+                },
+                Command::LoadState(path) => {
+                    // A missing or stale state file shouldn't end the game.
+                    if let Err(e) = cpu.load_state(&path) {
+                        tracing::warn!("Failed to load state from {}: {e}", path.display());
+                        let _ = events.send(Event::Notice(format!("failed to load state: {e}")));
+                    }
+                },
+                // Holding the key auto-repeats, stepping further back each time.
+                Command::Rewind => {
+                    if let Some(snapshot) = rewind.step_back() {
+                        cpu.restore(&snapshot)?;
+                    }
+                    rewinding = true;
+                }
+            }
+        }
+
+        if !rewinding && !cpu.is_paused() {
+            frame = frame.wrapping_add(1);
+            if frame.is_multiple_of(REWIND_INTERVAL) {
+                dirty.extend(cpu.take_dirty_pages());
+                let pages: Vec<usize> = std::mem::take(&mut dirty).into_iter().collect();
+                rewind.record(cpu.snapshot(), &pages);
+            }
+
+            ran = true;
+            // Turbo mode doesn't wait for ticks.
+            if turbo {
+                ticks.skip();
+                window.skip();
+            } else {
+                let now = Instant::now();
+                ticks.record(now);
+                window.record(now);
+                stats.ticks = ticks.stats();
+            }
+            let played = script.start_frame(&mut cpu, emulated);
+            stats.keys += played.len() as u64;
+            if watch_keys {
+                pressed.extend(played);
+            }
+            emulated += 1;
+            let summary = cpu.run_frame().inspect_err(|_| report_fault(&cpu, core, metrics.as_deref()))?;
+
+            if let Some(metrics) = &metrics {
+                metrics.record_frame(&summary, &cpu);
+            }
+            stats.record_frame(&summary, &cpu);
+            frames += 1;
+            instructions += summary.instructions;
+            drawn |= summary.drawn;
+            if summary.trapped {
+                tracing::warn!("The program trapped with\n{cpu}");
+            }
+            halted = summary.halted;
+            if watch_registers {
+                let _ = events.send(Event::Registers(Registers::of(&cpu)));
+            }
+            if watch_sprites {
+                let _ = events.send(Event::Sprites(cpu.take_sprites()));
+            }
+            if watch_keys && !pressed.is_empty() {
+                let _ = events.send(Event::Keys(std::mem::take(&mut pressed)));
+            }
+            if watch_memory {
+                let written = cpu.take_dirty_pages();
+                let memory = cpu.memory.to_bytes();
+                let pages = written.iter()
+                    .filter_map(|&page| Some((page, memory.chunks(PAGE_SIZE).nth(page)?.to_vec())))
+                    .collect();
+                let _ = events.send(Event::Memory(pages));
+                dirty.extend(written);
+            }
+        } else {
+            // Don't count the time paused as a slow frame.
+            ticks.skip();
+            window.skip();
+        }
+
+        // Commands like reset and rewind change the display too.
+        drawn |= cpu.take_drawn();
+        let skipped = turbo && ran && !halted && !frame.is_multiple_of(TURBO_FRAME_SKIP);
+        if drawn && !skipped {
+            let _ = events.send(Event::Frame(Box::new(cpu.screen().clone())));
+            drawn = false;
+            rendered += 1;
+            stats.presented += 1;
+        }
+        if halted {
+            return Ok(());
+        }
+
+        let elapsed = stats_since.elapsed();
+        if elapsed >= STATS_INTERVAL {
+            let per_second = |n: u32| (n as f64 / elapsed.as_secs_f64()).round() as u32;
+            if let Some(metrics) = &metrics {
+                metrics.set_ips(per_second(instructions));
+            }
+            let _ = events.send(Event::Stats(Stats {
+                fps: per_second(frames),
+                ips: per_second(instructions),
+                rendered: per_second(rendered),
+                paused: cpu.is_paused(),
+                turbo,
+                speed: speed.multiplier(),
+                ticks: window.stats(),
+                timer_hz: per_second(frames + caught_up)
+            }));
+            window.clear();
+            (frames, instructions, rendered, caught_up) = (0, 0, 0, 0);
+            stats_since = Instant::now();
+        }
+
+        if turbo {
+            // Start from now so normal speed resumes without a burst of frames.
+            pacer.resync();
+        } else {
+            let missed = pacer.wait();
+            // Running the missed frames now would be a burst of speed, but the
+            // timers can still make up for them, so games keep time.
+            if missed > 0 && catch_up && !cpu.is_paused() {
+                // Timers run out after 255 ticks anyway.
+                let missed = missed.min(u8::MAX.into());
+                tracing::debug!(target: "timer", "Fell {missed} frames behind; catching the timers up");
+                (0..missed).for_each(|_| cpu.tick_timers());
+                caught_up += missed;
+                stats.caught_up += u64::from(missed);
+            }
+        }
+    }
+}
+
+/// Count and log the fault `cpu` just stopped with, and write a core dump of
+/// it to `core`.
+fn report_fault(cpu: &Cpu, core: &Path, metrics: Option<&Metrics>) {
+    if let Some(metrics) = metrics {
+        metrics.record_error();
+    }
+    tracing::error!("The program faulted with\n{cpu}");
+    if let Err(e) = cpu.dump_core(core) {
+        tracing::error!("Failed to write core dump to {}: {e}", core.display());
+    }
+}
+
+/// Bounds on a headless run, which otherwise lasts until the program stops.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Instructions to execute.
+    pub cycles: Option<u64>,
+    pub frames: Option<u64>
+}
+
+/// Run `cpu` without presenting anything or waiting between frames, for
+/// tests, fuzzing, and benchmarks, with input from `script` if any. Stops when
+/// a limit is reached, or when the program exits, traps, or waits for a key
+/// that the script will never press. If the program faults, a core dump is
+/// written to `core`.
+pub fn run_headless(cpu: &mut Cpu, limits: Limits, script: &mut Script, core: &Path) -> Result<RunStats, CpuError> {
+    let result = run_limited(cpu, limits, script);
+    if result.is_err() {
+        if let Err(e) = cpu.dump_core(core) {
+            tracing::error!("Failed to write core dump to {}: {e}", core.display());
+        }
+    }
+    result
+}
+
+pub(crate) fn run_limited(cpu: &mut Cpu, limits: Limits, script: &mut Script) -> Result<RunStats, CpuError> {
+    let mut stats = RunStats::default();
+    let started = Instant::now();
+    let mut frames = 0u64;
+    loop {
+        if limits.frames.is_some_and(|n| frames >= n) {
+            tracing::info!("Stopped after {frames} frames");
+            break;
+        }
+        // Finish with single steps rather than overshoot in a whole frame.
+        if let Some(left) = limits.cycles.map(|n| n.saturating_sub(stats.instructions)) {
+            if left <= u64::from(cpu.ips() / FRAMES_PER_SECOND) {
+                for _ in 0..left {
+                    if cpu.step()? == StepOutcome::Halted {
+                        break;
+                    }
+                    stats.instructions += 1;
+                }
+                stats.max_stack_depth = stats.max_stack_depth.max(cpu.max_stack_depth());
+                tracing::info!("Stopped after {} instructions", stats.instructions);
+                break;
+            }
+        }
+
+        stats.keys += script.start_frame(cpu, frames).len() as u64;
+        let summary = cpu.run_frame()?;
+        stats.record_frame(&summary, cpu);
+        frames += 1;
+
+        if summary.halted {
+            tracing::info!("The program exited");
+            break;
+        }
+        if summary.trapped || cpu.is_paused() {
+            tracing::warn!("The program trapped with\n{cpu}");
+            break;
+        }
+        if summary.waiting_for_key && script.replay.as_ref().is_none_or(Replay::is_finished) {
+            tracing::info!("The program is waiting for a key at {:?}", cpu.pc());
+            break;
+        }
+    }
+
+    stats.wall_time = started.elapsed();
+    Ok(stats)
+}
+
+/// The multiplier the speed hotkeys apply to the configured speed.
+#[derive(Debug, Default)]
+struct Speed {
+    /// Doublings, or halvings if negative.
+    shift: i32,
+    fast_forward: bool
+}
+
+impl Speed {
+    fn multiplier(&self) -> f64 {
+        let fast_forward = if self.fast_forward { FAST_FORWARD } else { 1.0 };
+        2f64.powi(self.shift) * fast_forward
+    }
+
+    fn apply(&self, ips: u32) -> u32 {
+        ((ips as f64 * self.multiplier()).round() as u32).max(1)
+    }
+}
+
+/// Paces the loop to a fixed period. Each deadline follows on from the last
+/// rather than from when the wait ended, so oversleeping on one frame is made
+/// up on the next and the average rate doesn't drift.
+struct Pacer {
+    period: Duration,
+    deadline: Instant
+}
+
+impl Pacer {
+    fn new(period: Duration) -> Self {
+        Self { period, deadline: Instant::now() + period }
+    }
+
+    /// Block until the current deadline, then schedule the next one. If the
+    /// loop has fallen more than `MAX_LAG` behind, it starts again from now
+    /// instead, returning the number of periods skipped.
+    fn wait(&mut self) -> u32 {
+        let now = Instant::now();
+        let mut missed = 0;
+        if now > self.deadline + MAX_LAG {
+            let behind = now - self.deadline;
+            missed = (behind.as_nanos() / self.period.as_nanos()).try_into().unwrap_or(u32::MAX);
+            self.deadline = now;
+        }
+
+        // Sleep for most of the wait, then spin for the rest to be precise. 
+        // Parking may return early, so check again after each one.
+        while let Some(sleep) = self.deadline.checked_duration_since(Instant::now() + SPIN_THRESHOLD) {
+            thread::park_timeout(sleep);
+        }
+        while Instant::now() < self.deadline {
+            thread::yield_now();
+        }
+
+        self.deadline += self.period;
+        missed
+    }
+
+    fn resync(&mut self) {
+        self.deadline = Instant::now() + self.period;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{ErrorKind, ErrorPolicy};
+
+    #[test]
+    fn test_pacer_skips_after_falling_behind() {
+        let mut pacer = Pacer::new(FRAME_DURATION);
+        assert_eq!(pacer.wait(), 0);
+        pacer.deadline -= FRAME_DURATION * 30;
+        assert!((29..=31).contains(&pacer.wait()));
+        assert_eq!(pacer.wait(), 0);
+    }
+
+    #[test]
+    fn test_run_headless() {
+        // An endless loop of `ADD V0, 1` and a jump back.
+        let program = vec![0x70, 0x01, 0x12, 0x00];
+        let core = std::env::temp_dir().join(format!("chip8-headless-core-{}", std::process::id()));
+
+        let mut cpu = Cpu::from_program(program.clone()).unwrap();
+        let stats = run_headless(&mut cpu, Limits { cycles: Some(25), frames: None }, &mut Script::default(), &core).unwrap();
+        assert_eq!(cpu.pc().0, 0x202);
+        assert_eq!(stats.instructions, 25);
+
+        let mut cpu = Cpu::from_program(program).unwrap();
+        cpu.set_ips(600);
+        run_headless(&mut cpu, Limits { cycles: None, frames: Some(3) }, &mut Script::default(), &core).unwrap();
+        assert_eq!(cpu.snapshot().v[0], 15);
+        assert!(!core.exists());
+
+        // Waiting for a key the replay presses on the third frame.
+        let replay = Replay::parse("2 7 down").unwrap();
+        let mut script = Script { replay: Some(replay), recorder: None };
+        let mut cpu = Cpu::with_program(&[0xF00A, 0x6100, 0x1202]).unwrap();
+        let stats = run_headless(&mut cpu, Limits { cycles: None, frames: Some(4) }, &mut script, &core).unwrap();
+        assert_eq!(cpu.snapshot().v[0], 7);
+        assert_eq!(stats.keys, 1);
+
+        // `CALL 0x204`, then at 0x204 `CALL 0x208`, `DRW V0, V0, 1`, and a loop.
+        let mut cpu = Cpu::with_program(&[0x2204, 0x0000, 0x2208, 0x0000, 0xD001, 0x7001, 0x120A]).unwrap();
+        let stats = run_headless(&mut cpu, Limits { cycles: Some(100), frames: None }, &mut Script::default(), &core).unwrap();
+        assert_eq!((stats.draws, stats.max_stack_depth, stats.presented), (1, 2, 0));
+        assert!(stats.to_string().contains("max stack depth:  2"));
+    }
+
+    #[test]
+    fn test_background_leaves_paused_games_paused() {
+        let paused = |emulator: &Emulator| {
+            let (reply, machine) = mpsc::channel();
+            emulator.send(Command::Inspect(reply));
+            machine.recv_timeout(Duration::from_secs(2)).unwrap().paused
+        };
+        let core = std::env::temp_dir().join(format!("chip8-background-core-{}", std::process::id()));
+
+        let cpu = Cpu::with_program(&[0x7001, 0x1200]).unwrap();
+        let emulator = Emulator::spawn(cpu, core.clone(), Script::default(), None);
+        emulator.send(Command::Background(true));
+        assert!(paused(&emulator));
+        emulator.send(Command::Background(false));
+        assert!(!paused(&emulator));
+
+        emulator.send(Command::Pause);
+        emulator.send(Command::Background(true));
+        emulator.send(Command::Background(false));
+        assert!(paused(&emulator));
+        emulator.finish();
+
+        // An invalid instruction, trapped.
+        let mut cpu = Cpu::with_program(&[0xFFFF]).unwrap();
+        cpu.error_policies().set(ErrorKind::InvalidInstruction, ErrorPolicy::Trap);
+        let emulator = Emulator::spawn(cpu, core, Script::default(), None);
+        while !paused(&emulator) {}
+        emulator.send(Command::Background(true));
+        emulator.send(Command::Background(false));
+        assert!(paused(&emulator));
+        emulator.finish();
+    }
+
+    #[test]
+    fn test_last_frame_is_presented() {
+        // `DRW V0, V0, 5` then `EXIT`.
+        let cpu = Cpu::with_program(&[0xD005, 0x00FD]).unwrap();
+        let core = std::env::temp_dir().join(format!("chip8-exit-core-{}", std::process::id()));
+        let emulator = Emulator::spawn(cpu, core, Script::default(), None);
+        let mut last = None;
+        loop {
+            match emulator.recv_timeout(Duration::from_secs(2)) {
+                Some(Event::Frame(screen)) => last = Some(screen),
+                Some(Event::Stopped(result)) => break result.unwrap(),
+                Some(_) => (),
+                None => panic!("didn't stop")
+            }
+        }
+        assert!(last.unwrap().pixel(0, 0));
+        emulator.finish();
+    }
+
+    #[test]
+    fn test_failed_loads_and_saves_keep_running() {
+        let core = std::env::temp_dir().join(format!("chip8-missing-rom-core-{}", std::process::id()));
+        let cpu = Cpu::with_program(&[0x7001, 0x1200]).unwrap();
+        let emulator = Emulator::spawn(cpu, core, Script::default(), None);
+        let notice = || loop {
+            match emulator.recv_timeout(Duration::from_secs(2)) {
+                Some(Event::Notice(message)) => break message,
+                Some(Event::Stopped(result)) => panic!("stopped with {result:?}"),
+                Some(_) => (),
+                None => panic!("no notice")
+            }
+        };
+        emulator.send(Command::LoadRom(PathBuf::from("/nonexistent.ch8")));
+        assert!(notice().contains("/nonexistent.ch8"));
+        emulator.send(Command::SaveState(PathBuf::from("/nonexistent/state")));
+        assert!(notice().starts_with("failed to save state"));
+        emulator.finish();
+    }
+}
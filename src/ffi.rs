@@ -0,0 +1,278 @@
+//! A C API for the machine, so frontends in other languages can run it from
+//! the library built with `cargo rustc --lib --release --features ffi
+//! --crate-type cdylib`. `include/chip8.h` declares it.
+//!
+//! Functions that can fail return 0 on success, or the exit code of the
+//! error's kind (see `ErrorKind::exit_code`), with the message left for
+//! `chip8_last_error`. None of them take ownership of the pointers they're
+//! given, except `chip8_destroy`.
+use crate::{cpu::{Cpu, CpuError, StepOutcome}, screen::NCOLS};
+use alloc::{ffi::CString, vec::Vec};
+use core::{ffi::{c_char, c_int, CStr}, ptr, slice};
+use std::{io, path::Path};
+
+/// Bumped whenever a function's signature or meaning changes.
+pub const ABI_VERSION: u32 = 1;
+
+/// Returned by `chip8_step` and `chip8_run_frame` when the display changed.
+pub const DREW: c_int = -1;
+
+pub struct Chip8 {
+    cpu: Cpu,
+    /// The display from the last `chip8_framebuffer`, one byte per pixel.
+    framebuffer: Vec<u8>,
+    /// The message for the last error, or empty.
+    error: CString
+}
+
+/// The `len` bytes at `ptr`, or `None` if it's null. A null `ptr` with no
+/// bytes is an empty buffer, as C callers commonly pass one.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to `len` bytes.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (ptr.is_null(), len) {
+        (_, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(ptr, len))
+    }
+}
+
+impl Chip8 {
+    fn status<T>(&mut self, result: Result<T, CpuError>) -> Result<T, c_int> {
+        result.map_err(|e| {
+            self.error = CString::new(e.to_string().replace('\0', "")).unwrap_or_default();
+            e.kind().exit_code() as c_int
+        })
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// A machine with no program loaded, to be freed with `chip8_destroy`.
+#[no_mangle]
+pub extern "C" fn chip8_create() -> *mut Chip8 {
+    match Cpu::from_program(Vec::new()) {
+        Ok(cpu) => Box::into_raw(Box::new(Chip8 { cpu, framebuffer: Vec::new(), error: CString::default() })),
+        Err(_) => ptr::null_mut()
+    }
+}
+
+/// # Safety
+///
+/// `chip8` must be null or from `chip8_create`, and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Load the `len` bytes at `rom` and start running them from the beginning.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`, and `rom` point to `len` bytes. `rom`
+/// may only be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load(chip8: *mut Chip8, rom: *const u8, len: usize) -> c_int {
+    let chip8 = &mut *chip8;
+    let result = bytes(rom, len)
+        .ok_or_else(|| CpuError::ProgramLoadError(io::Error::new(io::ErrorKind::InvalidInput, "the ROM is null")))
+        .and_then(|program| chip8.cpu.load_program(program.to_vec()));
+    chip8.status(result).err().unwrap_or(0)
+}
+
+/// Like `chip8_load`, but reading the ROM, which may be zipped, from `path`.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`, and `path` a NUL-terminated UTF-8
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_file(chip8: *mut Chip8, path: *const c_char) -> c_int {
+    let chip8 = &mut *chip8;
+    let result = CStr::from_ptr(path).to_str()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+        .and_then(|path| crate::rom::read(Path::new(path)))
+        .map_err(CpuError::ProgramLoadError)
+        .and_then(|program| chip8.cpu.load_program(program));
+    chip8.status(result).err().unwrap_or(0)
+}
+
+/// Run one instruction. Returns `DREW` if it changed the display.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> c_int {
+    let chip8 = &mut *chip8;
+    let result = chip8.cpu.step();
+    match chip8.status(result) {
+        Ok(StepOutcome::DrewFrame) => DREW,
+        Ok(_) => 0,
+        Err(code) => code
+    }
+}
+
+/// Run a 60th of a second's worth of instructions and tick the timers.
+/// Returns `DREW` if the display changed.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Chip8) -> c_int {
+    let chip8 = &mut *chip8;
+    let result = chip8.cpu.run_frame();
+    match chip8.status(result) {
+        Ok(frame) if frame.drawn => DREW,
+        Ok(_) => 0,
+        Err(code) => code
+    }
+}
+
+/// The display, row by row, one byte per pixel that's 1 where it's lit.
+/// Its size is written to `width` and `height` when they aren't null. The
+/// buffer stays valid until the next call to `chip8_framebuffer` or
+/// `chip8_destroy`.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`, and `width` and `height` null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *mut Chip8, width: *mut usize, height: *mut usize) -> *const u8 {
+    let chip8 = &mut *chip8;
+    let screen = chip8.cpu.screen();
+    chip8.framebuffer.clear();
+    chip8.framebuffer.extend((0..screen.height()).flat_map(|y| (0..NCOLS).map(move |x| screen.pixel(x, y) as u8)));
+    if !width.is_null() {
+        *width = NCOLS;
+    }
+    if !height.is_null() {
+        *height = screen.height();
+    }
+    chip8.framebuffer.as_ptr()
+}
+
+/// Press (`pressed` non-zero) or release `key`, 0 through 0xF. Other keys
+/// are ignored.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: c_int) {
+    let chip8 = &mut *chip8;
+    match (key, pressed) {
+        (0x10.., _) => (),
+        (_, 0) => chip8.cpu.release_key(key),
+        _ => chip8.cpu.press_key(key)
+    }
+}
+
+/// Seed `RND`, for runs that can be repeated.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_seed(chip8: *mut Chip8, seed: u64) {
+    (*chip8).cpu.set_seed(seed);
+}
+
+/// Write the machine state, in the same format as `--save-state` files, to
+/// `buf` if it fits in `cap` bytes. Returns the state's size, so a caller
+/// can pass a null `buf` first to find how big it needs to be, or 0 on an
+/// error.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`, and `buf` null or point to `cap`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip8: *mut Chip8, buf: *mut u8, cap: usize) -> usize {
+    let chip8 = &mut *chip8;
+    let result = chip8.cpu.to_state();
+    match chip8.status(result) {
+        Ok(state) => {
+            if !buf.is_null() && state.len() <= cap {
+                ptr::copy_nonoverlapping(state.as_ptr(), buf, state.len());
+            }
+            state.len()
+        },
+        Err(_) => 0
+    }
+}
+
+/// Restore the `len` bytes of state at `state` from `chip8_save_state` of
+/// the same ROM.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`, and `state` point to `len` bytes.
+/// `state` may only be null if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(chip8: *mut Chip8, state: *const u8, len: usize) -> c_int {
+    let chip8 = &mut *chip8;
+    let result = bytes(state, len)
+        .ok_or_else(|| CpuError::InvalidSnapshot("Invalid save state: the state is null".into()))
+        .and_then(|state| chip8.cpu.restore_state(state));
+    chip8.status(result).err().unwrap_or(0)
+}
+
+/// The message for the last error, or an empty string. It stays valid until
+/// the next call that fails or `chip8_destroy`.
+///
+/// # Safety
+///
+/// `chip8` must be from `chip8_create`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_last_error(chip8: *const Chip8) -> *const c_char {
+    (*chip8).error.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let chip8 = chip8_create();
+            // Draw the 0 glyph, then count up in V1.
+            let rom = [0xD0, 0x15, 0x71, 0x01, 0x12, 0x02];
+            assert_eq!(chip8_load(chip8, rom.as_ptr(), rom.len()), 0);
+            chip8_set_seed(chip8, 1);
+            assert_eq!(chip8_step(chip8), DREW);
+            assert_eq!(chip8_step(chip8), 0);
+
+            let (mut width, mut height) = (0, 0);
+            let pixels = chip8_framebuffer(chip8, &mut width, &mut height);
+            let pixels = slice::from_raw_parts(pixels, width * height);
+            assert_eq!((width, height), (64, 32));
+            assert_eq!(&pixels[..4], &[1, 1, 1, 1]);
+
+            let len = chip8_save_state(chip8, ptr::null_mut(), 0);
+            let mut state = alloc::vec![0; len];
+            assert_eq!(chip8_save_state(chip8, state.as_mut_ptr(), len), len);
+            assert!(chip8_run_frame(chip8) <= 0);
+            assert_ne!((*chip8).cpu.registers()[1], 1);
+            assert_eq!(chip8_load_state(chip8, state.as_ptr(), len), 0);
+            assert_eq!((*chip8).cpu.registers()[1], 1);
+
+            assert_ne!(chip8_load_state(chip8, state.as_ptr(), 4), 0);
+            assert!(!CStr::from_ptr(chip8_last_error(chip8)).is_empty());
+            assert_ne!(chip8_load_state(chip8, ptr::null(), 0), 0);
+            assert_ne!(chip8_load(chip8, ptr::null(), 2), 0);
+            assert_eq!(chip8_load(chip8, ptr::null(), 0), 0);
+            chip8_destroy(chip8);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::screen::{Screen, NCOLS, NROWS};
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+/// At most this many flashes are shown per `WINDOW`, the limit WCAG sets to
+/// avoid triggering seizures.
+const MAX_FLASHES: usize = 3;
+const WINDOW: Duration = Duration::from_secs(1);
+/// A frame that changes at least this many pixels counts as a flash.
+const FLASH_PIXELS: u32 = (NCOLS * NROWS / 4) as u32;
+
+/// Keeps games that flash the whole display, e.g. when the player is hit,
+/// from doing so more than a few times a second. Frames that would exceed
+/// the cap are held back until it allows them.
+#[derive(Debug, Default)]
+pub struct FlashLimiter {
+    /// The last frame allowed through.
+    shown: Option<Vec<u64>>,
+    /// When the recent flashes were shown, oldest first.
+    flashes: VecDeque<Instant>
+}
+
+impl FlashLimiter {
+    /// Whether `screen` may be presented now. If not, the previous frame
+    /// should stay up and `screen` be offered again shortly.
+    pub fn allow(&mut self, screen: &Screen) -> bool {
+        let changed: u32 = match &self.shown {
+            Some(shown) => shown.iter().zip(screen.rows()).map(|(a, b)| (a ^ b).count_ones()).sum(),
+            None => 0
+        };
+
+        if changed >= FLASH_PIXELS {
+            let now = Instant::now();
+            while self.flashes.front().is_some_and(|&t| now.duration_since(t) >= WINDOW) {
+                self.flashes.pop_front();
+            }
+            if self.flashes.len() >= MAX_FLASHES {
+                return false;
+            }
+            self.flashes.push_back(now);
+        }
+
+        let shown = self.shown.get_or_insert_with(Vec::new);
+        shown.clear();
+        shown.extend_from_slice(screen.rows());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flash_limiter() {
+        let (dark, mut lit) = (Screen::new(), Screen::new());
+        lit.set_rows(&[u64::MAX; NROWS]);
+        let mut almost = dark.clone();
+        almost.flip(0, 0);
+
+        let mut limiter = FlashLimiter::default();
+        assert!(limiter.allow(&dark));
+        assert!(limiter.allow(&lit));
+        assert!(limiter.allow(&dark));
+        assert!(limiter.allow(&lit));
+        assert!(!limiter.allow(&dark));
+        // Small changes aren't flashes.
+        assert!(limiter.allow(&lit));
+        lit.flip(0, 0);
+        assert!(limiter.allow(&lit));
+        assert!(!limiter.allow(&almost));
+    }
+}
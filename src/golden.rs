@@ -0,0 +1,122 @@
+use crate::{
+    cpu::{Cpu, CpuError}, emulator::{self, Limits}, policy::ErrorKind, replay::Script, rom,
+    screen::Screen
+};
+use std::{env, fs, io, path::Path};
+
+/// Set this environment variable to write the current display over the
+/// stored snapshots instead of comparing against them, after a deliberate
+/// change to what a ROM draws.
+pub const UPDATE_VAR: &str = "CHIP8_UPDATE_GOLDEN";
+/// `RND` is seeded with this so snapshots of random games are repeatable.
+pub const SEED: u64 = 0xC8;
+
+/// Run `cpu` headlessly for up to `cycles` instructions, or until the program
+/// stops, and return the display it leaves behind.
+pub fn run(mut cpu: Cpu, cycles: u64) -> Result<Screen, CpuError> {
+    cpu.set_seed(SEED);
+    match emulator::run_limited(&mut cpu, Limits { cycles: Some(cycles), frames: None }, &mut Script::default()) {
+        Ok(_) => (),
+        // Programs commonly end by jumping to themselves.
+        Err(e) if e.kind() == ErrorKind::InfiniteLoop => (),
+        Err(e) => return Err(e)
+    }
+    Ok(cpu.screen().clone())
+}
+
+/// Compare `screen` against the snapshot at `path`, or record it there if
+/// `UPDATE_VAR` is set. A missing snapshot fails the check otherwise, so a
+/// deleted file or a mistyped path doesn't pass unnoticed.
+///
+/// Snapshots are text art from `Screen::render_text_into`, so changes show up
+/// in diffs, or a single `sha1:` line from `hash` for a smaller file. An
+/// existing snapshot keeps its format when re-recorded.
+pub fn check(path: &Path, screen: &Screen) -> Result<(), String> {
+    let mut actual = String::new();
+    screen.render_text_into(&mut actual);
+
+    let expected = match fs::read_to_string(path) {
+        Ok(expected) => Some(expected),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("failed to read {}: {e}", path.display()))
+    };
+    let hashed = expected.as_deref().is_some_and(|e| e.starts_with("sha1:"));
+    if hashed {
+        actual = format!("{}\n", hash(screen));
+    }
+
+    match expected {
+        _ if env::var_os(UPDATE_VAR).is_some() => {
+            let record = || {
+                fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+                fs::write(path, &actual)
+            };
+            record().map_err(|e| format!("failed to record {}: {e}", path.display()))
+        },
+        None => Err(format!("no snapshot at {}; rerun with {UPDATE_VAR}=1 to record it", path.display())),
+        Some(expected) => {
+            if expected == actual {
+                Ok(())
+            } else if hashed {
+                Err(format!("{}: expected {}, found {}", path.display(), expected.trim(), actual.trim()))
+            } else {
+                Err(format!("{}: the display differs\n{}", path.display(), diff(&expected, &actual)))
+            }
+        }
+    }
+}
+
+/// Panic with a description of the difference unless `screen` matches the
+/// snapshot at `path`, for use in tests.
+pub fn assert_matches(path: &Path, screen: &Screen) {
+    if let Err(e) = check(path, screen) {
+        panic!("{e}\n(set {UPDATE_VAR}=1 to re-record)");
+    }
+}
+
+/// The display as a `rom::hash` of its rows, in big-endian order.
+pub fn hash(screen: &Screen) -> String {
+    let bytes: Vec<u8> = screen.rows().iter().flat_map(|row| row.to_be_bytes()).collect();
+    rom::hash(&bytes)
+}
+
+/// The rows that differ between two text-art displays, each as the expected
+/// row above the actual one.
+fn diff(expected: &str, actual: &str) -> String {
+    let mut out = String::new();
+    for (y, (e, a)) in expected.lines().zip(actual.lines()).enumerate() {
+        if e != a {
+            out.push_str(&format!("row {y:>2} - {e}\n       + {a}\n"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_golden() {
+        let dir = env::temp_dir().join(format!("chip8-golden-{}", std::process::id()));
+        let (text, hashed) = (dir.join("text.txt"), dir.join("hashed.txt"));
+
+        let mut screen = Screen::new();
+        screen.flip(1, 0);
+        assert!(check(&text, &screen).unwrap_err().starts_with("no snapshot at"));
+        let mut art = String::new();
+        screen.render_text_into(&mut art);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&text, art).unwrap();
+        check(&text, &screen).unwrap();
+        fs::write(&hashed, format!("{}\n", hash(&screen))).unwrap();
+        check(&hashed, &screen).unwrap();
+
+        screen.flip(2, 1);
+        let e = check(&text, &screen).unwrap_err();
+        assert!(e.contains("row  1 - ....") && e.contains("+ ..#."));
+        assert!(check(&hashed, &screen).unwrap_err().contains("expected sha1:"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{address::Address, memory::{Memory, SegmentationFault}};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::{fmt::Write, ops::Range, sync::atomic::{AtomicU32, Ordering}};
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path};
+
+/// Addresses shown per row of the rendered map.
+const ROW_SIZE: usize = 64;
+/// Characters for increasing access counts, so the map is readable without
+/// color too.
+const SHADES: &[u8] = b" .:-=+*#%@";
+/// 256-color palette indices running from cold (blue) to hot (red).
+const COLORS: [u8; 10] = [17, 19, 21, 27, 33, 45, 226, 214, 208, 196];
+
+/// Per-address read and write counts, shared with the memory it is attached
+/// to so they can be collected while the `Cpu` runs on another thread.
+#[derive(Clone)]
+pub struct HeatMap {
+    reads: Arc<[AtomicU32]>,
+    writes: Arc<[AtomicU32]>
+}
+
+impl HeatMap {
+    pub fn new(len: usize) -> Self {
+        Self {
+            reads: (0..len).map(|_| AtomicU32::new(0)).collect(),
+            writes: (0..len).map(|_| AtomicU32::new(0)).collect()
+        }
+    }
+
+    /// Wrap `inner` so that every read and write through it is counted.
+    pub fn attach(&self, inner: Box<dyn Memory + Send>) -> Box<dyn Memory + Send> {
+        Box::new(CountedMemory { inner, map: self.clone() })
+    }
+
+    /// Reset every count to zero, e.g. to forget the writes made loading a ROM.
+    pub fn clear(&self) {
+        self.reads.iter().chain(self.writes.iter()).for_each(|c| c.store(0, Ordering::Relaxed));
+    }
+
+    pub fn reads(&self, address: Address) -> u32 {
+        self.reads.get(address.0 as usize).map_or(0, |c| c.load(Ordering::Relaxed))
+    }
+
+    pub fn writes(&self, address: Address) -> u32 {
+        self.writes.get(address.0 as usize).map_or(0, |c| c.load(Ordering::Relaxed))
+    }
+
+    /// Render the reads and writes as two maps of `ROW_SIZE` addresses per row,
+    /// shaded and colored with ANSI escapes on a log scale (view with `less -R`).
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (title, counts) in [("reads", &self.reads), ("writes", &self.writes)] {
+            let counts: Vec<u32> = counts.iter().map(|c| c.load(Ordering::Relaxed)).collect();
+            let max = counts.iter().copied().max().unwrap_or(0);
+            let _ = writeln!(out, "{title} (max {max})");
+
+            for (row, chunk) in counts.chunks(ROW_SIZE).enumerate() {
+                let _ = write!(out, "{:#06x} ", row * ROW_SIZE);
+                for &count in chunk {
+                    let level = shade(count, max);
+                    let _ = write!(out, "\x1b[38;5;{}m{}", COLORS[level], SHADES[level] as char);
+                }
+                out.push_str("\x1b[0m\n");
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.render())
+    }
+
+    fn count(counts: &[AtomicU32], range: Range<usize>) {
+        if let Some(counts) = counts.get(range) {
+            counts.iter().for_each(|c| { c.fetch_add(1, Ordering::Relaxed); });
+        }
+    }
+}
+
+/// Which of the `SHADES` a count falls into, with zero always the first.
+fn shade(count: u32, max: u32) -> usize {
+    if count == 0 || max == 0 {
+        return 0;
+    }
+
+    let levels = SHADES.len() as u32 - 1;
+    let scaled = ((count.ilog2() + 1) * levels).div_ceil(max.ilog2() + 1);
+    (scaled as usize).clamp(1, SHADES.len() - 1)
+}
+
+struct CountedMemory {
+    inner: Box<dyn Memory + Send>,
+    map: HeatMap
+}
+
+impl Memory for CountedMemory {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn get_byte(&self, address: Address) -> Result<u8, SegmentationFault> {
+        HeatMap::count(&self.map.reads, address.0 as usize..address.0 as usize + 1);
+        self.inner.get_byte(address)
+    }
+
+    fn set_byte(&mut self, address: Address, byte: u8) -> Result<u8, SegmentationFault> {
+        HeatMap::count(&self.map.writes, address.0 as usize..address.0 as usize + 1);
+        self.inner.set_byte(address, byte)
+    }
+
+    fn load_slice(&mut self, start: Address, data: &[u8]) -> Result<(), SegmentationFault> {
+        self.inner.load_slice(start, data)?;
+        HeatMap::count(&self.map.writes, start.0 as usize..start.0 as usize + data.len());
+        Ok(())
+    }
+
+    fn read_slice(&self, range: Range<usize>) -> Result<Vec<u8>, SegmentationFault> {
+        let bytes = self.inner.read_slice(range.clone())?;
+        HeatMap::count(&self.map.reads, range);
+        Ok(bytes)
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes()
+    }
+
+    fn take_dirty_pages(&mut self) -> Vec<usize> {
+        self.inner.take_dirty_pages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cpu::Cpu, memory::Ram};
+    use alloc::vec;
+
+    #[test]
+    fn test_heatmap_counts() {
+        let heatmap = HeatMap::new(0x1000);
+        let memory = heatmap.attach(Box::new(Ram::new()));
+        let mut cpu = Cpu::with_memory(vec![0xA3, 0x00, 0xF1, 0x55], memory).unwrap();
+        heatmap.clear();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(heatmap.reads(Address(0x202)), 1);
+        assert_eq!(heatmap.writes(Address(0x301)), 1);
+        assert_eq!(heatmap.writes(Address(0x302)), 0);
+        assert_eq!(shade(0, 100), 0);
+        assert_eq!(shade(100, 100), SHADES.len() - 1);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::memory::PAGE_SIZE;
+use std::fmt::Write;
+
+/// Bytes shown per row.
+const ROW_SIZE: usize = 16;
+/// Frames a written byte stays highlighted for.
+const HIGHLIGHT_FRAMES: u8 = 30;
+/// Columns a row takes: its address, then each byte after a space.
+pub const WIDTH: usize = 4 + ROW_SIZE * 3;
+
+/// A scrollable hex dump of the machine's memory, kept up to date from the
+/// pages the emulation thread sends as they're written, that highlights the
+/// bytes that changed recently.
+#[derive(Debug, Default)]
+pub struct HexView {
+    bytes: Vec<u8>,
+    /// Frames since each byte last changed, up to `HIGHLIGHT_FRAMES`.
+    ages: Vec<u8>,
+    /// The first row shown.
+    top: usize
+}
+
+impl HexView {
+    /// Take in the `PAGE_SIZE` pages written over a frame, as `(index,
+    /// bytes)`. The first pages received aren't highlighted.
+    pub fn update(&mut self, pages: Vec<(usize, Vec<u8>)>) {
+        self.ages.iter_mut().for_each(|age| *age = age.saturating_add(1).min(HIGHLIGHT_FRAMES));
+        for (page, bytes) in pages {
+            let (start, end) = (page * PAGE_SIZE, page * PAGE_SIZE + bytes.len());
+            if self.bytes.len() < end {
+                // A page seen for the first time has nothing to compare with.
+                self.bytes.resize(end, 0);
+                self.ages.resize(end, HIGHLIGHT_FRAMES);
+                self.bytes[start..end].copy_from_slice(&bytes);
+                continue;
+            }
+
+            let old = &mut self.bytes[start..end];
+            let ages = &mut self.ages[start..end];
+            for ((old, new), age) in old.iter_mut().zip(bytes).zip(ages) {
+                if *old != new {
+                    *old = new;
+                    *age = 0;
+                }
+            }
+        }
+    }
+
+    /// Scroll by `pages` of `PAGE_SIZE` bytes, back if negative.
+    pub fn scroll(&mut self, pages: isize) {
+        let last = self.bytes.len().div_ceil(ROW_SIZE).saturating_sub(1);
+        let rows = pages * (PAGE_SIZE / ROW_SIZE) as isize;
+        self.top = self.top.saturating_add_signed(rows).min(last);
+    }
+
+    /// Append `rows` rows of the dump, starting at column `col` of the first
+    /// line, with recently changed bytes in reverse video.
+    pub fn render_into(&self, out: &mut String, col: usize, rows: usize) {
+        for row in 0..rows {
+            let start = (self.top + row) * ROW_SIZE;
+            let _ = write!(out, "\x1B[{};{col}H\x1B[K", row + 1);
+            let Some(bytes) = self.bytes.get(start..(start + ROW_SIZE).min(self.bytes.len())) else { continue };
+            if bytes.is_empty() {
+                continue;
+            }
+
+            let _ = write!(out, "{start:04x}");
+            for (byte, &age) in bytes.iter().zip(&self.ages[start..]) {
+                if age < HIGHLIGHT_FRAMES {
+                    let _ = write!(out, " \x1B[7m{byte:02x}\x1B[27m");
+                } else {
+                    let _ = write!(out, " {byte:02x}");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_view() {
+        let mut view = HexView::default();
+        view.update(vec![(0, vec![0x11; PAGE_SIZE]), (1, vec![0; PAGE_SIZE])]);
+        let mut out = String::new();
+        view.render_into(&mut out, 70, 1);
+        assert_eq!(out, format!("\x1B[1;70H\x1B[K0000{}", " 11".repeat(ROW_SIZE)));
+
+        let mut page = vec![0; PAGE_SIZE];
+        page[0x12] = 0xAB;
+        view.update(vec![(1, page)]);
+        view.scroll(1);
+        out.clear();
+        view.render_into(&mut out, 70, 2);
+        assert!(out.ends_with("\x1B[2;70H\x1B[K0110 00 00 \x1B[7mab\x1B[27m 00 00 00 00 00 00 00 00 00 00 00 00 00"));
+
+        (0..HIGHLIGHT_FRAMES).for_each(|_| view.update(Vec::new()));
+        out.clear();
+        view.render_into(&mut out, 70, 2);
+        assert!(out.ends_with("0110 00 00 ab 00 00 00 00 00 00 00 00 00 00 00 00 00"));
+
+        // Past the end, the last row stays in view.
+        view.scroll(5);
+        out.clear();
+        view.render_into(&mut out, 70, 2);
+        assert!(out.starts_with("\x1B[1;70H\x1B[K01f0 00"));
+    }
+}
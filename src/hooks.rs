@@ -0,0 +1,44 @@
+use crate::{address::Address, cpu::{Cpu, CpuError}, register::VRegister, screen::Screen};
+use alloc::{boxed::Box, vec::Vec};
+
+pub type DrawHook = Box<dyn FnMut(&Screen) + Send>;
+pub type KeyWaitHook = Box<dyn FnMut(VRegister) + Send>;
+pub type TimerTickHook = Box<dyn FnMut(u8, u8) + Send>;
+pub type HaltHook = Box<dyn FnMut(Address) + Send>;
+pub type SelfModifyHook = Box<dyn FnMut(Address) + Send>;
+pub type MachineHook = Box<dyn FnMut(&mut Cpu) -> Result<(), CpuError> + Send>;
+
+/// Callbacks registered by an embedder and invoked by the `Cpu` at key points
+/// of its lifecycle.
+#[derive(Default)]
+pub struct Hooks {
+    pub(crate) draw: Vec<DrawHook>,
+    pub(crate) key_wait: Vec<KeyWaitHook>,
+    pub(crate) timer_tick: Vec<TimerTickHook>,
+    pub(crate) halt: Vec<HaltHook>,
+    pub(crate) self_modify: Vec<SelfModifyHook>,
+    pub(crate) frame: Vec<MachineHook>,
+    pub(crate) instruction: Vec<MachineHook>
+}
+
+impl Hooks {
+    pub(crate) fn draw(&mut self, screen: &Screen) {
+        self.draw.iter_mut().for_each(|f| f(screen));
+    }
+
+    pub(crate) fn key_wait(&mut self, reg: VRegister) {
+        self.key_wait.iter_mut().for_each(|f| f(reg));
+    }
+
+    pub(crate) fn timer_tick(&mut self, dt: u8, st: u8) {
+        self.timer_tick.iter_mut().for_each(|f| f(dt, st));
+    }
+
+    pub(crate) fn halt(&mut self, pc: Address) {
+        self.halt.iter_mut().for_each(|f| f(pc));
+    }
+
+    pub(crate) fn self_modify(&mut self, addr: Address) {
+        self.self_modify.iter_mut().for_each(|f| f(addr));
+    }
+}
@@ -0,0 +1,301 @@
+use crossterm::{
+    event::{
+        self, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, 
+        KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, 
+        PushKeyboardEnhancementFlags
+    },
+    execute, terminal::{self, EnterAlternateScreen, LeaveAlternateScreen}
+};
+use std::{collections::HashMap, io, time::{Duration, Instant}};
+
+/// How long a key is considered held after its last press or auto-repeat when
+/// the terminal cannot report key releases.
+pub const KEY_HOLD: Duration = Duration::from_millis(250);
+
+/// Commands issued by the host keyboard that control the emulator itself 
+/// rather than being forwarded to the CHIP-8 program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostCommand {
+    Quit,
+    Reset,
+    NextRom,
+    PreviousRom,
+    SaveState,
+    LoadState,
+    Rewind,
+    TogglePause,
+    ToggleTurbo,
+    /// Halve or double the emulation speed.
+    SlowDown,
+    SpeedUp,
+    /// Whether the fast-forward key is held. Terminals that can't report key
+    /// releases only ever send `FastForward(true)`, repeated while held.
+    FastForward(bool),
+    /// Save the display as a PNG.
+    Screenshot,
+    /// Show or hide the frames and instructions per second counter.
+    ToggleCounter,
+    /// Show or hide the registers over the display.
+    ToggleRegisters,
+    /// Show or hide the memory viewer beside the display.
+    ToggleMemory,
+    /// Scroll the memory viewer by this many pages.
+    ScrollMemory(isize),
+    /// Show or hide the outlines of the sprites drawn each frame.
+    ToggleSprites,
+    /// Show or hide the keys pressed lately.
+    ToggleKeys,
+    /// Open or close the cheat menu.
+    ToggleCheats,
+    /// Switch between the bordered display and one scaled to fill the 
+    /// terminal.
+    ToggleFullscreen,
+    /// Menu navigation with the arrow keys and Enter.
+    Up,
+    Down,
+    Select,
+    /// Escape, which closes the pause menu or otherwise quits.
+    Back,
+    /// The terminal changed size, so the display should be drawn again to
+    /// fit.
+    Resize,
+    /// The terminal gained (`true`) or lost focus, in terminals that report
+    /// it.
+    Focus(bool),
+    KeyDown(u8),
+    KeyUp(u8)
+}
+
+/// Commands for the sprite editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorCommand {
+    /// Move the cursor by `(columns, rows)`.
+    Move(isize, isize),
+    Toggle,
+    AddRow,
+    RemoveRow,
+    /// Finish and export the sprite.
+    Done,
+    /// Leave without exporting.
+    Cancel
+}
+
+/// Puts the terminal into raw mode for as long as this value is alive, so 
+/// that key presses are delivered immediately instead of line-buffered, and
+/// has it report when it gains or loses focus, if it can.
+pub struct RawTerminal {
+    reports_releases: bool
+}
+
+impl RawTerminal {
+    pub fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnableFocusChange)?;
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_releases {
+            execute!(io::stdout(), PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+            ))?;
+        }
+
+        Ok(Self { reports_releases })
+    }
+
+    /// Whether the terminal reports key releases. If not, releases must be 
+    /// emulated with `HeldKeys`.
+    pub fn reports_releases(&self) -> bool {
+        self.reports_releases
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(io::stdout(), DisableFocusChange);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Switches the terminal to its alternate screen for as long as this value is
+/// alive, so a fullscreen display doesn't scroll away the shell's output.
+pub struct AlternateScreen;
+
+impl AlternateScreen {
+    pub fn enter() -> io::Result<Self> {
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// Map the host keyboard onto the hexadecimal keypad using the conventional 
+/// layout:
+///
+/// ```text
+/// 1 2 3 4        1 2 3 C
+/// Q W E R   =>   4 5 6 D
+/// A S D F        7 8 9 E
+/// Z X C V        A 0 B F
+/// ```
+pub fn keypad(c: char) -> Option<u8> {
+    match c.to_ascii_lowercase() {
+        '1' => Some(0x1), '2' => Some(0x2), '3' => Some(0x3), '4' => Some(0xC),
+        'q' => Some(0x4), 'w' => Some(0x5), 'e' => Some(0x6), 'r' => Some(0xD),
+        'a' => Some(0x7), 's' => Some(0x8), 'd' => Some(0x9), 'f' => Some(0xE),
+        'z' => Some(0xA), 'x' => Some(0x0), 'c' => Some(0xB), 'v' => Some(0xF),
+        _ => None
+    }
+}
+
+/// Which host keys press which keypad keys: the conventional `keypad` layout,
+/// plus any extra or replacement mappings.
+#[derive(Debug, Clone, Default)]
+pub struct Keymap {
+    overrides: HashMap<char, u8>
+}
+
+impl Keymap {
+    /// Make the host key `c` press keypad key `key`, in place of whatever it 
+    /// pressed before.
+    pub fn set(&mut self, c: char, key: u8) {
+        self.overrides.insert(c.to_ascii_lowercase(), key & 0xF);
+    }
+
+    pub fn get(&self, c: char) -> Option<u8> {
+        self.overrides.get(&c.to_ascii_lowercase()).copied().or_else(|| keypad(c))
+    }
+}
+
+/// Return the next pending command without blocking, skipping over any 
+/// terminal events that don't map to one. Keys are mapped to the keypad with
+/// `keymap`.
+pub fn poll(keymap: &Keymap) -> io::Result<Option<HostCommand>> {
+    while event::poll(Duration::ZERO)? {
+        let (code, modifiers, kind) = match event::read()? {
+            Event::Key(KeyEvent { code, modifiers, kind, .. }) => (code, modifiers, kind),
+            Event::Resize(..) => return Ok(Some(HostCommand::Resize)),
+            Event::FocusGained => return Ok(Some(HostCommand::Focus(true))),
+            Event::FocusLost => return Ok(Some(HostCommand::Focus(false))),
+            _ => continue
+        };
+
+        if kind == KeyEventKind::Release {
+            if code == KeyCode::Char('`') {
+                return Ok(Some(HostCommand::FastForward(false)));
+            }
+            if let KeyCode::Char(c) = code {
+                if let Some(key) = keymap.get(c) {
+                    return Ok(Some(HostCommand::KeyUp(key)));
+                }
+            }
+            continue;
+        }
+
+        match (code, modifiers) {
+            (KeyCode::Esc, _) => return Ok(Some(HostCommand::Back)),
+            (KeyCode::F(11), _) => return Ok(Some(HostCommand::ToggleFullscreen)),
+            (KeyCode::F(12), _) => return Ok(Some(HostCommand::Screenshot)),
+            (KeyCode::Enter, KeyModifiers::ALT) => return Ok(Some(HostCommand::ToggleFullscreen)),
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Ok(Some(HostCommand::Quit)),
+            (KeyCode::Char('p'), _) | (KeyCode::Pause, _) => return Ok(Some(HostCommand::TogglePause)),
+            (KeyCode::Backspace, _) => return Ok(Some(HostCommand::Rewind)),
+            (KeyCode::Tab, _) => return Ok(Some(HostCommand::ToggleTurbo)),
+            (KeyCode::Char('-'), _) => return Ok(Some(HostCommand::SlowDown)),
+            (KeyCode::Char('=' | '+'), _) => return Ok(Some(HostCommand::SpeedUp)),
+            (KeyCode::Char('`'), _) => return Ok(Some(HostCommand::FastForward(true))),
+            (KeyCode::F(2), _) => return Ok(Some(HostCommand::SaveState)),
+            (KeyCode::F(3), _) => return Ok(Some(HostCommand::ToggleCounter)),
+            (KeyCode::F(4), _) => return Ok(Some(HostCommand::LoadState)),
+            (KeyCode::F(5), _) => return Ok(Some(HostCommand::Reset)),
+            (KeyCode::F(6), _) => return Ok(Some(HostCommand::ToggleRegisters)),
+            (KeyCode::F(7), _) => return Ok(Some(HostCommand::ToggleMemory)),
+            (KeyCode::F(8), _) => return Ok(Some(HostCommand::ToggleSprites)),
+            (KeyCode::F(9), _) => return Ok(Some(HostCommand::ToggleKeys)),
+            (KeyCode::F(10), _) => return Ok(Some(HostCommand::ToggleCheats)),
+            (KeyCode::Up, KeyModifiers::SHIFT) => return Ok(Some(HostCommand::ScrollMemory(-1))),
+            (KeyCode::Down, KeyModifiers::SHIFT) => return Ok(Some(HostCommand::ScrollMemory(1))),
+            (KeyCode::PageDown, _) => return Ok(Some(HostCommand::NextRom)),
+            (KeyCode::PageUp, _) => return Ok(Some(HostCommand::PreviousRom)),
+            (KeyCode::Up, _) => return Ok(Some(HostCommand::Up)),
+            (KeyCode::Down, _) => return Ok(Some(HostCommand::Down)),
+            (KeyCode::Enter, _) => return Ok(Some(HostCommand::Select)),
+            (KeyCode::Char(c), _) => {
+                if let Some(key) = keymap.get(c) {
+                    return Ok(Some(HostCommand::KeyDown(key)));
+                }
+            },
+            _ => ()
+        }
+    }
+
+    Ok(None)
+}
+
+/// Return the next pending sprite editor command without blocking, skipping
+/// over any terminal events that don't map to one.
+pub fn poll_editor() -> io::Result<Option<EditorCommand>> {
+    while event::poll(Duration::ZERO)? {
+        let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? else {
+            continue
+        };
+        if kind == KeyEventKind::Release {
+            continue;
+        }
+
+        let command = match (code, modifiers) {
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) | (KeyCode::Esc, _) => EditorCommand::Cancel,
+            (KeyCode::Enter, _) => EditorCommand::Done,
+            (KeyCode::Left | KeyCode::Char('h'), _) => EditorCommand::Move(-1, 0),
+            (KeyCode::Right | KeyCode::Char('l'), _) => EditorCommand::Move(1, 0),
+            (KeyCode::Up | KeyCode::Char('k'), _) => EditorCommand::Move(0, -1),
+            (KeyCode::Down | KeyCode::Char('j'), _) => EditorCommand::Move(0, 1),
+            (KeyCode::Char(' '), _) => EditorCommand::Toggle,
+            (KeyCode::Char('=' | '+'), _) => EditorCommand::AddRow,
+            (KeyCode::Char('-'), _) => EditorCommand::RemoveRow,
+            _ => continue
+        };
+        return Ok(Some(command));
+    }
+
+    Ok(None)
+}
+
+/// Emulates key releases for terminals that only report presses, treating a 
+/// key as held until `KEY_HOLD` passes without another press or auto-repeat.
+#[derive(Default)]
+pub struct HeldKeys {
+    pressed: [Option<Instant>; 0x10]
+}
+
+impl HeldKeys {
+    pub fn press(&mut self, key: u8) {
+        if let Some(k) = self.pressed.get_mut(key as usize) {
+            *k = Some(Instant::now());
+        }
+    }
+
+    /// Forget every held key, e.g. after a reset has released them all.
+    pub fn clear(&mut self) {
+        self.pressed = [None; 0x10];
+    }
+
+    /// Returns the keys whose hold has elapsed, forgetting them.
+    pub fn expired(&mut self) -> Vec<u8> {
+        let mut released = Vec::new();
+        for (key, pressed) in self.pressed.iter_mut().enumerate() {
+            if pressed.is_some_and(|t| t.elapsed() >= KEY_HOLD) {
+                *pressed = None;
+                released.push(key as u8);
+            }
+        }
+
+        released
+    }
+}
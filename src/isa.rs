@@ -1,7 +1,9 @@
-use crate::{address::Address, register::VRegister};
-use std::fmt::{Display, Formatter};
+use crate::{address::Address, cpu::split_into_nibbles, register::VRegister};
+use alloc::vec::Vec;
+use core::{fmt::{Display, Formatter}, str::FromStr};
+use serde::Serialize;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Instruction {
     /// `00E0` - `CLS`: Clear the display.
     ClearScreen,
@@ -9,6 +11,15 @@ pub enum Instruction {
     /// program counter to the address at the top of the stack, then subtracts 1 
     /// from the stack pointer.
     Return,
+    /// `00FD` - `EXIT`: Exit the interpreter. This is a SUPER-CHIP 
+    /// instruction, but it is the only clean way for a program to stop.
+    Exit,
+    /// `00F1` - `PASS`: Not a CHIP-8 instruction. Under 
+    /// `Cpu::set_test_oracle`, stop the program and report that the test it
+    /// runs passed, so test ROMs can check the emulator automatically.
+    Pass,
+    /// `00F2` - `FAIL`: Like `PASS`, but report that the test failed.
+    Fail,
     /// `1nnn` - `JP addr`: Jump to location `nnn`. The interpreter sets the 
     /// program counter to `nnn`.
     Jump(Address),
@@ -80,6 +91,10 @@ pub enum Instruction {
     /// `Annn` - `LD I, addr`: Set `I` = `nnn`. The value of register `I` is set 
     /// to `nnn`.
     LoadI(Address),
+    /// `F000 nnnn` - `LD I, long addr`: Set `I` = `nnnn`, the 16-bit address in 
+    /// the word following the instruction. This is an XO-CHIP instruction for 
+    /// reaching data beyond the 12-bit address space.
+    LoadLongI,
     /// `Bnnn` - `JP V0, addr`: Jump to location `nnn` + `V0`. The program 
     /// counter is set to `nnn` plus the value of `V0`.
     JumpOffset(Address),
@@ -99,21 +114,36 @@ pub enum Instruction {
     /// screen. See instruction 8xy3 for more information on XOR, and section 
     /// 2.4, Display, for more information on the Chip-8 screen and sprites.
     Draw(VRegister, VRegister, u8),
+    /// `Ex9E` - `SKP Vx`: Skip next instruction if key with the value of `Vx` 
+    /// is pressed. Checks the keyboard, and if the key corresponding to the 
+    /// value of `Vx` is currently in the down position, PC is increased by 2.
+    SkipIfKey(VRegister),
+    /// `ExA1` - `SKNP Vx`: Skip next instruction if key with the value of `Vx` 
+    /// is not pressed. Checks the keyboard, and if the key corresponding to the 
+    /// value of `Vx` is currently in the up position, PC is increased by 2.
+    SkipIfNotKey(VRegister),
     /// `Fx07` - `LD Vx, DT`: Set `Vx` = delay timer value. The value of `DT` is 
     /// placed into `Vx`.
     LoadDT(VRegister),
+    /// `Fx0A` - `LD Vx, K`: Wait for a key press, store the value of the key in 
+    /// `Vx`. All execution stops until a key is pressed, then the value of that 
+    /// key is stored in `Vx`.
+    WaitKey(VRegister),
     /// `Fx15` - `LD DT, Vx`: Set delay timer = `Vx`. `DT` is set equal to the 
     /// value of `Vx`.
     StoreDT(VRegister),
+    /// `Fx18` - `LD ST, Vx`: Set sound timer = `Vx`. `ST` is set equal to the 
+    /// value of `Vx`.
+    StoreST(VRegister),
     /// `Fx1E` - `ADD I, Vx`: Set `I` = `I` + `Vx`. The values of `I` and `Vx` 
     /// are added, and the results are stored in `I`.
     AddI(VRegister),
-    /// `Fx29` - `LD I, Vx`: Set `I` = location of sprite for digit `Vx`. The 
+    /// `Fx29` - `LD F, Vx`: Set `I` = location of sprite for digit `Vx`. The 
     /// value of I is set to the location for the hexadecimal sprite 
     /// corresponding to the value of `Vx`. See section 2.4, Display, for more 
     /// information on the Chip-8 hexadecimal font.
     LoadSprite(VRegister),
-    /// `Fx33` - `LD I, Vx`: Store BCD representation of `Vx` in memory 
+    /// `Fx33` - `LD B, Vx`: Store BCD representation of `Vx` in memory 
     /// locations `I`, `I+1`, and `I+2`. The interpreter takes the decimal value 
     /// of `Vx`, and places the hundreds digit in memory at location in `I`, the 
     /// tens digit at location `I+1`, and the ones digit at location `I+2`.
@@ -126,19 +156,370 @@ pub enum Instruction {
     /// starting at location `I`. The interpreter reads values from memory 
     /// starting at location `I` into registers `V0` through `Vx`.
     Load(VRegister),
+    /// `02A0` - `BGC`: Switch to the next background color. This and the
+    /// instructions below are CHIP-8X extensions, decoded only under
+    /// `Cpu::set_chip8x`.
+    CycleBackground,
+    /// `5xy1` - `ADDN Vx, Vy`: Set `Vx` = `Vx` + `Vy`, adding the high and low
+    /// nibbles separately, each modulo 8, for adding color zone coordinates.
+    AddNibbles(VRegister, VRegister),
+    /// `Bxyn` - `COL Vx, Vy, nibble`: Set the foreground color to `Vy`. With
+    /// `n` = 0 this colors a block of 8x4 pixel zones described by `Vx` and
+    /// `Vx+1`, otherwise the 8 pixel strip at (`Vx`, `Vx+1`) for `n` rows. See
+    /// `screen::Colors` for the details.
+    Color(VRegister, VRegister, u8),
+    /// `ExF2` - `SKP2 Vx`: Like `SKP Vx`, but for the second keypad.
+    SkipIfKey2(VRegister),
+    /// `ExF5` - `SKNP2 Vx`: Like `SKNP Vx`, but for the second keypad.
+    SkipIfNotKey2(VRegister),
+    /// `FxF8` - `OUT Vx`: Set the pitch of the buzzer to `Vx`.
+    Tone(VRegister),
     /// This instruction is not part of the official CHIP-8 ISA, but I have 
     /// added it regardless as a placeholder for instructions that are not yet 
     /// implemented by this interpreter. 
     Nop
 }
 
+impl Instruction {
+    /// The opcode for this instruction, the inverse of `Cpu::decode`, or 
+    /// `None` for `Nop`, which has none. `LoadLongI` is only the `F000`; the 
+    /// address follows it in the next word.
+    pub fn encode(self) -> Option<u16> {
+        use Instruction::*;
+        let x = |vx: VRegister| (vx as u16) << 8;
+        let xy = |vx: VRegister, vy: VRegister| x(vx) | (vy as u16) << 4;
+        let nnn = |addr: Address| addr.0 & Address::MASK;
+
+        Some(match self {
+            ClearScreen => 0x00E0,
+            Return => 0x00EE,
+            Exit => 0x00FD,
+            Pass => 0x00F1,
+            Fail => 0x00F2,
+            Jump(addr) => 0x1000 | nnn(addr),
+            Call(addr) => 0x2000 | nnn(addr),
+            SkipIfEqualImm(vx, b) => 0x3000 | x(vx) | b as u16,
+            SkipIfNotEqualImm(vx, b) => 0x4000 | x(vx) | b as u16,
+            SkipIfEqual(vx, vy) => 0x5000 | xy(vx, vy),
+            LoadImm(vx, b) => 0x6000 | x(vx) | b as u16,
+            AddImm(vx, b) => 0x7000 | x(vx) | b as u16,
+            Move(vx, vy) => 0x8000 | xy(vx, vy),
+            Or(vx, vy) => 0x8001 | xy(vx, vy),
+            And(vx, vy) => 0x8002 | xy(vx, vy),
+            Xor(vx, vy) => 0x8003 | xy(vx, vy),
+            Add(vx, vy) => 0x8004 | xy(vx, vy),
+            Subtract(vx, vy) => 0x8005 | xy(vx, vy),
+            ShiftRight(vx) => 0x8006 | x(vx),
+            SubtractN(vx, vy) => 0x8007 | xy(vx, vy),
+            ShiftLeft(vx) => 0x800E | x(vx),
+            SkipIfNotEqual(vx, vy) => 0x9000 | xy(vx, vy),
+            LoadI(addr) => 0xA000 | nnn(addr),
+            LoadLongI => 0xF000,
+            JumpOffset(addr) => 0xB000 | nnn(addr),
+            AndRandom(vx, b) => 0xC000 | x(vx) | b as u16,
+            Draw(vx, vy, n) => 0xD000 | xy(vx, vy) | (n & 0xF) as u16,
+            SkipIfKey(vx) => 0xE09E | x(vx),
+            SkipIfNotKey(vx) => 0xE0A1 | x(vx),
+            LoadDT(vx) => 0xF007 | x(vx),
+            WaitKey(vx) => 0xF00A | x(vx),
+            StoreDT(vx) => 0xF015 | x(vx),
+            StoreST(vx) => 0xF018 | x(vx),
+            AddI(vx) => 0xF01E | x(vx),
+            LoadSprite(vx) => 0xF029 | x(vx),
+            StoreBCD(vx) => 0xF033 | x(vx),
+            Store(vx) => 0xF055 | x(vx),
+            Load(vx) => 0xF065 | x(vx),
+            CycleBackground => 0x02A0,
+            AddNibbles(vx, vy) => 0x5001 | xy(vx, vy),
+            Color(vx, vy, n) => 0xB000 | xy(vx, vy) | (n & 0xF) as u16,
+            SkipIfKey2(vx) => 0xE0F2 | x(vx),
+            SkipIfNotKey2(vx) => 0xE0F5 | x(vx),
+            Tone(vx) => 0xF0F8 | x(vx),
+            Nop => return None
+        })
+    }
+}
+
+/// Which of the optional instructions `decode` accepts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Decoding {
+    /// The CHIP-8X instructions.
+    pub chip8x: bool,
+    /// `PASS` and `FAIL`, under `Cpu::set_test_oracle`.
+    pub test_oracle: bool,
+    /// `0230`, which clears a 64 row display.
+    pub tall_display: bool
+}
+
+/// The instruction `instruction` encodes, or the opcode back if it doesn't
+/// encode one under `decoding`.
+pub fn decode(instruction: u16, decoding: Decoding) -> Result<Instruction, u16> {
+    use Instruction::*;
+
+    let nibbles: [u8; 4] = split_into_nibbles(instruction);
+    let vx = VRegister::try_from(nibbles[1]).map_err(|_| instruction);
+    let vy = VRegister::try_from(nibbles[2]).map_err(|_| instruction);
+    let addr: Address = (instruction & Address::MASK).into();
+    let lsb = (instruction & 0xFF) as u8;
+    let lsn = (instruction & 0xF) as u8;
+
+    match nibbles {
+        [0x0, 0x0, 0xE, 0x0] => Ok(ClearScreen),
+        [0x0, 0x2, 0x3, 0x0] if decoding.tall_display => Ok(ClearScreen),
+        [0x0, 0x0, 0xE, 0xE] => Ok(Return),
+        [0x0, 0x0, 0xF, 0xD] => Ok(Exit),
+        [0x0, 0x0, 0xF, 0x1] if decoding.test_oracle => Ok(Pass),
+        [0x0, 0x0, 0xF, 0x2] if decoding.test_oracle => Ok(Fail),
+        [0x0, 0x2, 0xA, 0x0] if decoding.chip8x => Ok(CycleBackground),
+        [0x5, .., 0x1] if decoding.chip8x => Ok(AddNibbles(vx?, vy?)),
+        [0xB, ..] if decoding.chip8x => Ok(Color(vx?, vy?, lsn)),
+        [0xE, _, 0xF, 0x2] if decoding.chip8x => Ok(SkipIfKey2(vx?)),
+        [0xE, _, 0xF, 0x5] if decoding.chip8x => Ok(SkipIfNotKey2(vx?)),
+        [0xF, _, 0xF, 0x8] if decoding.chip8x => Ok(Tone(vx?)),
+        [0x1, ..]            => Ok(Jump(addr)),
+        [0x2, ..]            => Ok(Call(addr)),
+        [0x3, ..]            => Ok(SkipIfEqualImm(vx?, lsb)),
+        [0x4, ..]            => Ok(SkipIfNotEqualImm(vx?, lsb)),
+        [0x5, .., 0x0]       => Ok(SkipIfEqual(vx?, vy?)),
+        [0x6, ..]            => Ok(LoadImm(vx?, lsb)),
+        [0x7, ..]            => Ok(AddImm(vx?, lsb)),
+        [0x8, .., 0x0]       => Ok(Move(vx?, vy?)),
+        [0x8, .., 0x1]       => Ok(Or(vx?, vy?)),
+        [0x8, .., 0x2]       => Ok(And(vx?, vy?)),
+        [0x8, .., 0x3]       => Ok(Xor(vx?, vy?)),
+        [0x8, .., 0x4]       => Ok(Add(vx?, vy?)),
+        [0x8, .., 0x5]       => Ok(Subtract(vx?, vy?)),
+        [0x8, .., 0x6]       => Ok(ShiftRight(vx?)),
+        [0x8, .., 0x7]       => Ok(SubtractN(vx?, vy?)),
+        [0x8, .., 0xE]       => Ok(ShiftLeft(vx?)),
+        [0x9, .., 0x0]       => Ok(SkipIfNotEqual(vx?, vy?)),
+        [0xA, ..]            => Ok(LoadI(addr)),
+        [0xB, ..]            => Ok(JumpOffset(addr)),
+        [0xC, ..]            => Ok(AndRandom(vx?, lsb)),
+        [0xD, ..]            => Ok(Draw(vx?, vy?, lsn)),
+        [0xE, _, 0x9, 0xE]   => Ok(SkipIfKey(vx?)),
+        [0xE, _, 0xA, 0x1]   => Ok(SkipIfNotKey(vx?)),
+        [0xF, 0x0, 0x0, 0x0] => Ok(LoadLongI),
+        [0xF, _, 0x0, 0x7]   => Ok(LoadDT(vx?)),
+        [0xF, _, 0x0, 0xA]   => Ok(WaitKey(vx?)),
+        [0xF, _, 0x1, 0x5]   => Ok(StoreDT(vx?)),
+        [0xF, _, 0x1, 0x8]   => Ok(StoreST(vx?)),
+        [0xF, _, 0x1, 0xE]   => Ok(AddI(vx?)),
+        [0xF, _, 0x2, 0x9]   => Ok(LoadSprite(vx?)),
+        [0xF, _, 0x3, 0x3]   => Ok(StoreBCD(vx?)),
+        [0xF, _, 0x5, 0x5]   => Ok(Store(vx?)),
+        [0xF, _, 0x6, 0x5]   => Ok(Load(vx?)),
+        _ => Err(instruction)
+    }
+}
+
+/// An entry in the table of instructions the decoder supports, for editors,
+/// documentation, and test generators to read with `chip8 isa --json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Spec {
+    /// The opcode, with `x` and `y` for register numbers, `n` for a nibble,
+    /// `kk` for a byte, and `nnn` for an address, e.g. `8xy4`.
+    pub pattern: &'static str,
+    /// The assembly `Instruction` parses and displays, with the operands 
+    /// named as in `pattern`.
+    pub syntax: &'static str,
+    /// The kind of each operand in `syntax`, in order: `register`, `byte`,
+    /// `nibble`, `address`, or `long address`.
+    pub operands: &'static [&'static str],
+    /// The interpreter the instruction comes from, e.g. `chip-8` or `xo-chip`.
+    pub origin: &'static str,
+    /// What has to be turned on for the decoder to accept it, if anything.
+    pub requires: Option<&'static str>,
+    pub summary: &'static str
+}
+
+impl Spec {
+    /// Whether `opcode` has this instruction's fixed nibbles.
+    pub fn matches(&self, opcode: u16) -> bool {
+        // None of the operand letters are hex digits.
+        self.pattern.chars().take(4).enumerate().all(|(n, c)| match c.to_digit(16) {
+            Some(digit) => (opcode >> (12 - 4 * n)) & 0xF == digit as u16,
+            None => true
+        })
+    }
+}
+
+/// Every instruction the decoder supports, in opcode order.
+pub const SPECS: &[Spec] = &[
+    Spec { pattern: "00E0", syntax: "CLS", operands: &[], origin: "chip-8", requires: None, summary: "Clear the display." },
+    Spec { pattern: "00EE", syntax: "RET", operands: &[], origin: "chip-8", requires: None, summary: "Return from a subroutine." },
+    Spec { pattern: "00FD", syntax: "EXIT", operands: &[], origin: "super-chip", requires: None, summary: "Exit the interpreter." },
+    Spec { pattern: "00F1", syntax: "PASS", operands: &[], origin: "test", requires: Some("test oracle"), summary: "Stop and report that the test passed." },
+    Spec { pattern: "00F2", syntax: "FAIL", operands: &[], origin: "test", requires: Some("test oracle"), summary: "Stop and report that the test failed." },
+    Spec { pattern: "0230", syntax: "CLS", operands: &[], origin: "eti-660", requires: Some("64 row display"), summary: "Clear the display, in programs for the 64 row display." },
+    Spec { pattern: "02A0", syntax: "BGC", operands: &[], origin: "chip-8x", requires: Some("chip-8x"), summary: "Switch to the next background color." },
+    Spec { pattern: "1nnn", syntax: "JP nnn", operands: &["address"], origin: "chip-8", requires: None, summary: "Jump to `nnn`." },
+    Spec { pattern: "2nnn", syntax: "CALL nnn", operands: &["address"], origin: "chip-8", requires: None, summary: "Call the subroutine at `nnn`." },
+    Spec { pattern: "3xkk", syntax: "SE Vx, kk", operands: &["register", "byte"], origin: "chip-8", requires: None, summary: "Skip the next instruction if `Vx` = `kk`." },
+    Spec { pattern: "4xkk", syntax: "SNE Vx, kk", operands: &["register", "byte"], origin: "chip-8", requires: None, summary: "Skip the next instruction if `Vx` != `kk`." },
+    Spec { pattern: "5xy0", syntax: "SE Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Skip the next instruction if `Vx` = `Vy`." },
+    Spec { pattern: "5xy1", syntax: "ADDN Vx, Vy", operands: &["register", "register"], origin: "chip-8x", requires: Some("chip-8x"), summary: "Set `Vx` = `Vx` + `Vy`, adding each nibble separately, modulo 8." },
+    Spec { pattern: "6xkk", syntax: "LD Vx, kk", operands: &["register", "byte"], origin: "chip-8", requires: None, summary: "Set `Vx` = `kk`." },
+    Spec { pattern: "7xkk", syntax: "ADD Vx, kk", operands: &["register", "byte"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vx` + `kk`." },
+    Spec { pattern: "8xy0", syntax: "LD Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vy`." },
+    Spec { pattern: "8xy1", syntax: "OR Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vx` OR `Vy`." },
+    Spec { pattern: "8xy2", syntax: "AND Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vx` AND `Vy`." },
+    Spec { pattern: "8xy3", syntax: "XOR Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vx` XOR `Vy`." },
+    Spec { pattern: "8xy4", syntax: "ADD Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vx` + `Vy`, set `VF` = carry." },
+    Spec { pattern: "8xy5", syntax: "SUB Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vx` - `Vy`, set `VF` = NOT borrow." },
+    Spec { pattern: "8xy6", syntax: "SHR Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vx` >> 1, set `VF` = the bit shifted out." },
+    Spec { pattern: "8xy7", syntax: "SUBN Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vy` - `Vx`, set `VF` = NOT borrow." },
+    Spec { pattern: "8xyE", syntax: "SHL Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Set `Vx` = `Vx` << 1, set `VF` = the bit shifted out." },
+    Spec { pattern: "9xy0", syntax: "SNE Vx, Vy", operands: &["register", "register"], origin: "chip-8", requires: None, summary: "Skip the next instruction if `Vx` != `Vy`." },
+    Spec { pattern: "Annn", syntax: "LD I, nnn", operands: &["address"], origin: "chip-8", requires: None, summary: "Set `I` = `nnn`." },
+    Spec { pattern: "Bnnn", syntax: "JP V0, nnn", operands: &["address"], origin: "chip-8", requires: None, summary: "Jump to `nnn` + `V0`." },
+    Spec { pattern: "Bxyn", syntax: "COL Vx, Vy, n", operands: &["register", "register", "nibble"], origin: "chip-8x", requires: Some("chip-8x"), summary: "Set the foreground color of the zones at `Vx`, `Vx+1` to `Vy`." },
+    Spec { pattern: "Cxkk", syntax: "RND Vx, kk", operands: &["register", "byte"], origin: "chip-8", requires: None, summary: "Set `Vx` = a random byte AND `kk`." },
+    Spec { pattern: "Dxyn", syntax: "DRW Vx, Vy, n", operands: &["register", "register", "nibble"], origin: "chip-8", requires: None, summary: "Draw the `n` byte sprite at `I` at (`Vx`, `Vy`), set `VF` = collision." },
+    Spec { pattern: "Ex9E", syntax: "SKP Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Skip the next instruction if key `Vx` is down." },
+    Spec { pattern: "ExA1", syntax: "SKNP Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Skip the next instruction if key `Vx` is up." },
+    Spec { pattern: "ExF2", syntax: "SKP2 Vx", operands: &["register"], origin: "chip-8x", requires: Some("chip-8x"), summary: "Skip the next instruction if key `Vx` on the second keypad is down." },
+    Spec { pattern: "ExF5", syntax: "SKNP2 Vx", operands: &["register"], origin: "chip-8x", requires: Some("chip-8x"), summary: "Skip the next instruction if key `Vx` on the second keypad is up." },
+    Spec { pattern: "F000 nnnn", syntax: "LD I, long", operands: &["long address"], origin: "xo-chip", requires: None, summary: "Set `I` = the 16-bit address in the next word." },
+    Spec { pattern: "Fx07", syntax: "LD Vx, DT", operands: &["register"], origin: "chip-8", requires: None, summary: "Set `Vx` = the delay timer." },
+    Spec { pattern: "Fx0A", syntax: "LD Vx, K", operands: &["register"], origin: "chip-8", requires: None, summary: "Wait for a key press and store the key in `Vx`." },
+    Spec { pattern: "Fx15", syntax: "LD DT, Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Set the delay timer = `Vx`." },
+    Spec { pattern: "Fx18", syntax: "LD ST, Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Set the sound timer = `Vx`." },
+    Spec { pattern: "Fx1E", syntax: "ADD I, Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Set `I` = `I` + `Vx`." },
+    Spec { pattern: "Fx29", syntax: "LD F, Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Set `I` = the font sprite for digit `Vx`." },
+    Spec { pattern: "Fx33", syntax: "LD B, Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Store the BCD digits of `Vx` at `I`, `I+1`, and `I+2`." },
+    Spec { pattern: "Fx55", syntax: "LD [I], Vx", operands: &["register"], origin: "chip-8", requires: None, summary: "Store `V0` through `Vx` in memory starting at `I`." },
+    Spec { pattern: "Fx65", syntax: "LD Vx, [I]", operands: &["register"], origin: "chip-8", requires: None, summary: "Read `V0` through `Vx` from memory starting at `I`." },
+    Spec { pattern: "FxF8", syntax: "OUT Vx", operands: &["register"], origin: "chip-8x", requires: Some("chip-8x"), summary: "Set the pitch of the buzzer to `Vx`." }
+];
+
+/// An operand of an assembly mnemonic.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(VRegister),
+    Number(u16),
+    /// `I`
+    I,
+    /// `[I]`, the memory `I` points to.
+    AtI,
+    DT,
+    ST,
+    /// `K`, the next key pressed.
+    K,
+    /// `F`, the font sprite for a digit.
+    F,
+    /// `B`, the BCD digits of a value.
+    B,
+    /// `long`, the address in the next word.
+    Long
+}
+
+impl FromStr for Operand {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_ascii_uppercase();
+        match upper.as_str() {
+            "I" => Ok(Operand::I),
+            "[I]" => Ok(Operand::AtI),
+            "DT" => Ok(Operand::DT),
+            "ST" => Ok(Operand::ST),
+            "K" => Ok(Operand::K),
+            "F" => Ok(Operand::F),
+            "B" => Ok(Operand::B),
+            "LONG" => Ok(Operand::Long),
+            reg if reg.len() == 2 && reg.starts_with('V') => {
+                let n = u8::from_str_radix(&reg[1..], 16).map_err(|_| ())?;
+                n.try_into().map(Operand::V).map_err(|_| ())
+            },
+            n => match n.strip_prefix("0X") {
+                Some(hex) => u16::from_str_radix(hex, 16),
+                None => n.parse()
+            }.map(Operand::Number).map_err(|_| ())
+        }
+    }
+}
+
+/// Parses the mnemonics `Display` writes, e.g. `LD V0, 0x2a` or `JP 0x200`, 
+/// in any case. Numbers are decimal or `0x` hexadecimal.
+impl FromStr for Instruction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Instruction::*;
+        use Operand::*;
+
+        let s = s.trim();
+        let (mnemonic, operands) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let operands = match operands.trim() {
+            "" => Vec::new(),
+            operands => operands.split(',').map(|o| o.trim().parse()).collect::<Result<Vec<Operand>, _>>()?
+        };
+        let addr = |n: u16| if n <= Address::MASK { Ok(Address(n)) } else { Err(()) };
+        let byte = |n: u16| u8::try_from(n).map_err(|_| ());
+
+        match (mnemonic.to_ascii_uppercase().as_str(), operands.as_slice()) {
+            ("CLS", []) => Ok(ClearScreen),
+            ("RET", []) => Ok(Return),
+            ("EXIT", []) => Ok(Exit),
+            ("PASS", []) => Ok(Pass),
+            ("FAIL", []) => Ok(Fail),
+            ("NOP", []) => Ok(Nop),
+            ("JP", &[Number(n)]) => Ok(Jump(addr(n)?)),
+            ("JP", &[V(VRegister::V0), Number(n)]) => Ok(JumpOffset(addr(n)?)),
+            ("CALL", &[Number(n)]) => Ok(Call(addr(n)?)),
+            ("SE", &[V(vx), Number(n)]) => Ok(SkipIfEqualImm(vx, byte(n)?)),
+            ("SE", &[V(vx), V(vy)]) => Ok(SkipIfEqual(vx, vy)),
+            ("SNE", &[V(vx), Number(n)]) => Ok(SkipIfNotEqualImm(vx, byte(n)?)),
+            ("SNE", &[V(vx), V(vy)]) => Ok(SkipIfNotEqual(vx, vy)),
+            ("LD", &[V(vx), Number(n)]) => Ok(LoadImm(vx, byte(n)?)),
+            ("LD", &[V(vx), V(vy)]) => Ok(Move(vx, vy)),
+            ("LD", &[I, Number(n)]) => Ok(LoadI(addr(n)?)),
+            ("LD", &[I, Long]) => Ok(LoadLongI),
+            ("LD", &[V(vx), DT]) => Ok(LoadDT(vx)),
+            ("LD", &[V(vx), K]) => Ok(WaitKey(vx)),
+            ("LD", &[DT, V(vx)]) => Ok(StoreDT(vx)),
+            ("LD", &[ST, V(vx)]) => Ok(StoreST(vx)),
+            ("LD", &[F, V(vx)]) => Ok(LoadSprite(vx)),
+            ("LD", &[B, V(vx)]) => Ok(StoreBCD(vx)),
+            ("LD", &[AtI, V(vx)]) => Ok(Store(vx)),
+            ("LD", &[V(vx), AtI]) => Ok(Load(vx)),
+            ("ADD", &[V(vx), Number(n)]) => Ok(AddImm(vx, byte(n)?)),
+            ("ADD", &[V(vx), V(vy)]) => Ok(Add(vx, vy)),
+            ("ADD", &[I, V(vx)]) => Ok(AddI(vx)),
+            ("OR", &[V(vx), V(vy)]) => Ok(Or(vx, vy)),
+            ("AND", &[V(vx), V(vy)]) => Ok(And(vx, vy)),
+            ("XOR", &[V(vx), V(vy)]) => Ok(Xor(vx, vy)),
+            ("SUB", &[V(vx), V(vy)]) => Ok(Subtract(vx, vy)),
+            ("SUBN", &[V(vx), V(vy)]) => Ok(SubtractN(vx, vy)),
+            // Some assemblers name the unused second register too.
+            ("SHR", &[V(vx)] | &[V(vx), V(_)]) => Ok(ShiftRight(vx)),
+            ("SHL", &[V(vx)] | &[V(vx), V(_)]) => Ok(ShiftLeft(vx)),
+            ("RND", &[V(vx), Number(n)]) => Ok(AndRandom(vx, byte(n)?)),
+            ("DRW", &[V(vx), V(vy), Number(n)]) if n <= 0xF => Ok(Draw(vx, vy, n as u8)),
+            ("SKP", &[V(vx)]) => Ok(SkipIfKey(vx)),
+            ("SKNP", &[V(vx)]) => Ok(SkipIfNotKey(vx)),
+            ("BGC", []) => Ok(CycleBackground),
+            ("ADDN", &[V(vx), V(vy)]) => Ok(AddNibbles(vx, vy)),
+            ("COL", &[V(vx), V(vy), Number(n)]) if n <= 0xF => Ok(Color(vx, vy, n as u8)),
+            ("SKP2", &[V(vx)]) => Ok(SkipIfKey2(vx)),
+            ("SKNP2", &[V(vx)]) => Ok(SkipIfNotKey2(vx)),
+            ("OUT", &[V(vx)]) => Ok(Tone(vx)),
+            _ => Err(())
+        }
+    }
+}
+
 impl Display for Instruction {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
         use Instruction::*;
 
         match self {
             ClearScreen => write!(f, "CLS"),
             Return => write!(f, "RET"),
+            Exit => write!(f, "EXIT"),
+            Pass => write!(f, "PASS"),
+            Fail => write!(f, "FAIL"),
             Jump(addr) => write!(f, "JP {addr}"),
             Call(addr) => write!(f, "CALL {addr}"),
             SkipIfEqualImm(vx, b) => write!(f, "SE {vx}, {b}"),
@@ -150,40 +531,103 @@ impl Display for Instruction {
             Or(vx, vy) => write!(f, "OR {vx}, {vy}"),
             And(vx, vy) => write!(f, "AND {vx}, {vy}"),
             Xor(vx, vy) => write!(f, "XOR {vx}, {vy}"),
-            Add(vx, vy) => write!(f, "AND {vx}, {vy}"),
+            Add(vx, vy) => write!(f, "ADD {vx}, {vy}"),
             Subtract(vx, vy) => write!(f, "SUB {vx}, {vy}"),
             ShiftRight(vx) => write!(f, "SHR {vx}"),
             SubtractN(vx, vy) => write!(f, "SUBN {vx}, {vy}"),
             ShiftLeft(vx) => write!(f, "SHL {vx}"),
             SkipIfNotEqual(vx, vy) => write!(f, "SNE {vx}, {vy}"),
             LoadI(addr) => write!(f, "LD I, {addr}"),
+            LoadLongI => write!(f, "LD I, long"),
             JumpOffset(addr) => write!(f, "JP V0, {addr}"),
             AndRandom(vx, b) => write!(f, "RND {vx}, {b}"),
             Draw(vx, vy, b) => write!(f, "DRW {vx}, {vy}, {b}"),
+            SkipIfKey(vx) => write!(f, "SKP {vx}"),
+            SkipIfNotKey(vx) => write!(f, "SKNP {vx}"),
             LoadDT(vx) => write!(f, "LD {vx}, DT"),
+            WaitKey(vx) => write!(f, "LD {vx}, K"),
             StoreDT(vx) => write!(f, "LD DT, {vx}"),
+            StoreST(vx) => write!(f, "LD ST, {vx}"),
             AddI(vx) => write!(f, "ADD I, {vx}"),
-            LoadSprite(vx) => write!(f, "LD I, {vx}"),
-            StoreBCD(vx) => write!(f, "LD I, {vx}"),
+            LoadSprite(vx) => write!(f, "LD F, {vx}"),
+            StoreBCD(vx) => write!(f, "LD B, {vx}"),
             Store(vx) => write!(f, "LD [I], {vx}"),
             Load(vx) => write!(f, "LD {vx}, [I]"),
+            CycleBackground => write!(f, "BGC"),
+            AddNibbles(vx, vy) => write!(f, "ADDN {vx}, {vy}"),
+            Color(vx, vy, n) => write!(f, "COL {vx}, {vy}, {n}"),
+            SkipIfKey2(vx) => write!(f, "SKP2 {vx}"),
+            SkipIfNotKey2(vx) => write!(f, "SKNP2 {vx}"),
+            Tone(vx) => write!(f, "OUT {vx}"),
             Nop => write!(f, "NOP")
         }
     }
 }
 
-// Ex9E - SKP Vx
-// Skip next instruction if key with the value of Vx is pressed.
-// Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use alloc::string::{String, ToString};
+    use proptest::prelude::*;
 
-// ExA1 - SKNP Vx
-// Skip next instruction if key with the value of Vx is not pressed.
-// Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
+    /// Every opcode that decodes to an instruction.
+    fn instruction() -> impl Strategy<Value = Instruction> {
+        let cpu = Cpu::from_program(Vec::new()).unwrap();
+        any::<u16>().prop_filter_map("invalid opcode", move |op| cpu.decode(op).ok())
+    }
 
-// Fx0A - LD Vx, K
-// Wait for a key press, store the value of the key in Vx.
-// All execution stops until a key is pressed, then the value of that key is stored in Vx.
+    proptest! {
+        #[test]
+        fn test_decode_encode(i in instruction()) {
+            let cpu = Cpu::from_program(Vec::new()).unwrap();
+            prop_assert_eq!(cpu.decode(i.encode().unwrap()).unwrap(), i);
+        }
 
-// Fx18 - LD ST, Vx
-// Set sound timer = Vx.
-// ST is set equal to the value of Vx.
\ No newline at end of file
+        #[test]
+        fn test_parse_display(i in instruction()) {
+            prop_assert_eq!(i.to_string().parse::<Instruction>(), Ok(i));
+        }
+    }
+
+    #[test]
+    fn test_specs() {
+        let mnemonic = |s: &str| s.split_whitespace().next().unwrap_or_default().to_string();
+
+        // Everything decoded by default is in the table, as needing nothing.
+        let cpu = Cpu::from_program(Vec::new()).unwrap();
+        for op in 0..=u16::MAX {
+            if let Ok(i) = cpu.decode(op) {
+                let spec = SPECS.iter().find(|spec| spec.requires.is_none() && spec.matches(op));
+                assert_eq!(spec.map(|spec| mnemonic(spec.syntax)), Some(mnemonic(&i.to_string())), "{op:04x}");
+            }
+        }
+
+        // And everything in the table decodes, given what it requires.
+        for spec in SPECS {
+            let mut cpu = Cpu::from_program(Vec::new()).unwrap();
+            match spec.requires {
+                Some("test oracle") => cpu.set_test_oracle(true),
+                Some("chip-8x") => cpu.set_chip8x(true),
+                Some("64 row display") => cpu.set_display_rows(64),
+                requires => assert_eq!(requires, None)
+            }
+            let op: String = spec.pattern.chars().take(4).map(|c| if c.is_ascii_hexdigit() { c } else { '1' }).collect();
+            let op = u16::from_str_radix(&op, 16).unwrap();
+            assert!(spec.matches(op));
+            assert_eq!(cpu.decode(op).map(|i| mnemonic(&i.to_string())).ok(), Some(mnemonic(spec.syntax)), "{}", spec.pattern);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("ld v0, 0x2A".parse(), Ok(Instruction::LoadImm(VRegister::V0, 0x2A)));
+        assert_eq!(" drw V1,V2,15 ".parse(), Ok(Instruction::Draw(VRegister::V1, VRegister::V2, 15)));
+        assert_eq!("SHR V3, V4".parse(), Ok(Instruction::ShiftRight(VRegister::V3)));
+        assert_eq!("JP 0x1000".parse::<Instruction>(), Err(()));
+        assert_eq!("LD V0, 256".parse::<Instruction>(), Err(()));
+        assert_eq!("DRW V0, V1, 16".parse::<Instruction>(), Err(()));
+        assert_eq!(Instruction::Nop.encode(), None);
+        assert_eq!("fail".parse::<Instruction>().map(Instruction::encode), Ok(Some(0x00F2)));
+    }
+}
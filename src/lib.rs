@@ -1,8 +1,82 @@
-mod isa;
-mod screen;
-mod memory;
-mod ticker;
-mod address;
-mod register;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-pub mod cpu;
\ No newline at end of file
+extern crate alloc;
+
+mod hooks;
+
+pub mod cpu;
+pub mod memory;
+pub mod heatmap;
+pub mod isa;
+pub mod screen;
+pub mod address;
+pub mod register;
+pub mod observer;
+pub mod policy;
+pub mod platform;
+pub mod quirks;
+pub mod relocation;
+pub mod boot;
+pub mod cheat;
+#[cfg(feature = "std")]
+pub mod emulator;
+#[cfg(feature = "std")]
+pub mod input;
+#[cfg(feature = "std")]
+pub mod rom;
+#[cfg(feature = "std")]
+pub mod zip;
+#[cfg(feature = "std")]
+pub mod menu;
+#[cfg(feature = "std")]
+pub mod browser;
+#[cfg(feature = "std")]
+pub mod status;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod romdb;
+#[cfg(feature = "std")]
+pub mod screenshot;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod netplay;
+#[cfg(feature = "std")]
+pub mod serve;
+#[cfg(feature = "std")]
+pub mod api;
+#[cfg(feature = "std")]
+pub mod flash;
+#[cfg(feature = "std")]
+pub mod golden;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod reproduce;
+#[cfg(feature = "std")]
+pub mod assertion;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod pacing;
+#[cfg(feature = "std")]
+pub mod overlay;
+#[cfg(feature = "std")]
+pub mod hexview;
+#[cfg(feature = "std")]
+pub mod sprite;
+#[cfg(feature = "std")]
+pub mod cast;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod rewind;
+pub mod snapshot;
+#[cfg(feature = "embedded")]
+pub mod embedded;
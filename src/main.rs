@@ -1,42 +1,1505 @@
-use chip8::cpu::{Cpu, CpuError};
-use std::{env, sync::mpsc};
-use chrono::Duration;
+use chip8::{
+    cpu::{self, Cpu, CpuError, Verdict}, input::{self, AlternateScreen, HeldKeys, HostCommand, RawTerminal}, 
+    rom::{self, RomWatcher}, observer::Tracer, emulator::{self, Command, Emulator, Event, Limits},
+    policy::{ErrorKind, ErrorPolicy}, platform::Platform, memory::WriteProtection, 
+    quirks::IndexOverflow, heatmap::HeatMap, address::Address, menu::{CheatMenu, MenuItem, PauseMenu}, screen::Screen,
+    browser::{Browser, RomEntry}, rom::PlayHistory, status::StatusBar, 
+    config::{self, Config, Settings}, input::Keymap, screen::{ColorDepth, Palette}, screenshot,
+    compare::{self, Comparison}, netplay::{self, InputDelay, Message, Peer}, screen::NCOLS,
+    serve::Server, api, flash::FlashLimiter, replay::{Recorder, Replay, Script}, trace::{self, TraceWriter},
+    reproduce, assertion::Assertion, golden, pacing::Intervals, overlay::{self, KeyLog, Registers}, cpu::SpriteDraw,
+    hexview::{self, HexView}, isa, input::EditorCommand, sprite::{self, SpriteEditor},
+    relocation::Relocations, boot::{self, Boot}, cast::Cast, cheat::{self, Freeze, Kind}
+};
+use std::{
+    io::{self, Write}, path::{Path, PathBuf}, process::ExitCode, thread, 
+    time::{Duration, Instant}
+};
+#[cfg(feature = "metrics")]
+use {chip8::metrics::Metrics, std::sync::Arc};
+use clap::{Parser, Subcommand};
+use crossterm::terminal;
+use tracing_subscriber::EnvFilter;
 
-fn main() -> Result<(), CpuError> {
-    let args = env::args().collect::<Vec<_>>();
-    let mut cpu = Cpu::new(args[1].clone().into())?;
+/// How often the ROM file on disk is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// Shown in place of a hotkey's effect in deterministic mode.
+const NOT_DETERMINISTIC: &str = "not available in deterministic mode";
+/// How long the frontend waits for an event before polling the keyboard again.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
 
-    let (tx, rx) = mpsc::channel();
-    let timer = timer::MessageTimer::new(tx);
+#[derive(Parser, Clone)]
+#[command(about = "A CHIP-8 emulator", subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Subcommands>,
+    /// Path to the ROM to run. A `.zip` archive runs the first ROM in it,
+    /// or name one inside it, e.g. `roms.zip/games/pong.ch8`.
+    #[arg(required = true)]
+    rom: Option<PathBuf>,
+    /// Seed for the random number generator used by `RND`, for reproducible 
+    /// runs.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Make the run depend only on the ROM and its input: seed `RND` with 0
+    /// unless `--seed` is given, and turn off the hotkeys that reset, rewind,
+    /// load a state, switch ROMs, or change the speed. Timers always count
+    /// emulated frames rather than wall-clock time.
+    #[arg(long)]
+    deterministic: bool,
+    /// Press keys as recorded in this file, a line per key event of the form
+    /// `<frame> <key> down|up`, e.g. `120 a down`.
+    #[arg(long, value_name = "PATH")]
+    replay: Option<PathBuf>,
+    /// Record key presses and releases to this file, to `--replay` later.
+    #[arg(long, value_name = "PATH")]
+    record_input: Option<PathBuf>,
+    /// File used to save (F2) and load (F4) the machine state. Defaults to the
+    /// ROM path with a `.state` extension.
+    #[arg(long)]
+    state: Option<PathBuf>,
+    /// Restore the machine state from the state file before starting.
+    #[arg(long)]
+    load_state: bool,
+    /// Settings file, which flags given here take precedence over. Defaults 
+    /// to `~/.config/chip8/config.toml`.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// The CHIP-8 variant the ROM targets: `chip8`, `xo-chip`, `eti-660`,
+    /// or `chip-8x`.
+    /// Looked up for well-known ROMs, and otherwise detected from the ROM,
+    /// by default.
+    #[arg(long, value_parser = parse_platform)]
+    platform: Option<Platform>,
+    /// Where the ROM is loaded and starts executing, e.g. `0x600`. Defaults to
+    /// the platform's usual address, 0x200 for all but the ETI-660 and CHIP-8X.
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_load_address)]
+    load_address: Option<u16>,
+    /// Rows in the display: 32, 48, or 64. Defaults to the platform's, 48 for
+    /// the ETI-660 and 32 for the rest.
+    #[arg(long, value_name = "ROWS", value_parser = parse_display_rows)]
+    display_rows: Option<usize>,
+    /// A file of digit sprites to use in place of the built-in ones: 80 bytes,
+    /// or 240 with a hi-res font after them.
+    #[arg(long, value_name = "PATH")]
+    font: Option<PathBuf>,
+    /// Where the font is loaded, e.g. `0x50` [default: 0].
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_load_address)]
+    font_address: Option<u16>,
+    /// Move the ROM's own addresses along with `--load-address`, at the
+    /// offsets listed in this file, a hex offset per line. For ROMs
+    /// assembled to run from 0x200.
+    #[arg(long, value_name = "PATH")]
+    relocations: Option<PathBuf>,
+    /// What to run before the ROM, and again on every reset: `none`,
+    /// `splash` to show a logo for a second, or the path of a boot program
+    /// of your own, loaded at 0x100, that jumps to the ROM [default: none].
+    #[arg(long, value_name = "none|splash|PATH", value_parser = parse_boot)]
+    boot: Option<Boot>,
+    /// Hold a byte of memory or a register at a value, e.g. `0x2f0=3` to keep
+    /// the lives at 0x2f0 at 3, or `v5=9`. Can be given more than once.
+    #[arg(long, value_name = "TARGET=VALUE", value_parser = parse_freeze)]
+    freeze: Vec<Freeze>,
+    /// Cheats to turn on and off with F10, in place of the ROM's file in the
+    /// `cheats` directory beside the config file, named after its SHA-1.
+    #[arg(long, value_name = "PATH")]
+    cheats: Option<PathBuf>,
+    /// Emulation speed in instructions per second, from 1 to 1000000
+    /// [default: 700].
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=i64::from(config::MAX_IPS)))]
+    ips: Option<u32>,
+    /// How to handle faulting instructions: `halt`, `skip`, `log`, or `trap` 
+    /// (pause). Prefix with an error kind, e.g. `invalid-instruction=skip` or 
+    /// `segfault=trap`, to override the policy for that kind only. May be 
+    /// repeated.
+    #[arg(long = "on-error", value_name = "[KIND=]POLICY", value_parser = parse_error_policy)]
+    error_policies: Vec<(Option<ErrorKind>, ErrorPolicy)>,
+    /// Fault on writes to the interpreter area (`interpreter`) or to the 
+    /// interpreter area and the loaded ROM (`program`). Combine with 
+    /// `--on-error write-protected=log` to warn and skip the write instead.
+    /// [default: off]
+    #[arg(long, value_parser = parse_write_protection)]
+    protect: Option<WriteProtection>,
+    /// Have `Fx1E` set VF when I overflows past 0xFFF, like the Amiga 
+    /// interpreter. Needed by Spacefight 2091!
+    #[arg(long)]
+    add_i_overflow: bool,
+    /// What `Fx1E` does when I would point past the end of memory: `wrap` or 
+    /// `fault`. [default: wrap]
+    #[arg(long, value_parser = parse_index_overflow)]
+    index_overflow: Option<IndexOverflow>,
+    /// Start with the display scaled to fill the terminal (toggle with F11).
+    #[arg(long)]
+    fullscreen: bool,
+    /// Show the keypad keys pressed, from the keyboard or `--replay`, in the
+    /// bottom corner of the display, e.g. for recordings (toggle with F9).
+    #[arg(long)]
+    show_keys: bool,
+    /// Keep running while the terminal is in the background, rather than 
+    /// pausing until it's back.
+    #[arg(long)]
+    no_auto_pause: bool,
+    /// Draw in yellow on black, regardless of the terminal's colors or the 
+    /// configured palette.
+    #[arg(long)]
+    high_contrast: bool,
+    /// Swap the colors of lit and unlit pixels.
+    #[arg(long)]
+    invert: bool,
+    /// How colors are drawn: `truecolor`, or `256` for terminals without
+    /// 24-bit color. [default: truecolor]
+    #[arg(long, value_parser = parse_color_depth)]
+    color_depth: Option<ColorDepth>,
+    /// Hold back frames that would flash most of the display more than three
+    /// times a second, for photosensitive players.
+    #[arg(long)]
+    reduce_flashing: bool,
+    /// Count reads and writes to every memory address and write them to this
+    /// file as a colorized heat map on exit (view it with `less -R`).
+    #[arg(long, value_name = "PATH")]
+    heatmap: Option<PathBuf>,
+    /// Where to write the core dump (registers, stack, disassembly, and 
+    /// memory) if the program faults.
+    #[arg(long, value_name = "PATH", default_value = "core")]
+    core_dump: PathBuf,
+    /// If the program faults, write a copy of the ROM with every byte the run
+    /// never read zeroed out to this file, for bug reports. It is run again
+    /// headlessly to check it faults the same way.
+    #[arg(long, value_name = "PATH")]
+    reproducer: Option<PathBuf>,
+    /// Run the program with the experimental JIT backend, which translates 
+    /// basic blocks into closures. Disables instruction tracing.
+    #[arg(long)]
+    jit: bool,
+    /// Write every executed instruction to this file as JSON Lines, with the
+    /// registers it changed, I, and the timers, to compare with `trace-diff`.
+    #[arg(long, value_name = "PATH", conflicts_with = "jit")]
+    trace: Option<PathBuf>,
+    /// Which events to log to stderr, e.g. `debug` or `warn,execute=trace`. 
+    /// The targets are `frame` (a span around each 60Hz frame), `fetch`, 
+    /// `decode`, `execute`, `timer`, `draw`, and `input`. Overrides 
+    /// `RUST_LOG`; defaults to `warn`.
+    #[arg(long, value_name = "FILTER", value_parser = parse_log_level)]
+    log_level: Option<String>,
+    /// Host a two-player game on this address, e.g. `0.0.0.0:7878`, waiting
+    /// for the other player to `join` before starting.
+    #[arg(long, value_name = "ADDRESS")]
+    host: Option<String>,
+    /// When hosting, how many frames to hold back local key presses so both
+    /// players see the same lag. [default: 3]
+    #[arg(long, value_name = "FRAMES")]
+    input_delay: Option<u32>,
+    /// Also stream the display over WebSocket on this address, e.g. 
+    /// `0.0.0.0:8080`, and take key presses from it. Open the address in a
+    /// browser to play.
+    #[arg(long, value_name = "ADDRESS")]
+    serve: Option<String>,
+    /// Serve an HTTP API on this address, e.g. `127.0.0.1:8000`, to load 
+    /// ROMs, pause, resume, step, press keys, and read the registers, 
+    /// memory, and display as a PNG from scripts and other tools.
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["headless", "host"])]
+    api: Option<String>,
+    /// Serve Prometheus metrics on this address, e.g. `0.0.0.0:9100`, at 
+    /// `/metrics`: instructions, draws, frames, errors, and buzzer time so 
+    /// far, and the current speed and timers.
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "headless")]
+    metrics: Option<String>,
+    /// Run a Rhai script's `on_frame` after every frame and `on_instruction`
+    /// after every instruction, with the registers, memory, and keypad 
+    /// bound to `this`.
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "PATH")]
+    script: Option<PathBuf>,
+    /// Show how evenly frames are run and presented under the status bar,
+    /// and report it on exit, to diagnose games running fast, slow, or 
+    /// stuttering.
+    #[arg(long, conflicts_with = "headless")]
+    pacing: bool,
+    /// Record what's drawn to this file in asciinema's format, to replay with
+    /// `asciinema play` or embed on a web page.
+    #[arg(long, value_name = "PATH", conflicts_with = "headless")]
+    record_cast: Option<PathBuf>,
+    /// Run as fast as possible without drawing anything or reading the 
+    /// keyboard, until the program exits, traps, or waits for a key. For CI 
+    /// runs, fuzzing, and benchmarks. Each kind of error exits with its own 
+    /// status, from 10 (`stack-overflow`) to 24 (`script-error`).
+    #[arg(long, conflicts_with_all = ["fullscreen", "host", "serve"])]
+    headless: bool,
+    /// Stop a headless run after this many instructions.
+    #[arg(long, visible_alias = "cycles", value_name = "N", requires = "headless")]
+    max_cycles: Option<u64>,
+    /// Stop a headless run after this many 60Hz frames.
+    #[arg(long, value_name = "N", requires = "headless")]
+    max_frames: Option<u64>,
+    /// When a headless run ends, however it ends, write the display (as `#`
+    /// and `.`) and the registers to this file.
+    #[arg(long, value_name = "PATH", requires = "headless")]
+    dump_screen_on_exit: Option<PathBuf>,
+    /// Run a test ROM that reports its result with the pseudo-instructions
+    /// `00F1` (`PASS`) and `00F2` (`FAIL`). Exits with 0 on `PASS`, and with
+    /// the `test-failed` status on `FAIL` or if the program stops without
+    /// either.
+    #[arg(long, requires = "headless")]
+    test_oracle: bool,
+    /// Check the machine state when a headless run ends, e.g. `V3==0x1F`,
+    /// `mem[0x300]==7`, or `I>=0x400`, failing with the `test-failed` status
+    /// if it doesn't hold. The program jumping to itself ends the run rather
+    /// than being an error. May be repeated.
+    #[arg(long = "assert", value_name = "CONDITION", value_parser = parse_assertion, requires = "headless")]
+    assertions: Vec<Assertion>,
+    /// Check the display when a headless run ends against a snapshot written
+    /// as `#` and `.`, like `--dump-screen-on-exit` writes. Set
+    /// `CHIP8_UPDATE_GOLDEN=1` to record it instead.
+    #[arg(long, value_name = "PATH", requires = "headless")]
+    assert_screen: Option<PathBuf>
+}
 
-    // Start repeating.
-    let _guard = timer.schedule_repeating(Duration::microseconds(1000), ());
+#[derive(Subcommand, Clone)]
+enum Subcommands {
+    /// Pick a ROM from a directory to play, returning to the list when the 
+    /// game exits. The other options apply to every game launched.
+    Browse {
+        /// Directory to list `.ch8` and `.sc8` ROMs from.
+        dir: PathBuf
+    },
+    /// Pick one of the ROMs played recently to launch again.
+    Recent,
+    /// Run two copies of a ROM side by side on the same input, one of them
+    /// with different quirks or a patched ROM, and pause on the first frame
+    /// after which their registers, memory, or displays differ. With 
+    /// `--headless`, report that frame instead, and exit with 1 if there is
+    /// one. The other options apply to both.
+    Compare {
+        /// ROM to run on the left.
+        rom: PathBuf,
+        /// Run this ROM on the right instead, e.g. a patched build.
+        #[arg(long, value_name = "PATH")]
+        patched: Option<PathBuf>,
+        /// Turn on the `Fx1E` VF overflow quirk on the right only.
+        #[arg(long)]
+        right_add_i_overflow: bool,
+        /// What `Fx1E` does past the end of memory on the right only.
+        #[arg(long, value_parser = parse_index_overflow)]
+        right_index_overflow: Option<IndexOverflow>
+    },
+    /// Play a game hosted elsewhere with `--host`. The host runs the game;
+    /// this only sends key presses and shows the display.
+    Join {
+        /// The host's address, e.g. `192.168.1.2:7878`.
+        addr: String
+    },
+    /// Compare two instruction traces written with `--trace`, or by another
+    /// emulator in the same format, and show where they first differ. Exits
+    /// with 1 if they differ.
+    TraceDiff {
+        a: PathBuf,
+        b: PathBuf
+    },
+    /// List the instructions the decoder supports, with their opcodes,
+    /// assembly, and what has to be turned on for them.
+    Isa {
+        /// Print them as a JSON array, for other tools to read.
+        #[arg(long)]
+        json: bool
+    },
+    /// Draw an 8xN sprite on a grid, and print its bytes on exit, for
+    /// pasting into a ROM's source.
+    SpriteEdit {
+        /// Rows to start with, up to 15.
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(1..=15))]
+        height: u8,
+        /// Start from these bytes instead, as hex, e.g. `f0 90 90 90 f0`.
+        #[arg(long, value_name = "HEX", value_parser = parse_sprite, conflicts_with = "height")]
+        from: Option<Vec<u8>>,
+        /// Print `DB` directives rather than bare hex bytes.
+        #[arg(long)]
+        db: bool
+    }
+}
+
+fn parse_error_policy(s: &str) -> Result<(Option<ErrorKind>, ErrorPolicy), String> {
+    let (kind, policy) = match s.split_once('=') {
+        Some((kind, policy)) => {
+            let kind = kind.parse().map_err(|_| format!("unknown error kind `{kind}`"))?;
+            (Some(kind), policy)
+        },
+        None => (None, s)
+    };
+
+    let policy = policy.parse().map_err(|_| format!("unknown error policy `{policy}`"))?;
+    Ok((kind, policy))
+}
+
+fn parse_sprite(s: &str) -> Result<Vec<u8>, String> {
+    let bytes = s.split([' ', ',']).filter(|byte| !byte.is_empty())
+        .map(|byte| u8::from_str_radix(byte.trim_start_matches("0x"), 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("`{s}` isn't a list of hex bytes"))?;
+    match bytes.len() {
+        1..=sprite::MAX_HEIGHT => Ok(bytes),
+        _ => Err(format!("a sprite has 1 to {} rows", sprite::MAX_HEIGHT))
+    }
+}
+
+fn parse_boot(s: &str) -> Result<Boot, String> {
+    s.parse().map_err(|_| format!("invalid boot program `{s}`"))
+}
+
+fn parse_freeze(s: &str) -> Result<Freeze, String> {
+    s.parse().map_err(|_| format!("expected `<address or register>=<value>`, e.g. `0x2f0=3`, found `{s}`"))
+}
+
+fn parse_platform(s: &str) -> Result<Platform, String> {
+    s.parse().map_err(|_| format!("unknown platform `{s}`"))
+}
+
+fn parse_display_rows(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(rows @ (32 | 48 | 64)) => Ok(rows),
+        _ => Err(format!("`{s}` rows isn't a display size; use 32, 48, or 64"))
+    }
+}
+
+fn parse_load_address(s: &str) -> Result<u16, String> {
+    let address = match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse()
+    };
+    address.map_err(|_| format!("invalid address `{s}`"))
+}
+
+fn parse_write_protection(s: &str) -> Result<WriteProtection, String> {
+    s.parse().map_err(|_| format!("unknown write protection mode `{s}`"))
+}
+
+fn parse_assertion(s: &str) -> Result<Assertion, String> {
+    s.parse().map_err(|_| format!("invalid assertion `{s}`"))
+}
+
+fn parse_color_depth(s: &str) -> Result<ColorDepth, String> {
+    s.parse().map_err(|_| format!("unknown color depth `{s}`, expected `truecolor` or `256`"))
+}
+
+fn parse_index_overflow(s: &str) -> Result<IndexOverflow, String> {
+    s.parse().map_err(|_| format!("unknown index overflow behaviour `{s}`"))
+}
+
+fn parse_log_level(s: &str) -> Result<String, String> {
+    EnvFilter::try_new(s)
+        .map(|_| s.to_string())
+        .map_err(|e| format!("invalid log filter `{s}`: {e}"))
+}
+
+/// The cheats for `rom`, from `--cheats` or its file in the cheats
+/// directory, and where they were looked for.
+fn load_cheats(settings: &Settings, rom: &Path) -> Result<(CheatMenu, PathBuf), CpuError> {
+    let path = match &settings.cheats {
+        Some(path) => path.clone(),
+        None => cheat::default_path(&rom::read(rom)?)
+    };
+    Ok((CheatMenu::new(cheat::load(&path)?), path))
+}
+
+fn switch_rom(emulator: &Emulator, watcher: &mut RomWatcher, step: isize) -> io::Result<()> {
+    if let Some(path) = rom::neighbour(watcher.path(), step)? {
+        emulator.send(Command::LoadRom(path.clone()));
+        watcher.watch(path);
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let filter = args.log_level.as_deref()
+        .map(EnvFilter::new)
+        .or_else(|| EnvFilter::try_from_default_env().ok())
+        .unwrap_or_else(|| EnvFilter::new("warn"));
+    tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr).init();
+
+    let result = match (&args.command, &args.rom) {
+        (Some(Subcommands::Browse { dir }), _) => browse(&args, |history| Browser::scan(dir, history)),
+        (Some(Subcommands::Recent), _) => browse(&args, |history| Ok(Browser::recent(history))),
+        (Some(Subcommands::Compare { rom, patched, right_add_i_overflow, right_index_overflow }), _) => {
+            let right = Settings {
+                add_i_overflow: right_add_i_overflow.then_some(true),
+                index_overflow: *right_index_overflow,
+                ..Settings::default()
+            };
+            match compare(&args, rom, patched.as_deref().unwrap_or(rom), right) {
+                Ok(false) => return ExitCode::FAILURE,
+                result => result.map(drop)
+            }
+        },
+        (Some(Subcommands::Join { addr }), _) => join(&args, addr),
+        (Some(Subcommands::Isa { json }), _) => {
+            isa(*json);
+            Ok(())
+        },
+        (Some(Subcommands::SpriteEdit { height, from, db }), _) => {
+            let editor = match from {
+                Some(bytes) => SpriteEditor::from_bytes(bytes),
+                None => SpriteEditor::new((*height).into())
+            };
+            sprite_edit(editor, *db)
+        },
+        (Some(Subcommands::TraceDiff { a, b }), _) => match trace_diff(a, b) {
+            // Like `diff`, exit with 1 when the traces differ.
+            Ok(false) => return ExitCode::FAILURE,
+            result => result.map(drop)
+        },
+        (None, Some(rom)) => run(&args, rom.clone()),
+        // Clap requires the ROM when there's no subcommand.
+        (None, None) => unreachable!()
+    };
+
+    // Run to completion first so the terminal is restored before reporting.
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(e.kind().exit_code())
+        }
+    }
+}
+
+/// Launch ROMs picked from the browser built by `list`, coming back to it
+/// when each game exits.
+fn browse(args: &Args, list: impl Fn(&PlayHistory) -> io::Result<Browser>) -> Result<(), CpuError> {
+    let mut status = None;
+    let mut last: Option<PathBuf> = None;
 
     loop {
-        let fetched = cpu.fetch()
-            .or_else(|e| {
-                eprintln!("{}", cpu);
-                cpu.dump_core();
-                format!("{e:?}");
-                Err(e)
-            })?;
-        let decoded = cpu.decode(fetched)
-            .or_else(|e| {
-                eprintln!("{}", cpu);
-                cpu.dump_core();
-                format!("{e:?}");
-                Err(e)
-            })?;
-
-        eprintln!("{fetched:04x} => {decoded:?}");
-        cpu.execute(decoded)
-            .or_else(|e| {
-                eprintln!("{}", cpu);
-                cpu.dump_core();
-                format!("{e:?}");
-                Err(e)
-            })?;
-
-        rx.recv().unwrap()
-    }
-}
\ No newline at end of file
+        // Reload each time, since `run` records the game just played.
+        let history = PlayHistory::load(PlayHistory::default_path());
+        let mut browser = list(&history)?;
+        if let Some(path) = &last {
+            browser.select(path);
+        }
+
+        let Some(entry) = pick(&mut browser, status.take())? else {
+            return Ok(());
+        };
+
+        if let Err(e) = run(args, entry.path.clone()) {
+            status = Some(format!("{} stopped: {e}", entry.path.display()));
+        }
+        last = Some(entry.path);
+    }
+}
+
+/// Let the user choose a ROM from `browser`, or `None` if they quit.
+fn pick(browser: &mut Browser, status: Option<String>) -> Result<Option<RomEntry>, CpuError> {
+    let _raw = RawTerminal::enable()?;
+    let mut buffer = String::new();
+    let mut redraw = true;
+
+    loop {
+        if redraw {
+            browser.render_into(&mut buffer);
+            if let Some(status) = &status {
+                buffer.push_str(&format!("\r\n{status}\r\n"));
+            }
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(buffer.as_bytes())?;
+            stdout.flush()?;
+            redraw = false;
+        }
+
+        match input::poll(&Keymap::default())? {
+            Some(HostCommand::Up) => browser.up(),
+            Some(HostCommand::Down) => browser.down(),
+            Some(HostCommand::Select) => return Ok(browser.selected().cloned()),
+            Some(HostCommand::Quit | HostCommand::Back) => return Ok(None),
+            Some(HostCommand::Resize) => (),
+            Some(_) => continue,
+            None => {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+        }
+        redraw = true;
+    }
+}
+
+fn run(args: &Args, rom: PathBuf) -> Result<(), CpuError> {
+    let program = rom::read(&rom)?;
+    // Scripted runs aren't games anybody played.
+    if !args.headless {
+        if let Err(e) = PlayHistory::load(PlayHistory::default_path()).record(&rom, &program) {
+            tracing::warn!("Failed to save play history: {e}");
+        }
+    }
+    let config = Config::load(&args.config.clone().unwrap_or_else(Config::default_path))?;
+    let settings = settings(args, &config, &program);
+
+    let platform = settings.platform.unwrap_or_else(|| Platform::detect(&program));
+    let mut memory = platform.memory();
+    // The reproducer keeps only the bytes the heat map saw read.
+    let heatmap = (args.heatmap.is_some() || args.reproducer.is_some()).then(|| HeatMap::new(memory.len()));
+    if let Some(heatmap) = &heatmap {
+        memory = heatmap.attach(memory);
+    }
+
+    let mut cpu = Cpu::with_memory(program.clone(), memory)?;
+    set_platform(&mut cpu, platform, &settings)?;
+    let load_address = cpu.load_address();
+    // Loading the ROM isn't interesting.
+    if let Some(heatmap) = &heatmap {
+        heatmap.clear();
+    }
+    let seed = match settings.deterministic {
+        Some(true) => Some(args.seed.unwrap_or(0)),
+        _ => args.seed
+    };
+    if let Some(seed) = seed {
+        cpu.set_seed(seed);
+    }
+    let seed = cpu.seed();
+
+    let state = args.state.clone().unwrap_or_else(|| rom.with_extension("state"));
+    if args.load_state {
+        cpu.load_state(&state)?;
+    }
+
+    configure(&mut cpu, args, &settings)?;
+
+    cpu.attach(Tracer);
+    if let Some(path) = &args.trace {
+        cpu.attach(TraceWriter::create(path)?);
+    }
+    cpu.set_jit(args.jit);
+    cpu.set_test_oracle(args.test_oracle);
+
+    let mut script = Script {
+        replay: args.replay.as_deref().map(Replay::load).transpose()?,
+        recorder: args.record_input.as_deref().map(Recorder::create).transpose()?
+    };
+    let result = if args.headless {
+        let limits = Limits { cycles: args.max_cycles, frames: args.max_frames };
+        let result = emulator::run_headless(&mut cpu, limits, &mut script, &args.core_dump)
+            .map(|stats| eprintln!("{stats}"));
+        if let Some(path) = &args.dump_screen_on_exit {
+            let mut dump = String::new();
+            cpu.screen().render_text_into(&mut dump);
+            std::fs::write(path, format!("{dump}\n{cpu}"))?;
+        }
+        let checked = !args.assertions.is_empty() || args.assert_screen.is_some();
+        let result = match result {
+            // Test programs commonly end by jumping to themselves.
+            Err(e) if checked && e.kind() == ErrorKind::InfiniteLoop => Ok(()),
+            result => result
+        };
+        match (result, args.test_oracle) {
+            (Ok(()), true) => match cpu.verdict() {
+                Some(Verdict::Pass) => Ok(()),
+                Some(Verdict::Fail(pc)) => Err(CpuError::TestFailed(format!("test failed at {pc}"))),
+                None => Err(CpuError::TestFailed("the test stopped without passing or failing".to_string()))
+            },
+            (result, _) => result
+        }.and_then(|()| check_assertions(args, &cpu))
+    } else {
+        play(args, cpu, rom, &state, &settings, script)
+    };
+
+    if let (Err(e), Some(heatmap), Some(path)) = (&result, &heatmap, &args.reproducer) {
+        let platform = settings.platform.unwrap_or_else(|| Platform::detect(&program));
+        let reproducer = reproduce::minimize(&program, load_address, heatmap);
+        std::fs::write(path, &reproducer)?;
+
+        let mut cpu = Cpu::with_memory(reproducer, platform.memory())?;
+        set_platform(&mut cpu, platform, &settings)?;
+        cpu.set_seed(seed);
+        configure(&mut cpu, args, &settings)?;
+        let limits = Limits { cycles: args.max_cycles, frames: args.max_frames.or(Some(reproduce::CHECK_FRAMES)) };
+        if reproduce::reproduces(&mut cpu, limits, e) {
+            eprintln!("wrote a reproducer to {}", path.display());
+        } else {
+            eprintln!(
+                "wrote a reproducer to {}, but it doesn't fault the same way on its own; the fault may depend \
+                 on input (see --record-input) or a loaded state", path.display()
+            );
+        }
+    }
+    if let (Some(heatmap), Some(path)) = (heatmap, &args.heatmap) {
+        heatmap.save(path)?;
+    }
+    result
+}
+
+/// Check the state `cpu` was left in against `--assert` and `--assert-screen`,
+/// reporting every one that fails.
+fn check_assertions(args: &Args, cpu: &Cpu) -> Result<(), CpuError> {
+    let mut failed: Vec<String> = args.assertions.iter().filter_map(|a| a.check(cpu).err()).collect();
+    if let Some(path) = &args.assert_screen {
+        failed.extend(golden::check(path, cpu.screen()).err());
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(CpuError::TestFailed(format!("assertion failed: {}", failed.join("\nassertion failed: "))))
+    }
+}
+
+/// Run `cpu` in the terminal, and over the network if asked to.
+fn play(
+    args: &Args, cpu: Cpu, rom: PathBuf, state: &Path, settings: &Settings, script: Script
+) -> Result<(), CpuError> {
+    let netplay = match &args.host {
+        Some(addr) => {
+            println!("Waiting for the other player to join on {addr}...");
+            let peer = Peer::host(addr)?;
+            Some(Netplay { peer, delay: InputDelay::new(args.input_delay.unwrap_or(netplay::DEFAULT_INPUT_DELAY)) })
+        },
+        None => None
+    };
+
+    let server = args.serve.as_deref().map(Server::bind).transpose()?;
+
+    #[cfg(feature = "metrics")]
+    let metrics = match &args.metrics {
+        Some(addr) => {
+            let metrics = Arc::new(Metrics::default());
+            metrics.serve(addr.as_str())?;
+            Some(metrics)
+        },
+        None => None
+    };
+    #[cfg(not(feature = "metrics"))]
+    let metrics = None;
+
+    let mut watcher = RomWatcher::new(rom);
+    let raw = RawTerminal::enable()?;
+    let emulator = Emulator::spawn(cpu, args.core_dump.clone(), script, metrics);
+    if let Some(addr) = &args.api {
+        api::serve(addr.as_str(), emulator.commands())?;
+    }
+    if settings.deterministic.unwrap_or_default() {
+        emulator.send(Command::CatchUpTimers(false));
+    }
+    let mut presented = args.pacing.then(|| Intervals::new(None));
+    let cast = match &args.record_cast {
+        Some(path) => {
+            let (cols, rows) = terminal::size()?;
+            Some(Cast::create(path, cols, rows)?)
+        },
+        None => None
+    };
+    let recording = Recording { pacing: presented.as_mut(), cast };
+    let result = frontend(&emulator, &raw, &mut watcher, state, settings, Remotes { netplay, server }, recording);
+    let stats = emulator.finish();
+    // Print after leaving raw mode, so the lines start where they should.
+    drop(raw);
+    if result.is_ok() {
+        eprintln!("{stats}");
+    }
+    if let Some(presented) = presented {
+        eprintln!("timer ticks:      {}", stats.ticks);
+        eprintln!("timer catch-up:   {} ticks", stats.caught_up);
+        eprintln!("presented frames: {}", presented.stats());
+    }
+    result
+}
+
+/// The command-line and config file settings for `program`.
+fn settings(args: &Args, config: &Config, program: &[u8]) -> Settings {
+    let cli = Settings {
+        ips: args.ips,
+        platform: args.platform,
+        load_address: args.load_address,
+        display_rows: args.display_rows,
+        font: args.font.clone(),
+        font_address: args.font_address,
+        relocations: args.relocations.clone(),
+        boot: args.boot.clone(),
+        freeze: (!args.freeze.is_empty()).then(|| args.freeze.clone()),
+        cheats: args.cheats.clone(),
+        protect: args.protect,
+        add_i_overflow: args.add_i_overflow.then_some(true),
+        index_overflow: args.index_overflow,
+        fullscreen: args.fullscreen.then_some(true),
+        show_keys: args.show_keys.then_some(true),
+        auto_pause: args.no_auto_pause.then_some(false),
+        high_contrast: args.high_contrast.then_some(true),
+        invert: args.invert.then_some(true),
+        color_depth: args.color_depth,
+        reduce_flashing: args.reduce_flashing.then_some(true),
+        deterministic: args.deterministic.then_some(true),
+        ..Settings::default()
+    };
+    cli.or(&config.settings(program))
+}
+
+/// Apply the speed, error policies, write protection, quirks, frozen
+/// values, and script from `args` and `settings`, falling back to the 
+/// builtin defaults.
+fn configure(cpu: &mut Cpu, args: &Args, settings: &Settings) -> Result<(), CpuError> {
+    cpu.set_ips(settings.ips.unwrap_or(cpu::DEFAULT_IPS));
+    for &(kind, policy) in &args.error_policies {
+        match kind {
+            Some(kind) => cpu.error_policies().set(kind, policy),
+            None => cpu.error_policies().set_default(policy)
+        }
+    }
+    cpu.set_write_protection(settings.protect.unwrap_or_default());
+    cpu.quirks().add_i_overflow = settings.add_i_overflow.unwrap_or_default();
+    cpu.quirks().index_overflow = settings.index_overflow.unwrap_or_default();
+    for &freeze in settings.freeze.iter().flatten() {
+        cpu.freeze(freeze)?;
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(path) = &args.script {
+        chip8::scripting::UserScript::load(path)?.attach(cpu);
+    }
+    Ok(())
+}
+
+/// Load `rom` into a machine set up with `settings`.
+fn machine(args: &Args, rom: &Path, settings: &Settings) -> Result<Cpu, CpuError> {
+    let program = rom::read(rom)?;
+    let platform = settings.platform.unwrap_or_else(|| Platform::detect(&program));
+    let mut cpu = Cpu::with_memory(program, platform.memory())?;
+    set_platform(&mut cpu, platform, settings)?;
+    configure(&mut cpu, args, settings)?;
+    Ok(cpu)
+}
+
+/// Set `cpu` up for `platform`: where the ROM is loaded and its relocations,
+/// how tall the display is, which instructions it has, its font, and what
+/// boots it, unless `settings` say otherwise.
+fn set_platform(cpu: &mut Cpu, platform: Platform, settings: &Settings) -> Result<(), CpuError> {
+    if let Some(path) = &settings.relocations {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            CpuError::InvalidConfig(format!("Failed to read relocations {}: {e}", path.display()))
+        })?;
+        cpu.set_relocations(Relocations::parse(&text)?)?;
+    }
+    cpu.set_load_address(settings.load_address.map(Address).unwrap_or_else(|| platform.load_address()))?;
+    cpu.set_display_rows(settings.display_rows.unwrap_or_else(|| platform.display_rows()));
+    cpu.set_chip8x(platform == Platform::Chip8X);
+    if settings.font.is_some() || settings.font_address.is_some() {
+        let font = match &settings.font {
+            Some(path) => std::fs::read(path).map_err(|e| {
+                CpuError::InvalidConfig(format!("Failed to read font {}: {e}", path.display()))
+            })?,
+            None => cpu.font().to_vec()
+        };
+        cpu.set_font(font, Address(settings.font_address.unwrap_or(0)))?;
+    }
+    let boot = match settings.boot.clone().unwrap_or_default() {
+        Boot::None => None,
+        Boot::Splash => Some(boot::splash(cpu.load_address(), cpu.screen().height())),
+        Boot::Program(path) => Some(std::fs::read(&path).map_err(|e| {
+            CpuError::InvalidConfig(format!("Failed to read boot program {}: {e}", path.display()))
+        })?)
+    };
+    cpu.set_boot(boot)?;
+    Ok(())
+}
+
+/// Returns whether the machines stayed the same.
+fn compare(args: &Args, left: &Path, right: &Path, overrides: Settings) -> Result<bool, CpuError> {
+    let config = Config::load(&args.config.clone().unwrap_or_else(Config::default_path))?;
+    let left_settings = settings(args, &config, &rom::read(left)?);
+    let right_settings = overrides.or(&settings(args, &config, &rom::read(right)?));
+
+    let mut left_cpu = machine(args, left, &left_settings)?;
+    let mut right_cpu = machine(args, right, &right_settings)?;
+    // `RND` has to agree too, or every random game would diverge at once.
+    let seed = args.seed.unwrap_or_else(|| left_cpu.seed());
+    left_cpu.set_seed(seed);
+    right_cpu.set_seed(seed);
+    let mut comparison = Comparison::new(left_cpu, right_cpu);
+
+    if args.headless {
+        let frames = args.max_frames.unwrap_or(compare::DEFAULT_FRAMES);
+        while comparison.frame() < frames && !comparison.run_frame()? {}
+        match (comparison.diverged(), comparison.difference()) {
+            (Some(frame), Some(difference)) => println!("diverged at frame {frame}: {difference}"),
+            _ => println!("identical for {frames} frames")
+        }
+        return Ok(comparison.diverged().is_none());
+    }
+
+    let name = |rom: &Path| rom.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let labels = if left == right {
+        ["as configured".to_string(), "with overrides".to_string()]
+    } else {
+        [name(left), name(right)]
+    };
+    let labels = [labels[0].as_str(), labels[1].as_str()];
+
+    let keymap = left_settings.keymap();
+    let raw = RawTerminal::enable()?;
+    let mut held = HeldKeys::default();
+    let mut buffer = String::new();
+    let mut paused = false;
+
+    loop {
+        let started = Instant::now();
+        while let Some(command) = input::poll(&keymap)? {
+            match command {
+                HostCommand::Quit | HostCommand::Back => return Ok(comparison.diverged().is_none()),
+                HostCommand::TogglePause => paused = !paused,
+                HostCommand::KeyDown(key) => {
+                    comparison.press_key(key);
+                    if !raw.reports_releases() {
+                        held.press(key);
+                    }
+                },
+                HostCommand::KeyUp(key) => comparison.release_key(key),
+                _ => ()
+            }
+        }
+        for key in held.expired() {
+            comparison.release_key(key);
+        }
+
+        if !paused {
+            // Stop on the first difference so it can be inspected.
+            paused = comparison.run_frame()?;
+        }
+        comparison.render_into(&mut buffer, labels);
+        if paused {
+            buffer.push_str("paused, Space to continue, Esc to quit\r\n");
+        }
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(buffer.as_bytes())?;
+        stdout.flush()?;
+
+        thread::sleep(emulator::FRAME_DURATION.saturating_sub(started.elapsed()));
+    }
+}
+
+/// The players joining from elsewhere: with `--host` or `--serve`.
+struct Remotes {
+    netplay: Option<Netplay>,
+    server: Option<Server>
+}
+
+/// Where the frontend keeps a record of the frames it presents.
+struct Recording<'a> {
+    /// The time between them, for `--pacing`.
+    pacing: Option<&'a mut Intervals>,
+    /// What was drawn, for `--record-cast`.
+    cast: Option<Cast>
+}
+
+/// A two-player game hosted with `--host`.
+struct Netplay {
+    peer: Peer,
+    /// Local key presses on their way to the machine.
+    delay: InputDelay<Command>
+}
+
+/// Pass a key press or release on to the machine, after the input delay if
+/// hosting a two-player game.
+fn send_key(emulator: &Emulator, netplay: &mut Option<Netplay>, command: Command) {
+    match netplay {
+        Some(netplay) => netplay.delay.push(command),
+        None => emulator.send(command)
+    }
+}
+
+/// Print where the traces at `a` and `b` first differ, returning whether
+/// they match.
+fn trace_diff(a: &Path, b: &Path) -> Result<bool, CpuError> {
+    let open = |path: &Path| {
+        std::fs::File::open(path)
+            .map(io::BufReader::new)
+            .map_err(|e| CpuError::InvalidTrace(format!("Failed to read {}: {e}", path.display())))
+    };
+
+    match trace::diff(open(a)?, open(b)?)? {
+        Some(divergence) => {
+            println!("{divergence}");
+            Ok(false)
+        },
+        None => {
+            println!("the traces match");
+            Ok(true)
+        }
+    }
+}
+
+fn isa(json: bool) {
+    if json {
+        // Serializing plain strings can't fail.
+        println!("{}", serde_json::to_string_pretty(isa::SPECS).unwrap_or_default());
+        return;
+    }
+
+    for spec in isa::SPECS {
+        let requires = spec.requires.map(|requires| format!(" (needs {requires})")).unwrap_or_default();
+        println!("{:<9}  {:<14}  {}{requires}", spec.pattern, spec.syntax, spec.summary);
+    }
+}
+
+fn sprite_edit(mut editor: SpriteEditor, db: bool) -> Result<(), CpuError> {
+    let done = {
+        let _raw = RawTerminal::enable()?;
+        let _alternate = AlternateScreen::enter()?;
+        let mut buffer = String::new();
+        let mut redraw = true;
+
+        loop {
+            if redraw {
+                editor.render_into(&mut buffer);
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(buffer.as_bytes())?;
+                stdout.flush()?;
+            }
+
+            redraw = true;
+            match input::poll_editor()? {
+                Some(EditorCommand::Move(dx, dy)) => editor.move_cursor(dx, dy),
+                Some(EditorCommand::Toggle) => editor.toggle(),
+                Some(EditorCommand::AddRow) => editor.add_row(),
+                Some(EditorCommand::RemoveRow) => editor.remove_row(),
+                Some(EditorCommand::Done) => break true,
+                Some(EditorCommand::Cancel) => break false,
+                None => {
+                    redraw = false;
+                    thread::sleep(POLL_INTERVAL);
+                }
+            }
+        }
+    };
+
+    // Once the terminal is back to normal, so the output can be piped.
+    if done {
+        match db {
+            true => print!("{}", editor.db()),
+            false => println!("{}", editor.hex())
+        }
+    }
+    Ok(())
+}
+
+fn join(args: &Args, addr: &str) -> Result<(), CpuError> {
+    let config = Config::load(&args.config.clone().unwrap_or_else(Config::default_path))?;
+    let keymap = config.defaults.keymap();
+    println!("Joining the game on {addr}...");
+    let mut peer = Peer::join(addr)?;
+
+    let raw = RawTerminal::enable()?;
+    let mut held = HeldKeys::default();
+    let mut screen = Screen::new();
+    let mut buffer = String::new();
+
+    loop {
+        while let Some(command) = input::poll(&keymap)? {
+            match command {
+                HostCommand::Quit | HostCommand::Back => return Ok(()),
+                HostCommand::KeyDown(key) => {
+                    peer.send(&Message::Key { key, pressed: true })?;
+                    if !raw.reports_releases() {
+                        held.press(key);
+                    }
+                },
+                HostCommand::KeyUp(key) => peer.send(&Message::Key { key, pressed: false })?,
+                _ => ()
+            }
+        }
+        for key in held.expired() {
+            peer.send(&Message::Key { key, pressed: false })?;
+        }
+
+        let mut redraw = false;
+        while let Some(message) = peer.try_recv() {
+            if let Message::Frame(rows) = message {
+                screen.set_rows(&rows);
+                redraw = true;
+            }
+        }
+        if !peer.is_connected() {
+            return Ok(());
+        }
+
+        if redraw {
+            screen.render_into(&mut buffer, ColorDepth::default());
+            buffer.push_str(&format!("\x1B[{};1H\x1B[2KPlaying on {addr}", screen.height() + 3));
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(buffer.as_bytes())?;
+            stdout.flush()?;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Whether `command` would make a run depend on more than its input, so
+/// `--deterministic` turns it off.
+fn nondeterministic(command: &HostCommand) -> bool {
+    matches!(
+        command,
+        HostCommand::Reset | HostCommand::Rewind | HostCommand::LoadState | HostCommand::NextRom
+            | HostCommand::PreviousRom | HostCommand::SlowDown | HostCommand::SpeedUp | HostCommand::FastForward(_)
+            | HostCommand::ToggleCheats
+    )
+}
+
+fn frontend(
+    emulator: &Emulator, raw: &RawTerminal, watcher: &mut RomWatcher, state: &Path, settings: &Settings,
+    remotes: Remotes, recording: Recording
+) -> Result<(), CpuError> {
+    let Remotes { mut netplay, mut server } = remotes;
+    // Remote players keep playing whatever this terminal is doing.
+    let auto_pause = settings.auto_pause.unwrap_or(true) && netplay.is_none() && server.is_none();
+    let Recording { mut pacing, mut cast } = recording;
+    let keymap = settings.keymap();
+    let palette = settings.colors();
+    let depth = settings.color_depth.unwrap_or_default();
+    let colors = palette.map(|palette| {
+        let mut colors = String::new();
+        palette.apply_into(&mut colors, depth);
+        colors
+    });
+    let mut last_watch = Instant::now();
+    let mut held = HeldKeys::default();
+    // Reused for every frame so presenting doesn't allocate.
+    let mut buffer = String::new();
+    // The last frame, kept to redraw under the pause menu.
+    let mut screen: Option<Box<Screen>> = None;
+    let mut menu: Option<PauseMenu> = None;
+    let (mut cheats, mut cheats_path) = load_cheats(settings, watcher.path())?;
+    let mut show_cheats = false;
+    let mut status = StatusBar::new(watcher.path());
+    let mut show_counter = false;
+    let mut show_registers = false;
+    // The latest registers sent while they're shown.
+    let mut registers: Option<Registers> = None;
+    let mut memory: Option<HexView> = None;
+    // The sprites drawn in the last frame, while they're shown.
+    let mut sprites: Option<Vec<SpriteDraw>> = None;
+    let mut keys = settings.show_keys.unwrap_or_default().then(KeyLog::default);
+    if keys.is_some() {
+        emulator.send(Command::WatchKeys(true));
+    }
+    // Whether the key log was fading at the last redraw, to redraw once more
+    // when it's gone.
+    let mut keys_fading = false;
+    // When the fast-forward key was last seen held.
+    let mut fast_forward: Option<Instant> = None;
+    let mut fullscreen = match settings.fullscreen {
+        Some(true) => Some(AlternateScreen::enter()?),
+        _ => None
+    };
+
+    let deterministic = settings.deterministic.unwrap_or_default();
+    let mut flashes = settings.reduce_flashing.unwrap_or_default().then(FlashLimiter::default);
+    // A frame the flash limiter held back, to offer again.
+    let mut held_back = false;
+
+    loop {
+        let mut redraw = false;
+        while let Some(command) = input::poll(&keymap)? {
+            if let Some(open) = menu.as_mut() {
+                redraw = true;
+                match command {
+                    HostCommand::Up => open.up(),
+                    HostCommand::Down => open.down(),
+                    HostCommand::Quit => return Ok(()),
+                    HostCommand::Back | HostCommand::TogglePause => {
+                        menu = None;
+                        emulator.send(Command::Resume);
+                    },
+                    HostCommand::Select => {
+                        let item = open.selected();
+                        menu = None;
+                        if deterministic && matches!(item, MenuItem::Reset | MenuItem::LoadState) {
+                            status.notify(NOT_DETERMINISTIC.to_string());
+                            emulator.send(Command::Resume);
+                            continue;
+                        }
+                        match item {
+                            MenuItem::Quit => return Ok(()),
+                            MenuItem::Resume => (),
+                            MenuItem::Reset => emulator.send(Command::Reset),
+                            MenuItem::SaveState => emulator.send(Command::SaveState(state.to_path_buf())),
+                            MenuItem::LoadState => emulator.send(Command::LoadState(state.to_path_buf())),
+                            MenuItem::ToggleTurbo => emulator.send(Command::ToggleTurbo)
+                        }
+                        emulator.send(Command::Resume);
+                    },
+                    // The game doesn't see input while the menu is open.
+                    _ => ()
+                }
+                continue;
+            }
+            if show_cheats {
+                redraw = true;
+                match command {
+                    HostCommand::Up => cheats.up(),
+                    HostCommand::Down => cheats.down(),
+                    HostCommand::Quit => return Ok(()),
+                    HostCommand::Back | HostCommand::ToggleCheats | HostCommand::TogglePause => {
+                        show_cheats = false;
+                        emulator.send(Command::Resume);
+                    },
+                    HostCommand::Select => match cheats.toggle() {
+                        Some((cheat, true)) => emulator.send(Command::Freeze(cheat.freeze())),
+                        Some((cheat, false)) if cheat.kind == Kind::Freeze => {
+                            emulator.send(Command::Unfreeze(cheat.target));
+                        },
+                        Some((cheat, false)) => {
+                            emulator.send(Command::Poke(cheat.target, cheat.value));
+                            status.notify(format!("applied {}", cheat.name));
+                        },
+                        None => ()
+                    },
+                    _ => ()
+                }
+                continue;
+            }
+
+            if deterministic && nondeterministic(&command) {
+                status.notify(NOT_DETERMINISTIC.to_string());
+                redraw = true;
+                continue;
+            }
+
+            match command {
+                HostCommand::Quit | HostCommand::Back => return Ok(()),
+                HostCommand::Reset => {
+                    emulator.send(Command::Reset);
+                    held.clear();
+                    status.notify("reset".to_string());
+                    redraw = true;
+                },
+                HostCommand::NextRom | HostCommand::PreviousRom => {
+                    let step = if command == HostCommand::NextRom { 1 } else { -1 };
+                    // Cheats are for a single ROM.
+                    cheats.enabled().for_each(|cheat| emulator.send(Command::Unfreeze(cheat.target)));
+                    if let Err(e) = switch_rom(emulator, watcher, step) {
+                        status.notify(format!("failed to find the next ROM: {e}"));
+                    }
+                    status.set_rom(watcher.path());
+                    match load_cheats(settings, watcher.path()) {
+                        Ok(loaded) => (cheats, cheats_path) = loaded,
+                        Err(e) => {
+                            cheats = CheatMenu::default();
+                            status.notify(e.to_string());
+                        }
+                    }
+                    redraw = true;
+                },
+                HostCommand::Rewind => emulator.send(Command::Rewind),
+                HostCommand::TogglePause => {
+                    emulator.send(Command::Pause);
+                    menu = Some(PauseMenu::default());
+                    redraw = true;
+                },
+                HostCommand::ToggleTurbo => emulator.send(Command::ToggleTurbo),
+                HostCommand::SlowDown => emulator.send(Command::SlowDown),
+                HostCommand::SpeedUp => emulator.send(Command::SpeedUp),
+                HostCommand::FastForward(held) => {
+                    if !fast_forward.is_some_and(|_| held) {
+                        emulator.send(Command::FastForward(held));
+                    }
+                    fast_forward = held.then(Instant::now);
+                },
+                HostCommand::Screenshot => {
+                    if let Some(screen) = &screen {
+                        let saved = screenshot::next_path(watcher.path()).and_then(|path| {
+                            screenshot::save(screen, palette, screenshot::DEFAULT_SCALE, &path)?;
+                            Ok(path)
+                        });
+                        status.notify(match saved {
+                            Ok(path) => format!("saved {}", path.display()),
+                            Err(e) => format!("screenshot failed: {e}")
+                        });
+                        redraw = true;
+                    }
+                },
+                HostCommand::ToggleCounter => {
+                    show_counter = !show_counter;
+                    redraw = true;
+                },
+                HostCommand::ToggleSprites => {
+                    sprites = match sprites {
+                        Some(_) => None,
+                        None => Some(Vec::new())
+                    };
+                    emulator.send(Command::WatchSprites(sprites.is_some()));
+                    redraw = true;
+                },
+                HostCommand::ToggleCheats => {
+                    if cheats.is_empty() {
+                        status.notify(format!("no cheats in {}", cheats_path.display()));
+                    } else {
+                        emulator.send(Command::Pause);
+                        show_cheats = true;
+                    }
+                    redraw = true;
+                },
+                HostCommand::ToggleKeys => {
+                    keys = match keys {
+                        Some(_) => None,
+                        None => Some(KeyLog::default())
+                    };
+                    emulator.send(Command::WatchKeys(keys.is_some()));
+                    redraw = true;
+                },
+                HostCommand::ToggleMemory => {
+                    memory = match memory {
+                        Some(_) => None,
+                        None => Some(HexView::default())
+                    };
+                    emulator.send(Command::WatchMemory(memory.is_some()));
+                    redraw = true;
+                },
+                HostCommand::ScrollMemory(pages) => {
+                    if let Some(memory) = &mut memory {
+                        memory.scroll(pages);
+                        redraw = true;
+                    }
+                },
+                HostCommand::ToggleRegisters => {
+                    show_registers = !show_registers;
+                    emulator.send(Command::WatchRegisters(show_registers));
+                    registers = None;
+                    redraw = true;
+                },
+                HostCommand::ToggleFullscreen => {
+                    fullscreen = match fullscreen {
+                        Some(_) => None,
+                        None => Some(AlternateScreen::enter()?)
+                    };
+                    redraw = true;
+                },
+                HostCommand::Resize => redraw = true,
+                HostCommand::Focus(focused) => {
+                    if auto_pause {
+                        emulator.send(Command::Background(!focused));
+                    }
+                },
+                HostCommand::SaveState => emulator.send(Command::SaveState(state.to_path_buf())),
+                HostCommand::LoadState => emulator.send(Command::LoadState(state.to_path_buf())),
+                HostCommand::Up | HostCommand::Down | HostCommand::Select => (),
+                HostCommand::KeyDown(key) => {
+                    send_key(emulator, &mut netplay, Command::KeyDown(key));
+                    if !raw.reports_releases() {
+                        held.press(key);
+                    }
+                },
+                HostCommand::KeyUp(key) => send_key(emulator, &mut netplay, Command::KeyUp(key))
+            }
+        }
+
+        for key in held.expired() {
+            send_key(emulator, &mut netplay, Command::KeyUp(key));
+        }
+        if let Some(Netplay { peer, delay }) = &mut netplay {
+            delay.ready().into_iter().for_each(|command| emulator.send(command));
+            while let Some(message) = peer.try_recv() {
+                if let Message::Key { key, pressed } = message {
+                    emulator.send(if pressed { Command::KeyDown(key) } else { Command::KeyUp(key) });
+                }
+            }
+            if !peer.is_connected() {
+                // Carry on alone, without holding back key presses.
+                delay.ready_all().into_iter().for_each(|command| emulator.send(command));
+                netplay = None;
+                status.notify("the other player left".to_string());
+                redraw = true;
+            }
+        }
+        if let Some(server) = &mut server {
+            for (key, pressed) in server.poll() {
+                emulator.send(if pressed { Command::KeyDown(key) } else { Command::KeyUp(key) });
+            }
+        }
+        // Without key releases, fast-forward stops once the key stops repeating.
+        if !raw.reports_releases() && fast_forward.is_some_and(|t| t.elapsed() >= input::KEY_HOLD) {
+            emulator.send(Command::FastForward(false));
+            fast_forward = None;
+        }
+
+        if last_watch.elapsed() >= WATCH_INTERVAL {
+            last_watch = Instant::now();
+            if watcher.changed() && !deterministic {
+                emulator.send(Command::LoadRom(watcher.path().to_path_buf()));
+            }
+        }
+
+        // Only the most recent of any queued frames is worth presenting.
+        let mut next = emulator.recv_timeout(POLL_INTERVAL);
+        while let Some(event) = next {
+            match event {
+                Event::Frame(frame) => {
+                    if let Some(Netplay { peer, .. }) = &mut netplay {
+                        if peer.send(&Message::Frame(frame.rows().to_vec())).is_err() {
+                            netplay = None;
+                            status.notify("lost the other player".to_string());
+                        }
+                    }
+                    if let Some(server) = &mut server {
+                        server.broadcast(frame.rows());
+                    }
+                    screen = Some(frame);
+                    redraw = true;
+                },
+                Event::Stats(stats) => {
+                    status.set_stats(stats);
+                    redraw = true;
+                },
+                Event::Sprites(drawn) => {
+                    if let Some(sprites) = &mut sprites {
+                        // Outlines last a frame, so clearing them needs a redraw too.
+                        redraw |= !drawn.is_empty() || !sprites.is_empty();
+                        *sprites = drawn;
+                    }
+                },
+                Event::Keys(pressed) => {
+                    if let Some(keys) = &mut keys {
+                        let now = Instant::now();
+                        pressed.into_iter().for_each(|key| keys.press(key, now));
+                    }
+                },
+                Event::Memory(pages) => {
+                    if let Some(memory) = &mut memory {
+                        memory.update(pages);
+                        redraw = true;
+                    }
+                },
+                Event::Registers(latest) => {
+                    // Ignore any sent before the overlay was hidden.
+                    if show_registers {
+                        registers = Some(latest);
+                        redraw = true;
+                    }
+                },
+                Event::Notice(message) => {
+                    status.notify(message);
+                    redraw = true;
+                },
+                Event::Stopped(result) => return result
+            }
+            next = emulator.try_recv();
+        }
+
+        let fading = keys.as_ref().is_some_and(|keys| keys.is_fading(Instant::now()));
+        redraw |= fading || keys_fading;
+        keys_fading = fading;
+
+        if let (true, Some(screen)) = (redraw || held_back, &screen) {
+            held_back = flashes.as_mut().is_some_and(|flashes| !flashes.allow(screen));
+            if held_back {
+                continue;
+            }
+            let view = View {
+                menu: menu.as_ref(),
+                cheats: show_cheats.then_some(&cheats),
+                status: &status,
+                counter: show_counter,
+                registers: registers.as_ref(),
+                memory: memory.as_ref(),
+                sprites: sprites.as_deref(),
+                keys: keys.as_ref(),
+                pacing: pacing.as_deref(),
+                fullscreen: fullscreen.is_some(),
+                colors: colors.as_deref(),
+                depth
+            };
+            let _ = present(screen, &view, &mut buffer);
+            if let Some(pacing) = pacing.as_deref_mut() {
+                pacing.record(Instant::now());
+            }
+            if let Some(Err(e)) = cast.as_mut().map(|cast| cast.record(&buffer)) {
+                status.notify(format!("recording stopped: {e}"));
+                cast = None;
+            }
+        }
+    }
+}
+
+/// Everything the frontend draws around and over the display.
+struct View<'a> {
+    menu: Option<&'a PauseMenu>,
+    /// The cheat menu, while it's open.
+    cheats: Option<&'a CheatMenu>,
+    status: &'a StatusBar,
+    /// Show the speed counter in the corner.
+    counter: bool,
+    /// The registers to show in the other corner.
+    registers: Option<&'a Registers>,
+    /// The memory viewer to show beside the display.
+    memory: Option<&'a HexView>,
+    /// The sprites to outline on the display.
+    sprites: Option<&'a [SpriteDraw]>,
+    /// The keys pressed lately, to show in the corner.
+    keys: Option<&'a KeyLog>,
+    /// The time between presented frames, to show with the tick times.
+    pacing: Option<&'a Intervals>,
+    /// Scale the display to fill the terminal, without the border or status 
+    /// bar.
+    fullscreen: bool,
+    /// Escapes selecting the game's palette, if it has one.
+    colors: Option<&'a str>,
+    /// How to draw the CHIP-8X colors.
+    depth: ColorDepth
+}
+
+/// Draw `screen` and everything in `view` in a single write.
+fn present(screen: &Screen, view: &View, buffer: &mut String) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let (cols, rows) = (usize::from(cols), usize::from(rows));
+    // A terminal too small for the border and the status bar under it gets 
+    // the display scaled down to fit, as when fullscreen, rather than 
+    // wrapped.
+    let status_rows = if view.pacing.is_some() { 5 } else { 3 };
+    let scaled = view.fullscreen || cols < NCOLS + 2 || rows < screen.height() + status_rows;
+    if scaled {
+        screen.render_scaled_into(buffer, cols, rows, view.depth);
+        if let Some(menu) = view.menu {
+            menu.render_within(buffer, cols, rows);
+        }
+        if let Some(cheats) = view.cheats {
+            cheats.render_within(buffer, cols, rows);
+        }
+    } else {
+        screen.render_into(buffer, view.depth);
+        if let Some(sprites) = view.sprites {
+            overlay::render_sprites_into(buffer, screen, sprites);
+        }
+        if let Some(menu) = view.menu {
+            menu.render_into(buffer);
+        }
+        if let Some(cheats) = view.cheats {
+            cheats.render_into(buffer);
+        }
+    }
+    if let Some(colors) = view.colors {
+        buffer.insert_str(0, colors);
+        Palette::reset_into(buffer);
+    }
+    if !scaled {
+        view.status.render_into(buffer, screen.height());
+        if let Some(pacing) = view.pacing {
+            view.status.render_pacing_into(buffer, screen.height(), pacing);
+        }
+        // Beside the display, if there's room.
+        if let Some(memory) = view.memory.filter(|_| cols > NCOLS + 3 + hexview::WIDTH) {
+            memory.render_into(buffer, NCOLS + 4, screen.height() + 2);
+        }
+    }
+    if view.counter {
+        view.status.render_counter_into(buffer);
+    }
+    if let Some(keys) = view.keys {
+        // On the bottom row, inside the border unless scaled.
+        let row = if scaled { rows } else { screen.height() + 1 };
+        keys.render_into(buffer, row, Instant::now(), view.depth);
+    }
+    if let Some(registers) = view.registers {
+        // Inside the border, unless scaled.
+        let right = if scaled { cols } else { NCOLS + 1 };
+        registers.render_into(buffer, right);
+    }
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(buffer.as_bytes())?;
+    stdout.flush()
+}
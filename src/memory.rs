@@ -1,62 +1,353 @@
-use std::fmt::{Display, Formatter};
-use crate::address::Address;
+use core::{fmt::{Display, Formatter}, ops::Range, str::FromStr};
+use alloc::{boxed::Box, vec, vec::Vec};
+use crate::{address::Address, isa::{self, Decoding, Instruction}};
 
-const MEMORY_SIZE: usize = 0x1000;
+/// The 4K address space of the original CHIP-8 and SUPER-CHIP.
+pub const CLASSIC_SIZE: usize = 0x1000;
+/// The full 16-bit address space of XO-CHIP.
+pub const EXTENDED_SIZE: usize = 0x10000;
+/// Granularity at which writes are tracked for incremental snapshots.
+pub const PAGE_SIZE: usize = 0x100;
 
-pub struct Memory {
-    mem: [u8; MEMORY_SIZE],
+#[derive(Debug)]
+pub struct SegmentationFault(pub Address);
+
+/// Which regions of memory a running program is forbidden to write to. Such 
+/// writes are almost always a bug, in either the ROM or the emulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteProtection {
+    /// Any address may be written (the default).
+    #[default]
+    Off,
+    /// The interpreter area below the load address (usually `0x200`), which 
+    /// holds the font.
+    Interpreter,
+    /// The interpreter area and the loaded ROM image. Self-modifying ROMs 
+    /// will fault under this mode.
+    Program
 }
 
-pub struct SegmentationFault(pub Address);
+impl FromStr for WriteProtection {
+    type Err = ();
 
-impl Memory {
-    pub fn new() -> Self {
-        Self { mem: [0; MEMORY_SIZE] }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "interpreter" => Ok(Self::Interpreter),
+            "program" => Ok(Self::Program),
+            _ => Err(())
+        }
+    }
+}
+
+/// A byte-addressable memory backend. Only `len`, `get_byte`, and `set_byte` 
+/// are required; everything else is built on top of them, so alternative 
+/// implementations (banked, memory-mapped I/O, copy-on-write) can be swapped 
+/// in without touching the interpreter.
+pub trait Memory {
+    /// The number of addressable bytes.
+    fn len(&self) -> usize;
+
+    fn get_byte(&self, address: Address) -> Result<u8, SegmentationFault>;
+
+    /// Write `byte` to `address`, returning the byte it replaced.
+    fn set_byte(&mut self, address: Address, byte: u8) -> Result<u8, SegmentationFault>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get_short(&self, address: Address) -> Result<u16, SegmentationFault> {
+        let incr = address.0.checked_add(1).ok_or(SegmentationFault(address))?;
+        match (self.get_byte(address), self.get_byte(Address(incr))) {
+            (Ok(msb), Ok(lsb)) => Ok(((msb as u16) << 8) | (lsb as u16)),
+            _ => Err(SegmentationFault(address))
+        }
     }
 
-    pub fn copy_to_offset(&mut self, data: &[u8], len: usize, start: Address) -> Result<(), SegmentationFault> {        
-        for i in 0..len {
-            let addr = start.offset(i as u16);
-            self.set_byte(addr, data[i])?;
+    /// Copy `data` into memory starting at `start`. Nothing is written unless 
+    /// all of it fits.
+    fn load_slice(&mut self, start: Address, data: &[u8]) -> Result<(), SegmentationFault> {
+        check_bounds(self.len(), start.0 as usize..start.0 as usize + data.len())?;
+        for (i, &byte) in data.iter().enumerate() {
+            self.set_byte(Address(start.0 + i as u16), byte)?;
         }
 
         Ok(())
     }
 
-    pub fn set_byte(&mut self, address: Address, byte: u8) -> Result<u8, SegmentationFault> {
+    /// Copy out the bytes in `range`.
+    fn read_slice(&self, range: Range<usize>) -> Result<Vec<u8>, SegmentationFault> {
+        check_bounds(self.len(), range.clone())?;
+        range.map(|addr| self.get_byte(Address(addr as u16))).collect()
+    }
+
+    /// Zero every byte.
+    fn clear(&mut self) {
+        for addr in 0..self.len() {
+            let _ = self.set_byte(Address(addr as u16), 0);
+        }
+    }
+
+    /// Copy out the full contents, e.g. for a snapshot.
+    fn to_bytes(&self) -> Vec<u8> {
+        (0..self.len())
+            .map(|addr| self.get_byte(Address(addr as u16)).unwrap_or(0))
+            .collect()
+    }
+
+    /// The indices of the `PAGE_SIZE` pages written since the last call, in 
+    /// ascending order. Backends that don't track writes report every page.
+    fn take_dirty_pages(&mut self) -> Vec<usize> {
+        (0..self.len().div_ceil(PAGE_SIZE)).collect()
+    }
+
+    /// The instructions in `range`, decoded under `decoding`, with the opcode
+    /// in place of any that don't decode. Read from a copy, so disassembling
+    /// doesn't count as the program reading memory, e.g. in a heat map.
+    fn disassemble(&self, range: Range<usize>, decoding: Decoding) -> Disassembly {
+        Disassembly { bytes: self.to_bytes(), next: range.start, end: range.end, decoding }
+    }
+}
+
+/// An iterator over the instructions in a range of memory, from
+/// `Memory::disassemble`, as `(address, instruction)`. Instructions are two
+/// bytes, except `F000 nnnn`, which takes its address with it. A byte left
+/// over at the end of the range is skipped.
+pub struct Disassembly {
+    bytes: Vec<u8>,
+    next: usize,
+    end: usize,
+    decoding: Decoding
+}
+
+impl Iterator for Disassembly {
+    type Item = (Address, Result<Instruction, u16>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.end.min(self.bytes.len());
+        let &[msb, lsb] = self.bytes.get(self.next..(self.next + 2).min(end))? else {
+            return None;
+        };
+
+        let address = Address(self.next as u16);
+        let instruction = isa::decode(u16::from_be_bytes([msb, lsb]), self.decoding);
+        self.next += match instruction {
+            Ok(Instruction::LoadLongI) => 4,
+            _ => 2
+        };
+        Some((address, instruction))
+    }
+}
+
+/// Plain RAM, covering the classic 4K CHIP-8 address space by default.
+pub struct Ram {
+    mem: Vec<u8>,
+    dirty: Vec<bool>
+}
+
+impl Ram {
+    pub fn new() -> Self {
+        Self::with_size(CLASSIC_SIZE)
+    }
+
+    /// RAM spanning the full 64K XO-CHIP address space.
+    pub fn extended() -> Self {
+        Self::with_size(EXTENDED_SIZE)
+    }
+
+    /// Addresses are 16 bits wide, so `size` is capped at `EXTENDED_SIZE`.
+    pub fn with_size(size: usize) -> Self {
+        let size = size.min(EXTENDED_SIZE);
+        Self { mem: vec![0; size], dirty: vec![true; size.div_ceil(PAGE_SIZE)] }
+    }
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Memory for Ram {
+    fn len(&self) -> usize {
+        self.mem.len()
+    }
+
+    fn set_byte(&mut self, address: Address, byte: u8) -> Result<u8, SegmentationFault> {
         let loc = self.mem.get_mut(address.0 as usize);
         if let Some(val) = loc {
             let out = *val;
             *val = byte;
+            self.dirty[address.0 as usize / PAGE_SIZE] = true;
             Ok(out)
         } else {
             Err(SegmentationFault(address))
         }
     }
 
-    pub fn get_byte(&self, address: Address) -> Result<u8, SegmentationFault> {
+    fn get_byte(&self, address: Address) -> Result<u8, SegmentationFault> {
         self.mem.get(address.0 as usize)
             .copied()
-            .ok_or_else(|| SegmentationFault(address))
+            .ok_or(SegmentationFault(address))
     }
 
-    pub fn get_short(&self, address: Address) -> Result<u16, SegmentationFault> {
-        let incr = address + Address(1);
-        match (self.get_byte(address), self.get_byte(incr)) {
-            (Ok(msb), Ok(lsb)) => Ok(((msb as u16) << 8) | (lsb as u16)),
-            _ => Err(SegmentationFault(address))
+    fn load_slice(&mut self, start: Address, data: &[u8]) -> Result<(), SegmentationFault> {
+        let range = start.0 as usize..start.0 as usize + data.len();
+        check_bounds(self.len(), range.clone())?;
+        self.mem[range.clone()].copy_from_slice(data);
+        if !data.is_empty() {
+            self.dirty[range.start / PAGE_SIZE..=(range.end - 1) / PAGE_SIZE].fill(true);
+        }
+
+        Ok(())
+    }
+
+    fn read_slice(&self, range: Range<usize>) -> Result<Vec<u8>, SegmentationFault> {
+        check_bounds(self.len(), range.clone())?;
+        Ok(self.mem[range].to_vec())
+    }
+
+    fn clear(&mut self) {
+        self.mem.fill(0);
+        self.dirty.fill(true);
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.mem.clone()
+    }
+
+    fn take_dirty_pages(&mut self) -> Vec<usize> {
+        let pages = self.dirty.iter()
+            .enumerate()
+            .filter_map(|(page, &dirty)| dirty.then_some(page))
+            .collect();
+        self.dirty.fill(false);
+
+        pages
+    }
+}
+
+fn check_bounds(len: usize, range: Range<usize>) -> Result<(), SegmentationFault> {
+    if range.start > range.end || range.end > len {
+        // Report the first address that is out of bounds.
+        return Err(SegmentationFault(Address(range.start.max(len) as u16)));
+    }
+
+    Ok(())
+}
+
+pub type ReadHook = Box<dyn Fn(Address) -> Option<u8> + Send>;
+pub type WriteHook = Box<dyn FnMut(Address, u8) -> Option<u8> + Send>;
+
+/// Wraps another `Memory`, routing program reads and writes in registered 
+/// address ranges through callbacks. This is enough for memory-mapped I/O 
+/// experiments, watchpoints, and freezing values.
+///
+/// Bulk reads and writes only go through the callbacks where they overlap a
+/// mapped range. `clear` and `to_bytes`, which are used for resets and 
+/// snapshots, always go straight to the underlying memory.
+pub struct MappedMemory {
+    inner: Box<dyn Memory + Send>,
+    reads: Vec<(Range<u16>, ReadHook)>,
+    writes: Vec<(Range<u16>, WriteHook)>
+}
+
+impl MappedMemory {
+    pub fn new(inner: Box<dyn Memory + Send>) -> Self {
+        Self { inner, reads: Vec::new(), writes: Vec::new() }
+    }
+
+    /// Called on reads within `range`. Returning `Some` replaces the byte 
+    /// read; `None` reads the underlying memory as usual.
+    pub fn map_read<F: Fn(Address) -> Option<u8> + Send + 'static>(&mut self, range: Range<u16>, f: F) {
+        self.reads.push((range, Box::new(f)));
+    }
+
+    /// Called on writes within `range` with the byte being written. Returning
+    /// `Some` stores that byte in the underlying memory; `None` discards the 
+    /// write.
+    pub fn map_write<F: FnMut(Address, u8) -> Option<u8> + Send + 'static>(&mut self, range: Range<u16>, f: F) {
+        self.writes.push((range, Box::new(f)));
+    }
+}
+
+impl Memory for MappedMemory {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn get_byte(&self, address: Address) -> Result<u8, SegmentationFault> {
+        let byte = self.inner.get_byte(address)?;
+        let mapped = self.reads.iter()
+            .filter(|(range, _)| range.contains(&address.0))
+            .find_map(|(_, f)| f(address));
+
+        Ok(mapped.unwrap_or(byte))
+    }
+
+    fn set_byte(&mut self, address: Address, byte: u8) -> Result<u8, SegmentationFault> {
+        let mut value = Some(byte);
+        for (range, f) in self.writes.iter_mut() {
+            if let Some(b) = value.filter(|_| range.contains(&address.0)) {
+                value = f(address, b);
+            }
+        }
+
+        match value {
+            Some(b) => self.inner.set_byte(address, b),
+            None => self.inner.get_byte(address)
+        }
+    }
+
+    fn load_slice(&mut self, start: Address, data: &[u8]) -> Result<(), SegmentationFault> {
+        let range = start.0 as usize..start.0 as usize + data.len();
+        if !overlaps(self.writes.iter().map(|(r, _)| r), &range) {
+            return self.inner.load_slice(start, data);
+        }
+
+        check_bounds(self.len(), range)?;
+        for (i, &byte) in data.iter().enumerate() {
+            self.set_byte(Address(start.0 + i as u16), byte)?;
         }
+
+        Ok(())
+    }
+
+    fn read_slice(&self, range: Range<usize>) -> Result<Vec<u8>, SegmentationFault> {
+        if !overlaps(self.reads.iter().map(|(r, _)| r), &range) {
+            return self.inner.read_slice(range);
+        }
+
+        check_bounds(self.len(), range.clone())?;
+        range.map(|addr| self.get_byte(Address(addr as u16))).collect()
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes()
+    }
+
+    fn take_dirty_pages(&mut self) -> Vec<usize> {
+        self.inner.take_dirty_pages()
     }
 }
 
-impl Display for Memory {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+fn overlaps<'a>(mut mapped: impl Iterator<Item = &'a Range<u16>>, range: &Range<usize>) -> bool {
+    mapped.any(|r| (r.start as usize) < range.end && range.start < r.end as usize)
+}
+
+impl Display for dyn Memory + Send {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
         const ROW_SIZE: usize = 16;
 
-        for (idx, byte) in self.mem.iter().enumerate() {
+        for (idx, byte) in self.to_bytes().iter().enumerate() {
             if idx % ROW_SIZE == 0 {
                 if idx != 0 {
-                    write!(f, "\n")?
+                    writeln!(f)?
                 }
 
                 write!(f, "{idx:08x}:")?
@@ -71,4 +362,53 @@ impl Display for Memory {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    #[test]
+    fn test_mapped_memory() {
+        let port = Arc::new(AtomicU8::new(0));
+        let (r, w) = (port.clone(), port.clone());
+
+        let mut mem = MappedMemory::new(Box::new(Ram::new()));
+        mem.map_read(0xF00..0xF01, move |_| Some(r.load(Ordering::SeqCst)));
+        mem.map_write(0xF00..0xF01, move |_, b| { w.store(b, Ordering::SeqCst); None });
+        mem.map_write(0x300..0x310, |_, _| None);
+
+        mem.set_byte(Address(0xF00), 7).unwrap();
+        assert_eq!(mem.get_byte(Address(0xF00)).unwrap(), 7);
+        assert_eq!(mem.to_bytes()[0xF00], 0);
+
+        mem.set_byte(Address(0x305), 1).unwrap();
+        mem.set_byte(Address(0x310), 1).unwrap();
+        assert_eq!(mem.get_short(Address(0x30F)).unwrap(), 0x0001);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        use crate::register::VRegister;
+
+        // `CLS`, `LD I, 0x1234` (four bytes), an invalid opcode, `ADD V1, 2`,
+        // and a byte left over.
+        let mut mem = Ram::extended();
+        mem.load_slice(Address(0x200), &[0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0xFF, 0xFF, 0x71, 0x02, 0x01]).unwrap();
+        let listing: Vec<_> = mem.disassemble(0x200..0x20B, Decoding::default()).collect();
+        assert_eq!(listing, [
+            (Address(0x200), Ok(Instruction::ClearScreen)),
+            (Address(0x202), Ok(Instruction::LoadLongI)),
+            (Address(0x206), Err(0xFFFF)),
+            (Address(0x208), Ok(Instruction::AddImm(VRegister::V1, 2)))
+        ]);
+
+        // Past the end of memory, and under a decoding with more instructions.
+        assert_eq!(Ram::new().disassemble(0xFFE..0x1010, Decoding::default()).count(), 1);
+        mem.load_slice(Address(0x200), &[0x00, 0xF1]).unwrap();
+        let test_oracle = Decoding { test_oracle: true, ..Decoding::default() };
+        assert_eq!(mem.disassemble(0x200..0x202, test_oracle).next(), Some((Address(0x200), Ok(Instruction::Pass))));
+    }
+}
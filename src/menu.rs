@@ -0,0 +1,210 @@
+use crate::{cheat::{Cheat, Kind}, screen::{NCOLS, NROWS}};
+use std::fmt::Write;
+
+/// Inner width of the menu box, in terminal columns.
+const WIDTH: usize = 20;
+/// Inner width of the cheat menu's box, wider for the cheats' names.
+const CHEATS_WIDTH: usize = 32;
+
+/// The entries of the pause menu, in the order they are listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Resume,
+    Reset,
+    SaveState,
+    LoadState,
+    ToggleTurbo,
+    Quit
+}
+
+impl MenuItem {
+    pub const ALL: [MenuItem; 6] = [
+        MenuItem::Resume, MenuItem::Reset, MenuItem::SaveState,
+        MenuItem::LoadState, MenuItem::ToggleTurbo, MenuItem::Quit
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MenuItem::Resume => "Resume",
+            MenuItem::Reset => "Reset",
+            MenuItem::SaveState => "Save state",
+            MenuItem::LoadState => "Load state",
+            MenuItem::ToggleTurbo => "Turbo on/off",
+            MenuItem::Quit => "Quit"
+        }
+    }
+}
+
+/// The menu shown over the display while the emulator is paused, so that
+/// nobody has to remember the hotkeys for each action.
+#[derive(Debug, Default)]
+pub struct PauseMenu {
+    selected: usize
+}
+
+impl PauseMenu {
+    pub fn up(&mut self) {
+        self.selected = (self.selected + MenuItem::ALL.len() - 1) % MenuItem::ALL.len();
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1) % MenuItem::ALL.len();
+    }
+
+    pub fn selected(&self) -> MenuItem {
+        MenuItem::ALL[self.selected]
+    }
+
+    /// Append the menu to `out`, positioned with cursor escapes so it is drawn
+    /// over the middle of a display rendered by `Screen::render_into`.
+    pub fn render_into(&self, out: &mut String) {
+        // The display is framed by a border.
+        self.render_within(out, NCOLS + 2, NROWS + 2);
+    }
+
+    /// Like `render_into`, but centered in an area `cols` wide and `rows` 
+    /// high, such as the whole terminal.
+    pub fn render_within(&self, out: &mut String, cols: usize, rows: usize) {
+        let labels = MenuItem::ALL.iter().map(|item| item.label().to_string());
+        render_box(out, "PAUSED", labels, self.selected, WIDTH, (cols, rows));
+    }
+}
+
+/// The cheats for the running ROM, listed over the display to turn on and
+/// off while playing.
+#[derive(Debug, Default)]
+pub struct CheatMenu {
+    cheats: Vec<Cheat>,
+    /// Which of the freeze cheats are on.
+    enabled: Vec<bool>,
+    selected: usize
+}
+
+impl CheatMenu {
+    pub fn new(cheats: Vec<Cheat>) -> Self {
+        Self { enabled: vec![false; cheats.len()], cheats, selected: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn up(&mut self) {
+        self.selected = (self.selected + self.cheats.len().max(1) - 1) % self.cheats.len().max(1);
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1) % self.cheats.len().max(1);
+    }
+
+    /// Pick the selected cheat: turn it on or off, returning it and whether
+    /// it's now on. Cheats that write once are never left on.
+    pub fn toggle(&mut self) -> Option<(&Cheat, bool)> {
+        let cheat = self.cheats.get(self.selected)?;
+        let on = cheat.kind == Kind::Freeze && !self.enabled[self.selected];
+        self.enabled[self.selected] = on;
+        Some((cheat, on))
+    }
+
+    /// The cheats that are on.
+    pub fn enabled(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter().zip(&self.enabled).filter(|(_, &on)| on).map(|(cheat, _)| cheat)
+    }
+
+    /// Append the menu over the middle of a display rendered by
+    /// `Screen::render_into`.
+    pub fn render_into(&self, out: &mut String) {
+        self.render_within(out, NCOLS + 2, NROWS + 2);
+    }
+
+    /// Like `render_into`, but centered in an area `cols` wide and `rows`
+    /// high. Freeze cheats are shown with a checkbox, and ones that write
+    /// once with a `+`.
+    pub fn render_within(&self, out: &mut String, cols: usize, rows: usize) {
+        let labels = self.cheats.iter().zip(&self.enabled).map(|(cheat, &on)| {
+            let mark = match (cheat.kind, on) {
+                (Kind::Once, _) => " + ",
+                (Kind::Freeze, true) => "[x]",
+                (Kind::Freeze, false) => "[ ]"
+            };
+            let name: String = cheat.name.chars().take(CHEATS_WIDTH - 7).collect();
+            format!("{mark} {name}")
+        });
+        render_box(out, "CHEATS", labels, self.selected, CHEATS_WIDTH, (cols, rows));
+    }
+}
+
+/// Append a box `width` columns wide inside, centered in an area of
+/// `(cols, rows)`, with `title` over `items` and a cursor beside the
+/// `selected` one.
+fn render_box(
+    out: &mut String, title: &str, items: impl Iterator<Item = String>, selected: usize, width: usize,
+    (cols, rows): (usize, usize)
+) {
+    let blank = " ".repeat(width);
+    let mut lines = vec![format!("{title:^width$}"), blank.clone()];
+    for (i, item) in items.enumerate() {
+        let cursor = if i == selected { '>' } else { ' ' };
+        lines.push(format!(" {cursor} {item:<item_width$}", item_width = width - 3));
+    }
+    lines.push(blank);
+
+    // Terminal rows and columns count from 1.
+    let top = rows.saturating_sub(lines.len() + 2) / 2 + 1;
+    let left = cols.saturating_sub(width + 2) / 2 + 1;
+    let border = "─".repeat(width);
+    let _ = write!(out, "\x1B[{top};{left}H┌{border}┐");
+    for (row, line) in (top + 1..).zip(&lines) {
+        let _ = write!(out, "\x1B[{row};{left}H│{line}│");
+    }
+    let _ = write!(out, "\x1B[{};{left}H└{border}┘", top + lines.len() + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_menu() {
+        let mut menu = PauseMenu::default();
+        assert_eq!(menu.selected(), MenuItem::Resume);
+        menu.up();
+        assert_eq!(menu.selected(), MenuItem::Quit);
+        menu.down();
+        menu.down();
+        assert_eq!(menu.selected(), MenuItem::Reset);
+
+        let mut out = String::new();
+        menu.render_into(&mut out);
+        assert!(out.contains("> Reset"));
+    }
+
+    #[test]
+    fn test_cheat_menu() {
+        let cheats = crate::cheat::parse(r#"
+            [[cheat]]
+            name = "Infinite lives"
+            address = "0x2f0"
+            value = 3
+
+            [[cheat]]
+            name = "Max score"
+            address = "vE"
+            value = 0xFF
+            type = "once"
+        "#).unwrap();
+        let mut menu = CheatMenu::new(cheats);
+        assert_eq!(menu.toggle().map(|(cheat, on)| (cheat.name.as_str(), on)), Some(("Infinite lives", true)));
+        menu.down();
+        assert_eq!(menu.toggle().map(|(_, on)| on), Some(false));
+        assert_eq!(menu.enabled().count(), 1);
+
+        let mut out = String::new();
+        menu.render_into(&mut out);
+        assert!(out.contains("   [x] Infinite lives") && out.contains(" >  +  Max score"));
+        menu.up();
+        assert_eq!(menu.toggle().map(|(_, on)| on), Some(false));
+        assert_eq!(menu.enabled().count(), 0);
+        assert!(CheatMenu::default().toggle().is_none());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::cpu::{Cpu, Frame, FRAMES_PER_SECOND};
+use std::{fmt::Write, sync::atomic::{AtomicU32, AtomicU64, Ordering::Relaxed}};
+#[cfg(feature = "metrics")]
+use std::{
+    io::{self, Read, Write as _}, net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs}, sync::Arc, thread,
+    time::Duration
+};
+
+/// How long a scrape may take to send its request or read the response.
+#[cfg(feature = "metrics")]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Counters and gauges describing a running emulator, shared between the
+/// emulation thread that updates them and whatever reports them.
+///
+/// Counters only ever go up, even across resets and ROM changes, so rates
+/// can be taken from them.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    instructions: AtomicU64,
+    draws: AtomicU64,
+    frames: AtomicU64,
+    errors: AtomicU64,
+    /// Frames that ended with the buzzer on.
+    sound_frames: AtomicU64,
+    ips: AtomicU32,
+    dt: AtomicU32,
+    st: AtomicU32
+}
+
+impl Metrics {
+    /// Count a frame run by `cpu`, and take its timers as they were at the end
+    /// of it.
+    pub fn record_frame(&self, frame: &Frame, cpu: &Cpu) {
+        self.instructions.fetch_add(u64::from(frame.instructions), Relaxed);
+        self.draws.fetch_add(u64::from(frame.draws), Relaxed);
+        self.frames.fetch_add(1, Relaxed);
+        self.errors.fetch_add(u64::from(frame.errors), Relaxed);
+        self.sound_frames.fetch_add(frame.sound as u64, Relaxed);
+
+        let (dt, st) = cpu.timers();
+        self.dt.store(dt.into(), Relaxed);
+        self.st.store(st.into(), Relaxed);
+    }
+
+    /// Count an error that stopped the program.
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Relaxed);
+    }
+
+    /// Set the measured instructions per second.
+    pub fn set_ips(&self, ips: u32) {
+        self.ips.store(ips, Relaxed);
+    }
+
+    /// The metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let audio = self.sound_frames.load(Relaxed) as f64 / f64::from(FRAMES_PER_SECOND);
+        let metrics = [
+            ("instructions_total", "counter", "Instructions executed.", self.instructions.load(Relaxed) as f64),
+            ("draws_total", "counter", "DRW instructions executed.", self.draws.load(Relaxed) as f64),
+            ("frames_total", "counter", "60Hz frames run.", self.frames.load(Relaxed) as f64),
+            ("errors_total", "counter", "Errors raised by the program, whatever their policy.", self.errors.load(Relaxed) as f64),
+            ("audio_seconds_total", "counter", "Time the buzzer has been on.", audio),
+            ("instructions_per_second", "gauge", "Measured emulation speed.", self.ips.load(Relaxed).into()),
+            ("delay_timer", "gauge", "The delay timer.", self.dt.load(Relaxed).into()),
+            ("sound_timer", "gauge", "The sound timer.", self.st.load(Relaxed).into())
+        ];
+
+        let mut out = String::new();
+        for (name, kind, help, value) in metrics {
+            // Writing to a `String` can't fail.
+            let _ = write!(out, "# HELP chip8_{name} {help}\n# TYPE chip8_{name} {kind}\nchip8_{name} {value}\n");
+        }
+        out
+    }
+
+    /// Answer `GET /metrics` on `addr`, e.g. `0.0.0.0:9100`, from a thread of
+    /// its own, for Prometheus to scrape. Returns the address listened on,
+    /// which has the port chosen if `addr`'s is 0.
+    #[cfg(feature = "metrics")]
+    pub fn serve<A: ToSocketAddrs>(self: &Arc<Self>, addr: A) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local = listener.local_addr()?;
+        let metrics = Arc::clone(self);
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                if let Err(e) = respond(stream, &metrics) {
+                    tracing::warn!("Failed to answer a metrics request: {e}");
+                }
+            }
+        });
+        Ok(local)
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    // A scraper that stalls shouldn't keep the next one out for good.
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request = [0; 2048];
+    let len = stream.read(&mut request)?;
+    let request = String::from_utf8_lossy(&request[..len]);
+
+    let (status, body) = match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", "Not found; try /metrics\n".to_string())
+    };
+    write!(
+        stream, "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        // `LD ST, V0` with V0 = 30, then two `DRW`s and an endless loop.
+        let mut cpu = Cpu::with_program(&[0x601E, 0xF018, 0xD001, 0xD001, 0x7101, 0x1208]).unwrap();
+        cpu.set_ips(10 * FRAMES_PER_SECOND);
+        let metrics = Metrics::default();
+        for _ in 0..3 {
+            metrics.record_frame(&cpu.run_frame().unwrap(), &cpu);
+        }
+        metrics.set_ips(600);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE chip8_instructions_total counter\nchip8_instructions_total 30\n"));
+        assert!(text.contains("chip8_draws_total 2\n"));
+        assert!(text.contains("chip8_frames_total 3\n"));
+        assert!(text.contains("chip8_audio_seconds_total 0.05\n"));
+        assert!(text.contains("chip8_instructions_per_second 600\n"));
+        assert!(text.contains("chip8_sound_timer 27\n"));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_serve() {
+        let metrics = Arc::new(Metrics::default());
+        let addr = metrics.serve("127.0.0.1:0").unwrap();
+        metrics.record_error();
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK") && response.contains("chip8_errors_total 1\n"));
+        assert!(get("/").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{emulator::FRAME_DURATION, screen::MAX_ROWS};
+use std::{
+    collections::VecDeque, io::{self, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs}, sync::mpsc::{self, Receiver, TryRecvError},
+    thread, time::{Duration, Instant}
+};
+
+/// Frames the host holds back its own key presses for by default, roughly
+/// what a guest on a nearby network waits for its presses to arrive and the
+/// result to come back.
+pub const DEFAULT_INPUT_DELAY: u32 = 3;
+
+const KEY: u8 = 0;
+const FRAME: u8 = 1;
+
+/// What the host and guest send each other. The host runs the only machine
+/// and sends every frame; the guest only sends its keypad.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// A keypad key pressed or released on the guest.
+    Key { key: u8, pressed: bool },
+    /// The host's display, as `Screen::rows`.
+    Frame(Vec<u64>)
+}
+
+impl Message {
+    /// Encode the message as a tag byte followed by its fields, with the
+    /// display's row count and then its rows in big-endian order.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            Message::Key { key, pressed } => w.write_all(&[KEY, *key, *pressed as u8]),
+            Message::Frame(rows) => {
+                w.write_all(&[FRAME, rows.len() as u8])?;
+                rows.iter().try_for_each(|row| w.write_all(&row.to_be_bytes()))
+            }
+        }
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut tag = [0; 1];
+        r.read_exact(&mut tag)?;
+        match tag[0] {
+            KEY => {
+                let mut fields = [0; 2];
+                r.read_exact(&mut fields)?;
+                Ok(Message::Key { key: fields[0] & 0xF, pressed: fields[1] != 0 })
+            },
+            FRAME => {
+                let mut len = [0; 1];
+                r.read_exact(&mut len)?;
+                if len[0] as usize > MAX_ROWS {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} rows is too tall a display", len[0])));
+                }
+                let mut rows = vec![0; len[0] as usize];
+                for row in rows.iter_mut() {
+                    let mut bytes = [0; 8];
+                    r.read_exact(&mut bytes)?;
+                    *row = u64::from_be_bytes(bytes);
+                }
+                Ok(Message::Frame(rows))
+            },
+            tag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown message {tag}")))
+        }
+    }
+}
+
+/// The other end of a netplay session. Messages are read on a background
+/// thread so the frontend never blocks on the network.
+pub struct Peer {
+    writer: BufWriter<TcpStream>,
+    incoming: Receiver<Message>,
+    connected: bool
+}
+
+impl Peer {
+    /// Wait on `addr` for a guest to join.
+    pub fn host<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let (stream, _) = TcpListener::bind(addr)?.accept()?;
+        Self::new(stream)
+    }
+
+    /// Join the session hosted at `addr`.
+    pub fn join<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Self::new(TcpStream::connect(addr)?)
+    }
+
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        // Key presses are tiny and latency matters more than throughput.
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (tx, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(message) = Message::read_from(&mut reader) {
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { writer: BufWriter::new(stream), incoming, connected: true })
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        message.write_to(&mut self.writer)?;
+        self.writer.flush()
+    }
+
+    /// The next message received, if any.
+    pub fn try_recv(&mut self) -> Option<Message> {
+        match self.incoming.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.connected = false;
+                None
+            }
+        }
+    }
+
+    /// False once the other end has hung up.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+}
+
+/// Holds the host's own key presses back for a few frames, so that both
+/// players' presses take about as long to reach the machine and neither has
+/// an edge.
+#[derive(Debug)]
+pub struct InputDelay<T> {
+    delay: Duration,
+    queue: VecDeque<(Instant, T)>
+}
+
+impl<T> InputDelay<T> {
+    pub fn new(frames: u32) -> Self {
+        Self { delay: FRAME_DURATION * frames, queue: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, input: T) {
+        self.queue.push_back((Instant::now() + self.delay, input));
+    }
+
+    /// The inputs whose delay has passed, in the order they were pushed.
+    pub fn ready(&mut self) -> Vec<T> {
+        let now = Instant::now();
+        let due = self.queue.iter().take_while(|(at, _)| *at <= now).count();
+        self.queue.drain(..due).map(|(_, input)| input).collect()
+    }
+
+    /// Every input still held back, e.g. when the delay is no longer needed.
+    pub fn ready_all(&mut self) -> Vec<T> {
+        self.queue.drain(..).map(|(_, input)| input).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::NROWS;
+
+    /// Wait a few seconds for the next message from `peer`.
+    fn recv(peer: &mut Peer) -> Message {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match peer.try_recv() {
+                Some(message) => return message,
+                None if Instant::now() < deadline => thread::sleep(Duration::from_millis(1)),
+                None => panic!("nothing received")
+            }
+        }
+    }
+
+    #[test]
+    fn test_netplay() {
+        let mut rows = vec![0; NROWS];
+        rows[0] = 1 << 63;
+        rows[NROWS - 1] = 0x0123_4567_89AB_CDEF;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let guest = thread::spawn(move || {
+            let mut guest = Peer::join(addr).unwrap();
+            guest.send(&Message::Key { key: 0xA, pressed: true }).unwrap();
+            recv(&mut guest)
+        });
+
+        let mut host = Peer::new(listener.accept().unwrap().0).unwrap();
+        host.send(&Message::Frame(rows.clone())).unwrap();
+        assert_eq!(recv(&mut host), Message::Key { key: 0xA, pressed: true });
+        assert_eq!(guest.join().unwrap(), Message::Frame(rows));
+
+        let mut delay = InputDelay::new(0);
+        delay.push(1);
+        delay.push(2);
+        assert_eq!(delay.ready(), vec![1, 2]);
+        let mut delay = InputDelay::new(60);
+        delay.push(3);
+        assert!(delay.ready().is_empty());
+    }
+}
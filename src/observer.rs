@@ -0,0 +1,25 @@
+use crate::{cpu::Cpu, isa::Instruction};
+#[cfg(feature = "std")]
+use crate::address::Address;
+
+/// Receives a callback around every instruction the `Cpu` executes. Tracers, 
+/// profilers, and similar tools attach to the `Cpu` as observers instead of 
+/// being special-cased in the execution loop.
+pub trait Observer {
+    fn before_execute(&mut self, _cpu: &Cpu, _instruction: &Instruction) {}
+    fn after_execute(&mut self, _cpu: &Cpu, _instruction: &Instruction) {}
+}
+
+/// Emits every executed instruction as a `trace` event with the `execute`
+/// target, so it is only formatted when that level is enabled.
+#[cfg(feature = "std")]
+pub struct Tracer;
+
+#[cfg(feature = "std")]
+impl Observer for Tracer {
+    fn before_execute(&mut self, cpu: &Cpu, instruction: &Instruction) {
+        // The PC has already moved past the instruction by the time it executes.
+        let addr = Address(cpu.pc().0.wrapping_sub(2));
+        tracing::trace!(target: "execute", "{addr} => {instruction}");
+    }
+}
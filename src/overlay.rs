@@ -0,0 +1,177 @@
+use crate::{address::Address, cpu::{Cpu, SpriteDraw}, screen::{ColorDepth, Rgb, Screen, NCOLS}};
+use std::{collections::VecDeque, fmt::Write, time::{Duration, Instant}};
+
+/// Width of the register overlay, in terminal columns.
+const REGISTERS_WIDTH: usize = 30;
+/// The most keys the key log shows at once.
+const KEY_LOG_LEN: usize = 8;
+/// How long a key stays in the key log, fading out as it goes.
+const KEY_FADE: Duration = Duration::from_secs(2);
+
+/// The registers as of the end of a frame, sent from the emulation thread so
+/// the frontend can show them over the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: Address,
+    pub pc: Address,
+    pub dt: u8,
+    pub st: u8
+}
+
+impl Registers {
+    pub fn of(cpu: &Cpu) -> Self {
+        let (dt, st) = cpu.timers();
+        Self { v: *cpu.registers(), i: cpu.i(), pc: cpu.pc(), dt, st }
+    }
+
+    /// Append the registers as a box drawn over the top right corner of a
+    /// display whose last column is `right`, counting from 1.
+    pub fn render_into(&self, out: &mut String, right: usize) {
+        let hex = |regs: &[u8]| regs.iter().fold(String::new(), |mut s, reg| {
+            let _ = write!(s, " {reg:02x}");
+            s
+        });
+        let lines = [
+            format!(" V0-7{} ", hex(&self.v[..8])),
+            format!(" V8-F{} ", hex(&self.v[8..])),
+            format!(" PC {:03x} I {:03x} DT {:02x} ST {:02x}", self.pc.0, self.i.0, self.dt, self.st)
+        ];
+
+        let col = right.saturating_sub(REGISTERS_WIDTH) + 1;
+        for (row, line) in lines.iter().enumerate() {
+            let _ = write!(out, "\x1B[{};{col}H{line:<REGISTERS_WIDTH$}", row + 2);
+        }
+    }
+}
+
+/// The keypad keys pressed lately, shown in a strip over the bottom left
+/// corner of the display so recordings and tutorial videos show the input.
+#[derive(Debug, Default)]
+pub struct KeyLog {
+    /// The keys and when they were pressed, oldest first.
+    presses: VecDeque<(u8, Instant)>
+}
+
+impl KeyLog {
+    pub fn press(&mut self, key: u8, at: Instant) {
+        if self.presses.len() == KEY_LOG_LEN {
+            self.presses.pop_front();
+        }
+        self.presses.push_back((key, at));
+    }
+
+    /// Whether any key is still showing at `now`, so the strip needs drawing
+    /// again as it fades.
+    pub fn is_fading(&self, now: Instant) -> bool {
+        self.presses.back().is_some_and(|&(_, at)| now.duration_since(at) < KEY_FADE)
+    }
+
+    /// Append the keys still showing at `now` on terminal row `row`, counting
+    /// from 1, newest on the right and dimmer the older they are.
+    pub fn render_into(&self, out: &mut String, row: usize, now: Instant, depth: ColorDepth) {
+        let mut showing = self.presses.iter().filter(|&&(_, at)| now.duration_since(at) < KEY_FADE).peekable();
+        if showing.peek().is_none() {
+            return;
+        }
+
+        let _ = write!(out, "\x1B[{row};2H");
+        let _ = depth.background_into(out, Rgb(0, 0, 0));
+        for &(key, at) in showing {
+            let faded = now.duration_since(at).as_secs_f32() / KEY_FADE.as_secs_f32();
+            let level = (255.0 - 200.0 * faded) as u8;
+            let _ = depth.foreground_into(out, Rgb(level, level, level));
+            let _ = write!(out, " {key:X}");
+        }
+        out.push_str(" \x1B[0m");
+    }
+}
+
+/// Append outlines of the boxes `sprites` were drawn in, and the pixels
+/// they collided with in red, over `screen` rendered by `Screen::render_into`.
+pub fn render_sprites_into(out: &mut String, screen: &Screen, sprites: &[SpriteDraw]) {
+    // Pixels are one cell each, inside a border.
+    let mut cell = |x: usize, y: usize, style: &str| {
+        let pixel = if screen.pixel(x, y) { '█' } else { ' ' };
+        let _ = write!(out, "\x1B[{};{}H{style}{pixel}\x1B[0m", y + 2, x + 2);
+    };
+
+    for sprite in sprites.iter().filter(|sprite| sprite.height > 0) {
+        let (right, bottom) = ((sprite.x + 8).min(NCOLS) - 1, sprite.y + sprite.height - 1);
+        for x in sprite.x..=right {
+            for y in sprite.y..=bottom {
+                if x == sprite.x || x == right || y == sprite.y || y == bottom {
+                    cell(x, y, "\x1B[44m");
+                }
+            }
+        }
+    }
+    // After every outline, so none covers a collision.
+    for &(x, y) in sprites.iter().flat_map(|sprite| &sprite.collisions) {
+        let _ = write!(out, "\x1B[{};{}H\x1B[31m█\x1B[0m", y + 2, x + 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registers() {
+        // `LD VA, 0x2B`, `LD I, 0x345`, `LD DT, VA`.
+        let mut cpu = Cpu::with_program(&[0x6A2B, 0xA345, 0xFA15]).unwrap();
+        (0..3).for_each(|_| { cpu.step().unwrap(); });
+
+        let mut out = String::new();
+        Registers::of(&cpu).render_into(&mut out, 66);
+        assert_eq!(out, concat!(
+            "\x1B[2;37H V0-7 00 00 00 00 00 00 00 00 ",
+            "\x1B[3;37H V8-F 00 00 2b 00 00 00 00 00 ",
+            "\x1B[4;37H PC 206 I 345 DT 2b ST 00     "
+        ));
+    }
+
+    #[test]
+    fn test_key_log() {
+        let start = Instant::now();
+        let mut log = KeyLog::default();
+        (0..10).for_each(|key| log.press(key, start));
+        log.press(0xA, start + KEY_FADE);
+
+        let mut out = String::new();
+        log.render_into(&mut out, 33, start + KEY_FADE, ColorDepth::TrueColor);
+        // The oldest were pushed out, and all but the last have faded.
+        assert_eq!(out, "\x1B[33;2H\x1B[48;2;0;0;0m\x1B[38;2;255;255;255m A \x1B[0m");
+        assert!(log.is_fading(start + KEY_FADE));
+
+        out.clear();
+        log.render_into(&mut out, 33, start + KEY_FADE * 2, ColorDepth::TrueColor);
+        assert!(out.is_empty() && !log.is_fading(start + KEY_FADE * 2));
+        log.press(0x1, start + KEY_FADE * 2);
+        log.render_into(&mut out, 33, start + KEY_FADE * 2 + KEY_FADE / 2, ColorDepth::TrueColor);
+        assert!(out.contains("\x1B[38;2;155;155;155m 1 "));
+    }
+
+    #[test]
+    fn test_sprites() {
+        // `LD V0, 62`, `LD F, V0` (the 5-row font sprite for 0), drawn at 
+        // (62, 0) twice, then at (62, 28) once.
+        let mut cpu = Cpu::with_program(&[0x603E, 0x6100, 0xF029, 0xD015, 0xD015, 0x611C, 0xD015]).unwrap();
+        cpu.record_sprites(true);
+        (0..7).for_each(|_| { cpu.step().unwrap(); });
+
+        let sprites = cpu.take_sprites();
+        assert_eq!(sprites.len(), 3);
+        assert_eq!((sprites[0].x, sprites[0].y, sprites[0].height), (62, 0, 5));
+        assert!(sprites[0].collisions.is_empty());
+        // The top row of a 0 is 0xF0, clipped to its first two pixels.
+        assert_eq!(&sprites[1].collisions[..2], &[(62, 0), (63, 0)]);
+        assert_eq!(sprites[2].height, 4);
+        assert!(cpu.take_sprites().is_empty());
+
+        let mut out = String::new();
+        render_sprites_into(&mut out, cpu.screen(), &sprites[1..2]);
+        assert!(out.starts_with("\x1B[2;64H\x1B[44m \x1B[0m\x1B[3;64H\x1B[44m \x1B[0m"));
+        assert!(out.contains("\x1B[2;64H\x1B[31m█\x1B[0m\x1B[2;65H\x1B[31m█\x1B[0m\x1B[3;64H\x1B[31m█"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::VecDeque, fmt::{self, Display, Formatter}, time::{Duration, Instant}
+};
+
+/// Number of recent intervals kept to graph.
+pub const GRAPH_WIDTH: usize = 60;
+/// Bars from shortest to longest, for `Intervals::graph_into`.
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The time between occurrences of a recurring event, such as timer ticks or
+/// presented frames, for seeing how evenly it happens.
+#[derive(Debug, Clone)]
+pub struct Intervals {
+    /// How often the event is meant to happen, if it's meant to be regular.
+    period: Option<Duration>,
+    last: Option<Instant>,
+    count: u64,
+    total: Duration,
+    /// The sum of the squared intervals, in seconds, for the deviation.
+    squares: f64,
+    min: Duration,
+    max: Duration,
+    recent: VecDeque<Duration>
+}
+
+/// A summary of `Intervals`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IntervalStats {
+    pub count: u64,
+    pub mean: Duration,
+    /// The standard deviation of the intervals.
+    pub jitter: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// How much longer the intervals took in total than they would have at
+    /// exactly the period, in seconds, or `None` without a period. Positive
+    /// means the event ran slow.
+    pub drift: Option<f64>
+}
+
+impl Intervals {
+    pub fn new(period: Option<Duration>) -> Self {
+        Self {
+            period,
+            last: None,
+            count: 0,
+            total: Duration::ZERO,
+            squares: 0.0,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            recent: VecDeque::with_capacity(GRAPH_WIDTH)
+        }
+    }
+
+    /// Note that the event happened at `at`.
+    pub fn record(&mut self, at: Instant) {
+        if let Some(interval) = self.last.map(|last| at.saturating_duration_since(last)) {
+            self.count += 1;
+            self.total += interval;
+            self.squares += interval.as_secs_f64().powi(2);
+            self.min = self.min.min(interval);
+            self.max = self.max.max(interval);
+            if self.recent.len() == GRAPH_WIDTH {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(interval);
+        }
+        self.last = Some(at);
+    }
+
+    /// Don't count the time until the next event, e.g. while paused.
+    pub fn skip(&mut self) {
+        self.last = None;
+    }
+
+    /// Forget the intervals so far, to start a new summary.
+    pub fn clear(&mut self) {
+        *self = Self { last: self.last, ..Self::new(self.period) };
+    }
+
+    pub fn stats(&self) -> IntervalStats {
+        if self.count == 0 {
+            return IntervalStats::default();
+        }
+
+        let n = self.count as f64;
+        let mean = self.total.as_secs_f64() / n;
+        let variance = (self.squares / n - mean * mean).max(0.0);
+        IntervalStats {
+            count: self.count,
+            mean: Duration::from_secs_f64(mean),
+            jitter: Duration::from_secs_f64(variance.sqrt()),
+            min: self.min,
+            max: self.max,
+            drift: self.period.map(|period| self.total.as_secs_f64() - period.as_secs_f64() * n)
+        }
+    }
+
+    /// Append a bar graph of the most recent intervals, one character each,
+    /// scaled so twice the period (or the longest of them, without one) is a
+    /// full bar.
+    pub fn graph_into(&self, out: &mut String) {
+        let longest = || self.recent.iter().copied().max().unwrap_or_default();
+        let full = self.period.map_or_else(longest, |period| period * 2).as_secs_f64();
+        for interval in &self.recent {
+            let level = match full {
+                0.0 => 0.0,
+                full => interval.as_secs_f64() / full
+            };
+            out.push(BARS[((level * BARS.len() as f64) as usize).min(BARS.len() - 1)]);
+        }
+    }
+}
+
+impl Display for IntervalStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        write!(
+            f, "mean {:.2}ms, jitter {:.2}ms, min {:.2}ms, max {:.2}ms",
+            ms(self.mean), ms(self.jitter), ms(self.min), ms(self.max)
+        )?;
+        if let Some(drift) = self.drift {
+            write!(f, ", drift {:+.2}ms", drift * 1000.0)?;
+        }
+        write!(f, " over {} intervals", self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intervals() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut intervals = Intervals::new(Some(ms(10)));
+        for at in [0, 10, 20, 32, 40] {
+            intervals.record(start + ms(at));
+        }
+        intervals.skip();
+        // A pause, which isn't counted.
+        intervals.record(start + ms(1000));
+        intervals.record(start + ms(1010));
+
+        let stats = intervals.stats();
+        assert_eq!((stats.count, stats.mean, stats.min, stats.max), (5, ms(10), ms(8), ms(12)));
+        assert!(stats.drift.unwrap().abs() < 1e-9);
+        assert!((stats.jitter.as_secs_f64() * 1000.0 - 1.6f64.sqrt()).abs() < 1e-6);
+
+        let mut graph = String::new();
+        intervals.graph_into(&mut graph);
+        assert_eq!(graph, "▅▅▅▄▅");
+
+        intervals.clear();
+        assert_eq!(intervals.stats(), IntervalStats::default());
+        intervals.record(start + ms(1030));
+        assert_eq!(intervals.stats().max, ms(20));
+    }
+}
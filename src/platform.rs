@@ -0,0 +1,84 @@
+use alloc::boxed::Box;
+use core::{fmt::{self, Display, Formatter}, str::FromStr};
+use crate::{address::Address, cpu::PC_START, memory::{Memory, Ram, CLASSIC_SIZE}, screen::NROWS};
+
+/// The CHIP-8 variant a ROM was written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter, with 4K of memory (the default).
+    #[default]
+    Chip8,
+    /// XO-CHIP, which extends memory to the full 64K address space.
+    XoChip,
+    /// The ETI-660, whose programs are loaded at 0x600 rather than 0x200 and
+    /// whose display is 64x48.
+    Eti660,
+    /// CHIP-8X, for the VP-590 color board, whose programs are loaded at
+    /// 0x300 and can color the display.
+    Chip8X
+}
+
+impl Platform {
+    /// A fresh memory backend sized for this platform.
+    pub fn memory(&self) -> Box<dyn Memory + Send> {
+        match self {
+            Self::Chip8 => Box::new(Ram::new()),
+            Self::XoChip => Box::new(Ram::extended()),
+            Self::Eti660 | Self::Chip8X => Box::new(Ram::new())
+        }
+    }
+
+    /// Where programs for this platform are loaded and start executing.
+    pub fn load_address(&self) -> Address {
+        match self {
+            Self::Eti660 => Address(0x600),
+            Self::Chip8X => Address(0x300),
+            _ => PC_START
+        }
+    }
+
+    /// Rows in this platform's display. The ETI-660 also has a 64 row mode,
+    /// which has to be asked for.
+    pub fn display_rows(&self) -> usize {
+        match self {
+            Self::Eti660 => 48,
+            _ => NROWS
+        }
+    }
+
+    /// Guess which platform a ROM targets: it's XO-CHIP if it is too big for
+    /// 4K of memory or uses the XO-CHIP only `F000 nnnn` instruction.
+    pub fn detect(program: &[u8]) -> Self {
+        let long_i = program.chunks_exact(2).any(|op| op == [0xF0, 0x00]);
+        if program.len() > CLASSIC_SIZE - PC_START.0 as usize || long_i {
+            Self::XoChip
+        } else {
+            Self::Chip8
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chip8 => write!(f, "CHIP-8"),
+            Self::XoChip => write!(f, "XO-CHIP"),
+            Self::Eti660 => write!(f, "ETI-660"),
+            Self::Chip8X => write!(f, "CHIP-8X")
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chip8" | "chip-8" => Ok(Self::Chip8),
+            "xochip" | "xo-chip" => Ok(Self::XoChip),
+            "eti660" | "eti-660" => Ok(Self::Eti660),
+            "chip8x" | "chip-8x" => Ok(Self::Chip8X),
+            _ => Err(())
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+/// What the `Cpu` does when an instruction faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Stop execution and return the error (the default).
+    #[default]
+    Halt,
+    /// Ignore the faulting instruction and carry on with the next one.
+    Skip,
+    /// Like `Skip`, but log a warning first.
+    Log,
+    /// Pause the machine just past the faulting instruction so its state can 
+    /// be inspected before resuming.
+    Trap
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "halt" => Ok(Self::Halt),
+            "skip" => Ok(Self::Skip),
+            "log" => Ok(Self::Log),
+            "trap" => Ok(Self::Trap),
+            _ => Err(())
+        }
+    }
+}
+
+/// The kind of a `CpuError`, without any of its context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    StackOverflow,
+    StackUnderflow,
+    InfiniteLoop,
+    InvalidAddress,
+    InvalidRegister,
+    SegmentationFault,
+    InvalidInstruction,
+    InvalidSnapshot,
+    ProgramLoadError,
+    WriteProtected,
+    InvalidConfig,
+    InvalidReplay,
+    InvalidTrace,
+    TestFailed,
+    ScriptError
+}
+
+impl FromStr for ErrorKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stack-overflow" => Ok(Self::StackOverflow),
+            "stack-underflow" => Ok(Self::StackUnderflow),
+            "infinite-loop" => Ok(Self::InfiniteLoop),
+            "invalid-address" => Ok(Self::InvalidAddress),
+            "invalid-register" => Ok(Self::InvalidRegister),
+            "segmentation-fault" | "segfault" => Ok(Self::SegmentationFault),
+            "invalid-instruction" => Ok(Self::InvalidInstruction),
+            "invalid-snapshot" => Ok(Self::InvalidSnapshot),
+            "program-load-error" => Ok(Self::ProgramLoadError),
+            "write-protected" => Ok(Self::WriteProtected),
+            "invalid-config" => Ok(Self::InvalidConfig),
+            "invalid-replay" => Ok(Self::InvalidReplay),
+            "invalid-trace" => Ok(Self::InvalidTrace),
+            "test-failed" => Ok(Self::TestFailed),
+            "script-error" => Ok(Self::ScriptError),
+            _ => Err(())
+        }
+    }
+}
+
+impl ErrorKind {
+    /// The process exit status for a run that ended with this kind of error,
+    /// distinct for each kind so scripts can tell them apart. They start at
+    /// 10 to stay clear of the generic failure (1) and usage error (2).
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::StackOverflow => 10,
+            Self::StackUnderflow => 11,
+            Self::InfiniteLoop => 12,
+            Self::InvalidAddress => 13,
+            Self::InvalidRegister => 14,
+            Self::SegmentationFault => 15,
+            Self::InvalidInstruction => 16,
+            Self::InvalidSnapshot => 17,
+            Self::ProgramLoadError => 18,
+            Self::WriteProtected => 19,
+            Self::InvalidConfig => 20,
+            Self::InvalidReplay => 21,
+            Self::InvalidTrace => 22,
+            Self::TestFailed => 23,
+            Self::ScriptError => 24
+        }
+    }
+}
+
+/// A default `ErrorPolicy` plus overrides for specific kinds of error.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorPolicies {
+    default: ErrorPolicy,
+    overrides: Vec<(ErrorKind, ErrorPolicy)>
+}
+
+impl ErrorPolicies {
+    pub fn get(&self, kind: ErrorKind) -> ErrorPolicy {
+        self.overrides.iter()
+            .find(|(k, _)| *k == kind)
+            .map_or(self.default, |(_, p)| *p)
+    }
+
+    /// Set the policy for every kind of error without an override.
+    pub fn set_default(&mut self, policy: ErrorPolicy) {
+        self.default = policy;
+    }
+
+    pub fn set(&mut self, kind: ErrorKind, policy: ErrorPolicy) {
+        self.overrides.retain(|(k, _)| *k != kind);
+        self.overrides.push((kind, policy));
+    }
+}
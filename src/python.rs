@@ -0,0 +1,218 @@
+use crate::{address::Address, cheat::Target, cpu::{Cpu, CpuError}, register::VRegister, screen::NCOLS};
+use numpy::{ndarray::Array2, IntoPyArray, PyArray2};
+use pyo3::{create_exception, exceptions::PyException, prelude::*, types::PyBytes};
+use std::path::PathBuf;
+
+create_exception!(chip8, Chip8Error, PyException, "The machine faulted or was given something invalid.");
+
+impl From<CpuError> for PyErr {
+    fn from(e: CpuError) -> Self {
+        Chip8Error::new_err(e.to_string())
+    }
+}
+
+/// The machine, for driving from Python, e.g. as an RL environment:
+///
+/// ```python
+/// import chip8
+///
+/// machine = chip8.Chip8.load("rom/tank.ch8")
+/// machine.seed = 1
+/// start = machine.save_state()
+/// while machine.st == 0:
+///     machine.press(5)
+///     machine.run_frame()
+///     observation = machine.framebuffer()
+/// machine.load_state(start)
+/// ```
+#[pyclass(name = "Chip8", module = "chip8", unsendable)]
+pub struct Machine {
+    cpu: Cpu
+}
+
+#[pymethods]
+impl Machine {
+    /// A machine running `rom`, the program's bytes.
+    #[new]
+    fn new(rom: Vec<u8>) -> PyResult<Self> {
+        Ok(Self { cpu: Cpu::from_program(rom)? })
+    }
+
+    /// A machine running the ROM at `path`, which may be zipped.
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        Ok(Self { cpu: Cpu::new(path)? })
+    }
+
+    /// Start the program over, as if the machine was turned off and on.
+    fn reset(&mut self) -> PyResult<()> {
+        Ok(self.cpu.reset()?)
+    }
+
+    /// Run one instruction, returning whether the display changed.
+    fn step(&mut self) -> PyResult<bool> {
+        Ok(self.cpu.step()? == crate::cpu::StepOutcome::DrewFrame)
+    }
+
+    /// Run a 60th of a second's worth of instructions and tick the timers,
+    /// returning whether the display changed.
+    fn run_frame(&mut self) -> PyResult<bool> {
+        Ok(self.cpu.run_frame()?.drawn)
+    }
+
+    /// The display as a `(rows, 64)` array of `uint8`, 1 where a pixel is lit.
+    fn framebuffer<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<u8>> {
+        let screen = self.cpu.screen();
+        Array2::from_shape_fn((screen.height(), NCOLS), |(y, x)| screen.pixel(x, y) as u8).into_pyarray(py)
+    }
+
+    fn press(&mut self, key: u8) -> PyResult<()> {
+        self.cpu.press_key(check_key(key)?);
+        Ok(())
+    }
+
+    fn release(&mut self, key: u8) -> PyResult<()> {
+        self.cpu.release_key(check_key(key)?);
+        Ok(())
+    }
+
+    fn key_pressed(&self, key: u8) -> PyResult<bool> {
+        Ok(self.cpu.key_pressed(check_key(key)?))
+    }
+
+    /// V0 through VF.
+    #[getter]
+    fn v(&self) -> [u8; 16] {
+        *self.cpu.registers()
+    }
+
+    fn set_v(&mut self, n: u8, value: u8) -> PyResult<()> {
+        self.cpu.set_v(VRegister::try_from(n).map_err(CpuError::from)?, value);
+        Ok(())
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.cpu.pc().0
+    }
+
+    #[setter]
+    fn set_pc(&mut self, pc: u16) {
+        self.cpu.set_pc(Address(pc));
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.cpu.i().0
+    }
+
+    #[setter]
+    fn set_i(&mut self, i: u16) {
+        self.cpu.set_i(Address(i));
+    }
+
+    #[getter]
+    fn dt(&self) -> u8 {
+        self.cpu.timers().0
+    }
+
+    #[setter]
+    fn set_dt(&mut self, dt: u8) {
+        self.cpu.set_dt(dt);
+    }
+
+    #[getter]
+    fn st(&self) -> u8 {
+        self.cpu.timers().1
+    }
+
+    #[setter]
+    fn set_st(&mut self, st: u8) {
+        self.cpu.set_st(st);
+    }
+
+    /// Seeds `RND`, for runs that can be repeated.
+    #[getter]
+    fn seed(&self) -> u64 {
+        self.cpu.seed()
+    }
+
+    #[setter]
+    fn set_seed(&mut self, seed: u64) {
+        self.cpu.set_seed(seed);
+    }
+
+    /// All of memory.
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.cpu.memory.to_bytes())
+    }
+
+    fn peek(&self, addr: u16) -> PyResult<u8> {
+        Ok(self.cpu.memory.get_byte(Address(addr)).map_err(CpuError::from)?)
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) -> PyResult<()> {
+        Ok(self.cpu.poke(Target::Memory(Address(addr)), value)?)
+    }
+
+    /// The machine state, in the same format as `--save-state` files.
+    fn save_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new(py, &self.cpu.to_state()?))
+    }
+
+    /// Restore a state from `save_state` of the same ROM.
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        Ok(self.cpu.restore_state(state)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Chip8 pc={} i={}>", self.cpu.pc(), self.cpu.i())
+    }
+}
+
+fn check_key(key: u8) -> PyResult<u8> {
+    match key {
+        0..=0xF => Ok(key),
+        _ => Err(Chip8Error::new_err(format!("No key {key:#x}")))
+    }
+}
+
+#[pymodule]
+fn chip8(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Machine>()?;
+    m.add("Chip8Error", m.py().get_type::<Chip8Error>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::IntoPyDict;
+
+    #[test]
+    fn test_machine() {
+        Python::initialize();
+        Python::attach(|py| {
+            let machine = Bound::new(py, Machine::new(vec![0x60, 0x05, 0x70, 0x01, 0x12, 0x02]).unwrap()).unwrap();
+            let module = PyModule::new(py, "chip8").unwrap();
+            chip8(&module).unwrap();
+            let locals = [("machine", machine.as_any()), ("chip8", module.as_any())]
+                .into_py_dict(py).unwrap();
+            py.run(c"
+machine.step()
+state = machine.save_state()
+machine.step()
+machine.poke(0x300, 7)
+assert (machine.v[0], machine.pc, machine.peek(0x300)) == (6, 0x204, 7), machine
+machine.load_state(state)
+assert (machine.v[0], machine.pc) == (5, 0x202), machine
+try:
+    machine.press(16)
+    assert False
+except chip8.Chip8Error:
+    pass
+", None, Some(&locals)).unwrap();
+        });
+    }
+}
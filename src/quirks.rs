@@ -0,0 +1,35 @@
+use core::str::FromStr;
+
+/// Behaviours that differ between CHIP-8 interpreters. Most ROMs don't care, 
+/// but some only run correctly with the quirks of the interpreter they were 
+/// written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// `Fx1E` sets `VF` to 1 when `I` overflows past `0xFFF` and to 0 
+    /// otherwise, as the Amiga interpreter did. Spacefight 2091! relies on it.
+    pub add_i_overflow: bool,
+    /// What `Fx1E` does when `I` would point past the end of memory.
+    pub index_overflow: IndexOverflow
+}
+
+/// How `I` is kept within memory by `Fx1E`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexOverflow {
+    /// Wrap around to the start of memory (the default).
+    #[default]
+    Wrap,
+    /// Raise `CpuError::InvalidAddress`.
+    Fault
+}
+
+impl FromStr for IndexOverflow {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(Self::Wrap),
+            "fault" => Ok(Self::Fault),
+            _ => Err(())
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use std::fmt::{Display, Formatter};
-use std::ops::{IndexMut, Index};
+use core::fmt::{Display, Formatter};
+use core::ops::{IndexMut, Index};
+use alloc::{format, string::String};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum VRegister { 
     V0 = 0x0,
     V1 = 0x1,
@@ -67,7 +68,7 @@ impl IndexMut<VRegister> for [u8] {
 }
 
 impl Display for VRegister {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
         write!(f, "{self:?}")
     }
 }
\ No newline at end of file
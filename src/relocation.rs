@@ -0,0 +1,96 @@
+use alloc::{borrow::Cow, format, vec::Vec};
+use crate::{address::Address, cpu::{CpuError, PC_START}};
+
+/// Where a program assembled to run from `PC_START` refers to its own
+/// addresses, so it can be loaded anywhere else: the offsets into the program
+/// of the `JP`, `CALL`, `LD I`, and `JP V0` instructions to move along with
+/// it.
+///
+/// Written a hex offset per line, e.g. `0x01a`. Blank lines and `#` comments
+/// are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Relocations {
+    offsets: Vec<usize>
+}
+
+impl Relocations {
+    pub fn new(offsets: Vec<usize>) -> Self {
+        Self { offsets }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, CpuError> {
+        let mut offsets = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let offset = usize::from_str_radix(line.trim_start_matches("0x"), 16).map_err(|_| {
+                CpuError::InvalidConfig(format!("line {}: expected a hex offset, found `{line}`", n + 1))
+            })?;
+            offsets.push(offset);
+        }
+
+        Ok(Self { offsets })
+    }
+
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// `program` with its addresses moved to run from `start`, or as it is
+    /// without any relocations.
+    pub fn apply<'a>(&self, program: &'a [u8], start: Address) -> Result<Cow<'a, [u8]>, CpuError> {
+        if self.offsets.is_empty() {
+            return Ok(Cow::Borrowed(program));
+        }
+
+        let mut relocated = program.to_vec();
+        for &offset in &self.offsets {
+            let invalid = |reason| CpuError::InvalidConfig(format!("Can't relocate offset {offset:#05x}: {reason}"));
+            let Some(bytes) = relocated.get_mut(offset..).and_then(|rest| rest.get_mut(..2)) else {
+                return Err(invalid("past the end of the program"));
+            };
+
+            let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+            if !matches!(opcode >> 12, 0x1 | 0x2 | 0xA | 0xB) {
+                return Err(invalid("not an instruction with an address"));
+            }
+            let target = (opcode & 0xFFF).checked_sub(PC_START.0).ok_or_else(|| invalid("points below the program"))?;
+            let target = start.0 as usize + target as usize;
+            if target > 0xFFF {
+                return Err(invalid("moved out of reach of a 12-bit address"));
+            }
+            bytes.copy_from_slice(&((opcode & 0xF000) | target as u16).to_be_bytes());
+        }
+
+        Ok(Cow::Owned(relocated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_relocations() {
+        let relocations = Relocations::parse("# jump over the data\n0x000\n\n4 # and load it\n").unwrap();
+        assert_eq!(relocations.offsets(), &[0, 4]);
+        assert!(Relocations::parse("x").is_err());
+
+        // `JP 0x206`, data, `LD I, 0x202`.
+        let program = [0x12, 0x06, 0xF0, 0x90, 0xA2, 0x02];
+        assert_eq!(*relocations.apply(&program, PC_START).unwrap(), program);
+        assert_eq!(*relocations.apply(&program, Address(0x600)).unwrap(), [0x16, 0x06, 0xF0, 0x90, 0xA6, 0x02]);
+        assert!(relocations.apply(&program, Address(0xFFC)).is_err());
+        assert!(Relocations::new(vec![2]).apply(&program, Address(0x600)).is_err());
+        assert!(Relocations::new(vec![5]).apply(&program, Address(0x600)).is_err());
+        assert!(Relocations::new(vec![usize::MAX]).apply(&program, Address(0x600)).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::cpu::Cpu;
+use std::{
+    collections::VecDeque, fs::File, io::{self, BufWriter, Write}, path::Path
+};
+
+#[derive(Debug)]
+pub struct InvalidReplay(pub String);
+
+/// A keypad key pressed or released before an emulated frame, counting from
+/// 0 for the first frame run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool
+}
+
+/// Input played back from a file, so a run can be repeated exactly. Each line
+/// is `<frame> <key> down` or `<frame> <key> up`, with the key in hex, e.g.
+/// `120 a down`. Blank lines and `#` comments are ignored.
+#[derive(Debug, Default)]
+pub struct Replay {
+    events: VecDeque<KeyEvent>
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, InvalidReplay> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| InvalidReplay(format!("Failed to read {}: {e}", path.display())))?;
+        Self::parse(&text).map_err(|InvalidReplay(e)| InvalidReplay(format!("{}: {e}", path.display())))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, InvalidReplay> {
+        let mut events = VecDeque::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || InvalidReplay(format!("line {}: expected `<frame> <key> down|up`, found `{line}`", n + 1));
+            let event = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [frame, key, state] => KeyEvent {
+                    frame: frame.parse().map_err(|_| invalid())?,
+                    key: u8::from_str_radix(key, 16).ok().filter(|&k| k <= 0xF).ok_or_else(invalid)?,
+                    pressed: match state {
+                        "down" => true,
+                        "up" => false,
+                        _ => return Err(invalid())
+                    }
+                },
+                _ => return Err(invalid())
+            };
+
+            if events.back().is_some_and(|last: &KeyEvent| last.frame > event.frame) {
+                return Err(InvalidReplay(format!("line {}: frame {} is out of order", n + 1, event.frame)));
+            }
+            events.push_back(event);
+        }
+
+        Ok(Self { events })
+    }
+
+    /// Press and release the keys due before `frame` on `cpu`, returning the
+    /// events applied.
+    pub fn apply(&mut self, cpu: &mut Cpu, frame: u64) -> Vec<KeyEvent> {
+        let due = self.events.iter().take_while(|e| e.frame <= frame).count();
+        let events: Vec<KeyEvent> = self.events.drain(..due).collect();
+        for event in &events {
+            if event.pressed {
+                cpu.press_key(event.key);
+            } else {
+                cpu.release_key(event.key);
+            }
+        }
+        events
+    }
+
+    /// True once every event has been played.
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Writes key events in the format `Replay` reads, so a session can be
+/// played back later.
+pub struct Recorder {
+    out: BufWriter<File>
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "# frame key down|up")?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, event: KeyEvent) -> io::Result<()> {
+        let state = if event.pressed { "down" } else { "up" };
+        writeln!(self.out, "{} {:x} {state}", event.frame, event.key)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+/// Where a run's input comes from besides the keyboard, and where it goes.
+#[derive(Default)]
+pub struct Script {
+    pub replay: Option<Replay>,
+    pub recorder: Option<Recorder>
+}
+
+impl Script {
+    /// Play back the input due before `frame`, recording it too, and return
+    /// the keys pressed.
+    pub fn start_frame(&mut self, cpu: &mut Cpu, frame: u64) -> Vec<u8> {
+        let events = match &mut self.replay {
+            Some(replay) => replay.apply(cpu, frame),
+            None => return Vec::new()
+        };
+        let pressed = events.iter().filter(|event| event.pressed).map(|event| event.key).collect();
+        events.into_iter().for_each(|event| self.record(event));
+        pressed
+    }
+
+    /// Record `event` if recording, logging rather than stopping the game if
+    /// the file can't be written.
+    pub fn record(&mut self, event: KeyEvent) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.record(event) {
+                tracing::warn!("Failed to record input: {e}");
+                self.recorder = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay() {
+        let path = std::env::temp_dir().join(format!("chip8-replay-{}", std::process::id()));
+        {
+            let mut recorder = Recorder::create(&path).unwrap();
+            recorder.record(KeyEvent { frame: 0, key: 0x5, pressed: true }).unwrap();
+            recorder.record(KeyEvent { frame: 3, key: 0x5, pressed: false }).unwrap();
+        }
+
+        let mut replay = Replay::load(&path).unwrap();
+        let mut cpu = Cpu::with_program(&[0xF00A]).unwrap();
+        assert_eq!(replay.apply(&mut cpu, 0).len(), 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.snapshot().v[0], 0x5);
+        assert!(replay.apply(&mut cpu, 2).is_empty());
+        assert_eq!(replay.apply(&mut cpu, 3), vec![KeyEvent { frame: 3, key: 0x5, pressed: false }]);
+        assert!(replay.is_finished());
+
+        assert!(Replay::parse("4 1 down\n2 1 up").unwrap_err().0.contains("out of order"));
+        assert!(Replay::parse("1 10 down").is_err());
+        assert!(Replay::parse("1 f sideways").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
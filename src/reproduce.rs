@@ -0,0 +1,64 @@
+use crate::{
+    address::Address, cpu::{Cpu, CpuError}, emulator::{self, Limits}, heatmap::HeatMap, policy::ErrorKind,
+    replay::Script
+};
+
+/// How long a reproducer is run to check it still faults, when the original
+/// run had no limits: five minutes of emulated time.
+pub const CHECK_FRAMES: u64 = 5 * 60 * 60;
+
+/// Cut `program`, loaded at `load_address`, down to the bytes a run read as
+/// counted by `heatmap`, whether as instructions or data. Bytes never read
+/// are zeroed and any after the last one read are dropped, so what is left
+/// is the code path and data behind a fault, in its original place.
+pub fn minimize(program: &[u8], load_address: Address, heatmap: &HeatMap) -> Vec<u8> {
+    let read = |offset: usize| heatmap.reads(Address(load_address.0.wrapping_add(offset as u16))) > 0;
+    let len = (0..program.len()).rev().find(|&offset| read(offset)).map_or(0, |last| last + 1);
+    program[..len].iter().enumerate().map(|(offset, &byte)| if read(offset) { byte } else { 0 }).collect()
+}
+
+/// The kind of a fault and the address of the instruction that raised it,
+/// or `None` for errors that aren't faults.
+pub fn fault_site(error: &CpuError) -> Option<(ErrorKind, Address)> {
+    match error {
+        CpuError::Fault(_, context) => Some((error.kind(), context.pc)),
+        _ => None
+    }
+}
+
+/// Whether running `cpu` headlessly within `limits` faults the same way as
+/// `error`: with the same kind of error at the same address.
+pub fn reproduces(cpu: &mut Cpu, limits: Limits, error: &CpuError) -> bool {
+    let expected = fault_site(error);
+    match emulator::run_limited(cpu, limits, &mut Script::default()) {
+        Ok(_) => false,
+        Err(e) => expected.is_some() && fault_site(&e) == expected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Ram;
+
+    #[test]
+    fn test_minimize() {
+        // `LD I, 0x20A`, `LD V0, [I]` (reading the byte at 0x20A), a return
+        // with nothing on the stack, unreachable code, then the byte read.
+        let program = vec![0xA2, 0x0A, 0xF0, 0x65, 0x00, 0xEE, 0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC];
+        let heatmap = HeatMap::new(0x1000);
+        let memory = heatmap.attach(Box::new(Ram::new()));
+        let mut cpu = Cpu::with_memory(program.clone(), memory).unwrap();
+        heatmap.clear();
+        let limits = Limits { cycles: Some(100), frames: None };
+        let error = emulator::run_limited(&mut cpu, limits, &mut Script::default()).unwrap_err();
+        assert_eq!(fault_site(&error), Some((ErrorKind::StackUnderflow, Address(0x204))));
+
+        let minimized = minimize(&program, Address(0x200), &heatmap);
+        assert_eq!(minimized, [0xA2, 0x0A, 0xF0, 0x65, 0x00, 0xEE, 0, 0, 0, 0, 0x9A]);
+        let mut cpu = Cpu::from_program(minimized).unwrap();
+        assert!(reproduces(&mut cpu, limits, &error));
+        let mut cpu = Cpu::from_program(program).unwrap();
+        assert!(!reproduces(&mut cpu, limits, &CpuError::InfiniteLoop));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{memory::PAGE_SIZE, snapshot::Snapshot};
+use alloc::{collections::VecDeque, vec::Vec};
+
+/// A bounded history of recent machine states. Once full, recording a new 
+/// state discards the oldest one.
+///
+/// Only the most recent state's memory is kept in full. Every other entry 
+/// stores just the pages that changed after it, which keeps the history small
+/// even for the 64K XO-CHIP address space.
+pub struct Rewind {
+    history: VecDeque<Entry>,
+    /// Memory as of the most recently recorded state.
+    memory: Vec<u8>,
+    capacity: usize
+}
+
+struct Entry {
+    /// The recorded state, with its memory moved out into `Rewind::memory`.
+    state: Snapshot,
+    /// The contents of the pages written since the previous entry, as they 
+    /// were in that entry.
+    undo: Vec<(usize, Vec<u8>)>
+}
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Self {
+        Self { history: VecDeque::with_capacity(capacity), memory: Vec::new(), capacity }
+    }
+
+    /// Record a state, given the memory pages written since the previously 
+    /// recorded one (see `Cpu::take_dirty_pages`).
+    pub fn record(&mut self, mut snapshot: Snapshot, dirty: &[usize]) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        // Without a previous state of the same size there is nothing to diff.
+        if self.memory.len() != snapshot.memory.len() {
+            self.history.clear();
+            self.memory = core::mem::take(&mut snapshot.memory);
+            self.history.push_back(Entry { state: snapshot, undo: Vec::new() });
+            return;
+        }
+
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+
+        let undo = dirty.iter()
+            .filter_map(|&page| {
+                let start = page * PAGE_SIZE;
+                let end = (start + PAGE_SIZE).min(self.memory.len());
+                self.memory.get(start..end).map(|bytes| (page, bytes.to_vec()))
+            })
+            .collect();
+        self.memory = core::mem::take(&mut snapshot.memory);
+        self.history.push_back(Entry { state: snapshot, undo });
+    }
+
+    /// Step back to the most recently recorded state, removing it from the 
+    /// history.
+    pub fn step_back(&mut self) -> Option<Snapshot> {
+        let entry = self.history.pop_back()?;
+        let mut snapshot = entry.state;
+        snapshot.memory = self.memory.clone();
+
+        for (page, bytes) in entry.undo {
+            let start = page * PAGE_SIZE;
+            self.memory[start..start + bytes.len()].copy_from_slice(&bytes);
+        }
+
+        Some(snapshot)
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.memory.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{address::Address, cpu::Cpu};
+    use alloc::vec;
+
+    #[test]
+    fn test_rewind_discards_oldest() {
+        let mut rewind = Rewind::new(2);
+        let mut cpus = (0u8..3)
+            .map(|b| Cpu::from_program(vec![b]).unwrap())
+            .collect::<Vec<_>>();
+        let snapshots = cpus.iter()
+            .map(|cpu| cpu.snapshot())
+            .collect::<Vec<_>>();
+
+        for (cpu, s) in cpus.iter_mut().zip(snapshots.iter()) {
+            rewind.record(s.clone(), &cpu.take_dirty_pages());
+        }
+
+        assert_eq!(rewind.len(), 2);
+        assert_eq!(rewind.step_back().as_ref(), Some(&snapshots[2]));
+        assert_eq!(rewind.step_back().as_ref(), Some(&snapshots[1]));
+        assert_eq!(rewind.step_back(), None);
+    }
+
+    #[test]
+    fn test_rewind_stores_dirty_pages() {
+        let mut rewind = Rewind::new(4);
+        let mut cpu = Cpu::from_program(vec![0x00, 0xE0]).unwrap();
+        let mut snapshots = Vec::new();
+
+        for b in 1u8..4 {
+            snapshots.push(cpu.snapshot());
+            rewind.record(cpu.snapshot(), &cpu.take_dirty_pages());
+            cpu.memory.set_byte(Address(0x300 + b as u16), b).unwrap();
+        }
+
+        assert!(rewind.history.iter().skip(1).all(|e| e.undo.len() == 1 && e.undo[0].0 == 3));
+        for s in snapshots.iter().rev() {
+            assert_eq!(rewind.step_back().as_ref(), Some(s));
+        }
+    }
+}
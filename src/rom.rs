@@ -0,0 +1,237 @@
+use crate::zip;
+use std::{
+    env, fs, io, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+const ROM_EXTENSIONS: [&str; 2] = ["ch8", "sc8"];
+/// File in the home directory that `PlayHistory` is kept in by default.
+const HISTORY_FILE: &str = ".chip8_history";
+/// Number of ROMs `PlayHistory` remembers.
+const RECENT_LIMIT: usize = 50;
+
+/// Watches a ROM file on disk so it can be reloaded whenever it is rebuilt.
+pub struct RomWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>
+}
+
+impl RomWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Start watching a different file, e.g. after switching ROMs.
+    pub fn watch(&mut self, path: PathBuf) {
+        *self = Self::new(path);
+    }
+
+    /// Returns true if the file has been modified since the last call.
+    pub fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    let file = in_archive(path).map_or(path, |(archive, _)| archive);
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// Read the ROM at `path`. A zip archive is read as the first ROM in it, and
+/// a path inside one, e.g. `roms.zip/games/pong.ch8`, as that file.
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let Some((archive, inner)) = in_archive(path) else {
+        return fs::read(path);
+    };
+
+    let bytes = fs::read(archive)?;
+    let entries = zip::entries(&bytes)?;
+    let entry = match inner {
+        Some(inner) => entries.iter().find(|entry| Path::new(&entry.name) == inner),
+        None => entries.iter().find(|entry| is_rom(Path::new(&entry.name)))
+    };
+    let Some(entry) = entry else {
+        let what = inner.map_or_else(|| "no ROM".to_string(), |inner| inner.display().to_string());
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{what} in {}", archive.display())));
+    };
+    zip::extract(&bytes, entry)
+}
+
+/// The zip archive `path` is, or is a path inside, with the path inside it.
+fn in_archive(path: &Path) -> Option<(&Path, Option<&Path>)> {
+    let archive = path.ancestors().find(|ancestor| {
+        ancestor.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) && ancestor.is_file()
+    })?;
+    let inner = path.strip_prefix(archive).ok().filter(|inner| !inner.as_os_str().is_empty());
+    Some((archive, inner))
+}
+
+fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Identify a ROM by its contents, as `sha1:` and the hex digest, so it is
+/// recognised whatever the file is called.
+pub fn hash(program: &[u8]) -> String {
+    format!("sha1:{}", sha1_smol::Sha1::from(program).digest())
+}
+
+/// List every ROM in the same directory as `path`, or the same archive,
+/// sorted by file name.
+pub fn siblings(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if let Some((archive, _)) = in_archive(path) {
+        let mut roms: Vec<_> = zip::entries(&fs::read(archive)?)?
+            .into_iter()
+            .map(|entry| archive.join(entry.name))
+            .filter(|path| is_rom(path))
+            .collect();
+        roms.sort();
+        return Ok(roms);
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new(".")
+    };
+
+    list(dir)
+}
+
+/// List every ROM in `dir`, sorted by file name.
+pub fn list(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| is_rom(p))
+        .collect::<Vec<_>>();
+
+    roms.sort();
+    Ok(roms)
+}
+
+/// Find the ROM `step` places away from `path` in its directory, wrapping 
+/// around at either end.
+pub fn neighbour(path: &Path, step: isize) -> io::Result<Option<PathBuf>> {
+    let roms = siblings(path)?;
+    if roms.is_empty() {
+        return Ok(None);
+    }
+
+    let name = path.file_name();
+    let current = roms.iter()
+        .position(|p| p.file_name() == name)
+        .unwrap_or(0) as isize;
+    let next = (current + step).rem_euclid(roms.len() as isize) as usize;
+
+    Ok(Some(roms[next].clone()))
+}
+
+/// A ROM that was launched, and when.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Played {
+    pub path: PathBuf,
+    /// The ROM's `hash` when it was launched, to match it against per-game
+    /// settings even if the file has since moved.
+    pub hash: String,
+    pub time: SystemTime
+}
+
+/// The ROMs launched recently, most recent first. It is stored as one
+/// `<unix seconds>\t<hash>\t<path>` line per ROM, with paths made absolute
+/// so the same ROM launched from different directories is one entry.
+pub struct PlayHistory {
+    path: PathBuf,
+    entries: Vec<Played>
+}
+
+impl PlayHistory {
+    /// Read the history from `path`. A missing or unreadable file is an empty
+    /// history, since losing it only loses the "last played" column.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (secs, rest) = line.split_once('\t')?;
+                let time = UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?);
+                // Older histories have no hash column.
+                let (hash, rom) = match rest.split_once('\t') {
+                    Some((hash, rom)) if hash.starts_with("sha1:") => (hash, rom),
+                    _ => ("", rest)
+                };
+                Some(Played { path: PathBuf::from(rom), hash: hash.to_string(), time })
+            })
+            .take(RECENT_LIMIT)
+            .collect();
+
+        Self { path, entries }
+    }
+
+    /// `~/.chip8_history`, or the working directory if there is no home.
+    pub fn default_path() -> PathBuf {
+        env::var_os("HOME").map(PathBuf::from).unwrap_or_default().join(HISTORY_FILE)
+    }
+
+    pub fn last_played(&self, rom: &Path) -> Option<SystemTime> {
+        let rom = absolute(rom);
+        self.entries.iter().find(|e| e.path == rom).map(|e| e.time)
+    }
+
+    /// The ROMs launched, most recent first.
+    pub fn recent(&self) -> &[Played] {
+        &self.entries
+    }
+
+    /// Note that `rom`, containing `program`, was just launched and save the
+    /// history, forgetting the oldest entries past `RECENT_LIMIT`.
+    pub fn record(&mut self, rom: &Path, program: &[u8]) -> io::Result<()> {
+        let rom = absolute(rom);
+        self.entries.retain(|e| e.path != rom);
+        self.entries.insert(0, Played { path: rom, hash: hash(program), time: SystemTime::now() });
+        self.entries.truncate(RECENT_LIMIT);
+
+        let contents: String = self.entries.iter()
+            .map(|e| {
+                let secs = e.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                format!("{secs}\t{}\t{}\n", e.hash, e.path.display())
+            })
+            .collect();
+        fs::write(&self.path, contents)
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_archive() {
+        let dir = env::temp_dir().join(format!("chip8-zip-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("roms.zip");
+        fs::write(&archive, zip::archive(&[("README", b"hi"), ("b.ch8", &[0x12, 0x00]), ("a.CH8", &[0x00, 0xE0])])).unwrap();
+
+        assert_eq!(read(&archive).unwrap(), [0x12, 0x00]);
+        assert_eq!(read(&archive.join("a.CH8")).unwrap(), [0x00, 0xE0]);
+        assert_eq!(read(&archive.join("c.ch8")).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(siblings(&archive.join("b.ch8")).unwrap(), [archive.join("a.CH8"), archive.join("b.ch8")]);
+        assert_eq!(neighbour(&archive.join("b.ch8"), 1).unwrap(), Some(archive.join("a.CH8")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
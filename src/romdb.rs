@@ -0,0 +1,45 @@
+use crate::{config::Settings, rom};
+use serde::Deserialize;
+use std::{collections::HashMap, sync::OnceLock};
+
+/// Well-known ROMs, keyed by `rom::hash`.
+const DATABASE: &str = include_str!("romdb.toml");
+
+/// A well-known ROM: what it's called, and the settings it plays best with.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KnownRom {
+    pub title: String,
+    #[serde(default)]
+    pub settings: Settings
+}
+
+/// What's known about `program`, if it's in the database.
+pub fn lookup(program: &[u8]) -> Option<&'static KnownRom> {
+    static ROMS: OnceLock<HashMap<String, KnownRom>> = OnceLock::new();
+    // The database is checked by the tests, so it always parses.
+    ROMS.get_or_init(|| toml::from_str(DATABASE).unwrap_or_default()).get(&rom::hash(program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, platform::Platform};
+
+    #[test]
+    fn test_database() {
+        let roms: HashMap<String, KnownRom> = toml::from_str(DATABASE).unwrap();
+        assert!(roms.keys().all(|hash| hash.starts_with("sha1:") && hash.len() == 45));
+
+        let program = include_bytes!("../rom/ibm.ch8");
+        let ibm = lookup(program).unwrap();
+        assert_eq!((ibm.title.as_str(), ibm.settings.platform), ("IBM Logo", Some(Platform::Chip8)));
+        assert!(lookup(&[0x00, 0xE0]).is_none());
+
+        // Applied over the defaults, but under the game's own settings.
+        let settings = Config::parse("ips = 900").unwrap().settings(program);
+        assert_eq!((settings.ips, settings.platform), (Some(900), Some(Platform::Chip8)));
+        let text = format!("[game.\"{}\"]\nplatform = \"xo-chip\"", rom::hash(program));
+        assert_eq!(Config::parse(&text).unwrap().settings(program).platform, Some(Platform::XoChip));
+    }
+}
@@ -1,54 +1,535 @@
-use std::fmt::{self, Display, Formatter};
+use core::{fmt::{self, Display, Formatter, Write}, str::FromStr};
+use alloc::string::String;
 
+/// Rows in the usual display. Some platforms have taller ones, up to
+/// `MAX_ROWS`.
 pub const NROWS: usize = 32;
 pub const NCOLS: usize = 64;
+/// Rows in the tallest display, the ETI-660's 64x64 mode.
+pub const MAX_ROWS: usize = 64;
+/// The pixel each dot of a braille cell stands for, as (x, y) within the 2x4
+/// pixels it covers, in the order of the bits in its code point.
+const BRAILLE_DOTS: [(usize, usize); 8] = [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (0, 3), (1, 3)];
 
+/// The display as one bitmask per row, with the leftmost pixel in the MSB, so
+/// drawing a sprite row is a shift and an XOR.
+#[derive(Clone, PartialEq, Eq)]
 pub struct Screen {
-    pixels: [[bool; NCOLS]; NROWS]
+    rows: [u64; MAX_ROWS],
+    /// Rows in use, `NROWS` unless the platform's display is taller.
+    height: usize,
+    /// The colors pixels are drawn in, on platforms that have them.
+    colors: Option<Colors>
 }
 
 impl Screen {
     pub fn new() -> Self {
-        Self { pixels: [[false; NCOLS]; NROWS] }
+        Self { rows: [0; MAX_ROWS], height: NROWS, colors: None }
     }
 
     pub fn clear(&mut self) {
-        self.pixels = [[false; NCOLS]; NROWS];
+        self.rows = [0; MAX_ROWS];
+    }
+
+    /// Rows in the display.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Change the display to `height` rows, up to `MAX_ROWS`, e.g. 48 for the
+    /// ETI-660, and clear it.
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height.clamp(1, MAX_ROWS);
+        self.clear();
+    }
+
+    /// Whether the pixel at (`x`, `y`) is lit. Out of range pixels are off.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        x < NCOLS && self.rows().get(y).is_some_and(|row| row & Self::bit(x) != 0)
     }
 
     pub fn flip(&mut self, x: usize, y: usize) -> Option<bool> {
-        if x >= NCOLS || y >= NROWS {
-            return None;
+        if x >= NCOLS || y >= self.height {
+            None
         } else {
-            let out = self.pixels[y][x];
-            self.pixels[y][x] = !out;
+            let out = self.rows[y] & Self::bit(x) != 0;
+            self.rows[y] ^= Self::bit(x);
             Some(out)
         }
     }
 
+    /// XOR an 8 pixel sprite row onto row `y` starting at column `x`, clipping
+    /// whatever falls past the right edge. Returns whether any lit pixel was
+    /// turned off, or `None` if `y` is off screen.
+    pub fn draw_row(&mut self, x: usize, y: usize, sprite: u8) -> Option<bool> {
+        let row = self.rows[..self.height].get_mut(y)?;
+        let mask = ((sprite as u64) << (NCOLS - 8)).checked_shr(x as u32).unwrap_or(0);
+        let collision = *row & mask != 0;
+        *row ^= mask;
+        Some(collision)
+    }
+
+    /// Each row as a bitmask, with the leftmost pixel in the MSB.
+    pub fn rows(&self) -> &[u64] {
+        &self.rows[..self.height]
+    }
+
+    /// Restore the display from bitmasks produced by `rows`, taking its height
+    /// from how many there are.
+    pub fn set_rows(&mut self, rows: &[u64]) {
+        self.set_height(rows.len());
+        for (row, &mask) in self.rows.iter_mut().zip(rows) {
+            *row = mask;
+        }
+    }
+
+    /// The CHIP-8X colors, if the display has them.
+    pub fn colors(&self) -> Option<&Colors> {
+        self.colors.as_ref()
+    }
+
+    pub fn colors_mut(&mut self) -> Option<&mut Colors> {
+        self.colors.as_mut()
+    }
+
+    /// Give the display CHIP-8X colors, or take them away with `None`.
+    pub fn set_colors(&mut self, colors: Option<Colors>) {
+        self.colors = colors;
+    }
+
+    /// The color of the pixel at (`x`, `y`), if the display has colors.
+    pub fn color(&self, x: usize, y: usize) -> Option<Rgb> {
+        let colors = self.colors.as_ref()?;
+        Some(if self.pixel(x, y) { colors.foreground(x, y) } else { colors.background() })
+    }
+
+    fn bit(x: usize) -> u64 {
+        1 << (NCOLS - 1 - x)
+    }
+
+    /// Draw the display to the terminal. Without `std` there is no terminal,
+    /// so embedders render from an `on_draw` hook instead.
     pub fn show(&self) {
-        print!("{}", self)
+        #[cfg(feature = "std")]
+        let _ = self.show_with(&mut String::new());
     }
-}
 
-impl Display for Screen {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
-        writeln!(f, "\x1B[2J\x1B[H┌{}┐", "─".repeat(NCOLS))?;
-        for row in 0..NROWS {
-            write!(f, "│")?;
+    /// Draw the display to the terminal, rendering into `buffer` so a frontend
+    /// can reuse one allocation across frames. The frame goes to a locked
+    /// stdout in a single write.
+    #[cfg(feature = "std")]
+    pub fn show_with(&self, buffer: &mut String) -> std::io::Result<()> {
+        use std::io::Write;
+
+        self.render_into(buffer, ColorDepth::default());
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(buffer.as_bytes())?;
+        stdout.flush()
+    }
+
+    /// Replace the contents of `out` with the rendered display, keeping its
+    /// capacity, with any colors in `depth`.
+    pub fn render_into(&self, out: &mut String, depth: ColorDepth) {
+        out.clear();
+        let _ = self.render(out, depth);
+    }
+
+    /// Replace the contents of `out` with the display as plain text, one line
+    /// per row with `#` for lit pixels and `.` for the rest, for diffing.
+    pub fn render_text_into(&self, out: &mut String) {
+        out.clear();
+        for y in 0..self.height {
+            out.extend((0..NCOLS).map(|x| if self.pixel(x, y) { '#' } else { '.' }));
+            out.push('\n');
+        }
+    }
+
+    /// Like `render_into`, but without the border and scaled up by the largest
+    /// whole factor that fits a terminal `cols` wide and `rows` high, centered
+    /// with blank bars around it. Cells are about twice as tall as they are 
+    /// wide, so each holds two pixels stacked with half blocks to keep pixels
+    /// square and the display at 2:1.
+    ///
+    /// A terminal too small for that gets the display in braille, 2x4 pixels
+    /// a cell, cropped if it still doesn't fit, so it never wraps.
+    pub fn render_scaled_into(&self, out: &mut String, cols: usize, rows: usize, depth: ColorDepth) {
+        if cols < NCOLS || rows * 2 < self.height {
+            return self.render_braille_into(out, cols, rows, depth);
+        }
+        let scale = (cols / NCOLS).min(rows * 2 / self.height).max(1);
+        let (width, height) = (NCOLS * scale, self.height * scale / 2);
+        let left = cols.saturating_sub(width) / 2 + 1;
+        let top = rows.saturating_sub(height) / 2 + 1;
+
+        out.clear();
+        out.push_str("\x1B[2J");
+        for row in 0..height {
+            let _ = write!(out, "\x1B[{};{left}H", top + row);
+            let (upper, lower) = (2 * row / scale, (2 * row + 1) / scale);
+            for col in 0..width {
+                if let (Some(top), Some(bottom)) = (self.color(col / scale, upper), self.color(col / scale, lower)) {
+                    // The upper pixel is the foreground and the lower the 
+                    // background, whichever are lit.
+                    let _ = depth.foreground_into(out, top).and_then(|()| depth.background_into(out, bottom));
+                    out.push('▀');
+                    continue;
+                }
+                out.push(match (self.pixel(col / scale, upper), self.pixel(col / scale, lower)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' '
+                });
+            }
+        }
+        if self.colors.is_some() {
+            Palette::reset_into(out);
+        }
+    }
+
+    fn render_braille_into(&self, out: &mut String, cols: usize, rows: usize, depth: ColorDepth) {
+        let (width, height) = ((NCOLS / 2).min(cols), self.height.div_ceil(4).min(rows));
+        let left = (cols - width) / 2 + 1;
+        let top = (rows - height) / 2 + 1;
+
+        out.clear();
+        out.push_str("\x1B[2J");
+        for row in 0..height {
+            let _ = write!(out, "\x1B[{};{left}H", top + row);
+            for col in 0..width {
+                let lit = |&(x, y): &(usize, usize)| self.pixel(col * 2 + x, row * 4 + y);
+                if let Some(colors) = &self.colors {
+                    // A cell has one foreground, so it takes its first lit pixel's.
+                    let foreground = BRAILLE_DOTS.iter().find(|dot| lit(dot))
+                        .map_or(colors.background(), |(x, y)| colors.foreground(col * 2 + x, row * 4 + y));
+                    let _ = depth.foreground_into(out, foreground)
+                        .and_then(|()| depth.background_into(out, colors.background()));
+                }
+                let dots = BRAILLE_DOTS.iter().enumerate().filter(|(_, dot)| lit(dot)).fold(0, |dots, (bit, _)| dots | 1 << bit);
+                out.push(if dots == 0 { ' ' } else { char::from_u32(0x2800 + dots).unwrap_or(' ') });
+            }
+        }
+        if self.colors.is_some() {
+            Palette::reset_into(out);
+        }
+    }
+
+    fn render<W: fmt::Write>(&self, out: &mut W, depth: ColorDepth) -> fmt::Result {
+        out.write_str("\x1B[2J\x1B[H┌")?;
+        (0..NCOLS).try_for_each(|_| out.write_char('─'))?;
+        out.write_str("┐\r\n")?;
+
+        for (y, &row) in self.rows().iter().enumerate() {
+            out.write_char('│')?;
+            let mut last = None;
             for col in 0..NCOLS {
-                if self.pixels[row][col] {
-                    write!(f, "█")?
-                } else {
-                    write!(f, " ")?
+                let lit = row & Self::bit(col) != 0;
+                if let Some(colors) = &self.colors {
+                    // Only change colors where they change, a strip at a time.
+                    let color = (colors.background(), lit.then(|| colors.foreground(col, y)));
+                    if last != Some(color) {
+                        depth.background_into(out, color.0)?;
+                        if let Some(foreground) = color.1 {
+                            depth.foreground_into(out, foreground)?;
+                        }
+                        last = Some(color);
+                    }
                 }
+                out.write_char(if lit { '█' } else { ' ' })?;
             }
+            if self.colors.is_some() {
+                out.write_str("\x1B[0m")?;
+            }
+            out.write_str("│\r\n")?;
+        }
 
-            writeln!(f, "│")?
+        out.write_char('└')?;
+        (0..NCOLS).try_for_each(|_| out.write_char('─'))?;
+        out.write_str("┘\r\n")
+    }
+}
+
+/// A 24-bit color, parsed from `#rrggbb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl FromStr for Rgb {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(());
         }
 
-        writeln!(f, "└{}┘", "─".repeat(NCOLS))?;
+        let value = u32::from_str_radix(hex, 16).map_err(|_| ())?;
 
-        Ok(())
+        Ok(Self((value >> 16) as u8, (value >> 8) as u8, value as u8))
     }
-}
\ No newline at end of file
+}
+
+impl Rgb {
+    /// The nearest of the 240 colors xterm's 256-color palette has besides the
+    /// 16 the terminal theme sets: the 6x6x6 color cube from 16, or the gray
+    /// ramp from 232.
+    pub fn ansi256(self) -> u8 {
+        const LEVELS: [u8; 6] = [0, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+        let nearest = |c: u8| (0..LEVELS.len()).min_by_key(|&i| LEVELS[i].abs_diff(c)).unwrap_or_default();
+        let distance = |Rgb(r, g, b): Rgb| {
+            [(r, self.0), (g, self.1), (b, self.2)].iter().map(|&(a, b)| (a.abs_diff(b) as u32).pow(2)).sum::<u32>()
+        };
+
+        let (r, g, b) = (nearest(self.0), nearest(self.1), nearest(self.2));
+        let cube = Rgb(LEVELS[r], LEVELS[g], LEVELS[b]);
+        let average = (self.0 as u32 + self.1 as u32 + self.2 as u32) / 3;
+        let gray = (average.saturating_sub(3) / 10).min(23) as u8;
+        let level = 8 + 10 * gray;
+        if distance(Rgb(level, level, level)) < distance(cube) {
+            232 + gray
+        } else {
+            16 + 36 * r as u8 + 6 * g as u8 + b as u8
+        }
+    }
+}
+
+/// How colors are written to the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorDepth {
+    /// 24-bit escapes, exact on terminals that support them.
+    #[default]
+    TrueColor,
+    /// The nearest colors in the 256-color palette, for terminals without
+    /// 24-bit color such as macOS Terminal, or `screen` and older `tmux`.
+    Ansi256
+}
+
+impl ColorDepth {
+    /// Write the escape setting the foreground to `color`.
+    pub fn foreground_into<W: fmt::Write>(self, out: &mut W, color: Rgb) -> fmt::Result {
+        self.color_into(out, 38, color)
+    }
+
+    /// Write the escape setting the background to `color`.
+    pub fn background_into<W: fmt::Write>(self, out: &mut W, color: Rgb) -> fmt::Result {
+        self.color_into(out, 48, color)
+    }
+
+    fn color_into<W: fmt::Write>(self, out: &mut W, layer: u8, color: Rgb) -> fmt::Result {
+        match self {
+            Self::TrueColor => {
+                let Rgb(r, g, b) = color;
+                write!(out, "\x1B[{layer};2;{r};{g};{b}m")
+            },
+            Self::Ansi256 => write!(out, "\x1B[{layer};5;{}m", color.ansi256())
+        }
+    }
+}
+
+impl FromStr for ColorDepth {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truecolor" | "24bit" => Ok(Self::TrueColor),
+            "256" => Ok(Self::Ansi256),
+            _ => Err(())
+        }
+    }
+}
+
+/// The CHIP-8X foreground colors, by number.
+const FOREGROUNDS: [Rgb; 8] = [
+    Rgb(0, 0, 0), Rgb(0xff, 0, 0), Rgb(0, 0, 0xff), Rgb(0xff, 0, 0xff),
+    Rgb(0, 0xff, 0), Rgb(0xff, 0xff, 0), Rgb(0, 0xff, 0xff), Rgb(0xff, 0xff, 0xff)
+];
+/// The CHIP-8X background colors, in the order `02A0` cycles through them.
+const BACKGROUNDS: [Rgb; 4] = [Rgb(0, 0, 0x80), Rgb(0, 0, 0), Rgb(0, 0x80, 0), Rgb(0x80, 0, 0)];
+/// Width of the strips foreground colors are set for, in pixels.
+const ZONE_WIDTH: usize = 8;
+/// Height of the zones `Bxy0` colors, in rows.
+const ZONE_HEIGHT: usize = 4;
+
+/// The CHIP-8X color model: one background color behind the whole display,
+/// and a foreground color for lit pixels in each 8 pixel wide strip of each
+/// row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    background: usize,
+    foreground: [[u8; NCOLS / ZONE_WIDTH]; NROWS]
+}
+
+impl Colors {
+    /// Red on dark blue, as the VP-590 color board starts up.
+    pub fn new() -> Self {
+        Self { background: 0, foreground: [[1; NCOLS / ZONE_WIDTH]; NROWS] }
+    }
+
+    /// `02A0`: switch to the next background color.
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len();
+    }
+
+    pub fn background(&self) -> Rgb {
+        BACKGROUNDS[self.background]
+    }
+
+    /// The color lit pixels at (`x`, `y`) are drawn in.
+    pub fn foreground(&self, x: usize, y: usize) -> Rgb {
+        let color = self.foreground[y % NROWS][x % NCOLS / ZONE_WIDTH];
+        FOREGROUNDS[color as usize]
+    }
+
+    /// `Bxy0`: set the foreground of a block of 8x4 pixel zones to `color`.
+    /// The low nibbles of `horizontal` and `vertical` are the zone the block
+    /// starts at, and the high nibbles how many more zones it extends right
+    /// and down, wrapping around the edges.
+    pub fn fill_zones(&mut self, horizontal: u8, vertical: u8, color: u8) {
+        let (x, width) = ((horizontal & 0xF) as usize, (horizontal >> 4) as usize + 1);
+        let (y, height) = ((vertical & 0xF) as usize, (vertical >> 4) as usize + 1);
+        for zone_y in y..y + height {
+            for row in 0..ZONE_HEIGHT {
+                let row = (zone_y * ZONE_HEIGHT + row) % NROWS;
+                for zone_x in x..x + width {
+                    self.foreground[row][zone_x % (NCOLS / ZONE_WIDTH)] = color & 0x7;
+                }
+            }
+        }
+    }
+
+    /// `Bxyn`: set the foreground of the strip containing pixel (`x`, `y`)
+    /// and the `height - 1` rows below it to `color`.
+    pub fn fill_rows(&mut self, x: u8, y: u8, height: u8, color: u8) {
+        let zone_x = x as usize % NCOLS / ZONE_WIDTH;
+        for row in y as usize..y as usize + height as usize {
+            self.foreground[row % NROWS][zone_x] = color & 0x7;
+        }
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The colors of lit and unlit pixels. Renders use the terminal's own colors
+/// unless a palette is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub on: Rgb,
+    pub off: Rgb
+}
+
+impl Palette {
+    /// White on black, used where colors are needed but none are configured.
+    pub const DEFAULT: Palette = Palette { on: Rgb(0xff, 0xff, 0xff), off: Rgb(0, 0, 0) };
+    /// Yellow on black, which stays legible for low-vision users whatever the
+    /// terminal's own colors are.
+    pub const HIGH_CONTRAST: Palette = Palette { on: Rgb(0xff, 0xff, 0), off: Rgb(0, 0, 0) };
+
+    /// The same colors with lit and unlit pixels swapped, e.g. for users who
+    /// find dark pixels on a light background easier to see.
+    pub fn inverted(self) -> Palette {
+        Palette { on: self.off, off: self.on }
+    }
+
+    /// Append the escapes that switch the terminal to this palette, in
+    /// `depth`. Lit pixels are drawn as foreground blocks, so `on` is the
+    /// foreground and `off` the background.
+    pub fn apply_into(&self, out: &mut String, depth: ColorDepth) {
+        let _ = depth.foreground_into(out, self.on).and_then(|()| depth.background_into(out, self.off));
+    }
+
+    /// Append the escape that restores the terminal's colors.
+    pub fn reset_into(out: &mut String) {
+        out.push_str("\x1B[0m");
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for Screen {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        self.render(f, ColorDepth::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_draw_row() {
+        let mut screen = Screen::new();
+        assert_eq!(screen.draw_row(60, 2, 0xff), Some(false));
+        assert_eq!(screen.rows()[2], 0xf);
+        assert!(screen.pixel(63, 2) && !screen.pixel(59, 2));
+
+        assert_eq!(screen.draw_row(56, 2, 0x80), Some(false));
+        assert_eq!(screen.draw_row(62, 2, 0x80), Some(true));
+        assert!(!screen.pixel(62, 2));
+        assert_eq!(screen.draw_row(0, NROWS, 0xff), None);
+    }
+
+    #[test]
+    fn test_palette() {
+        assert_eq!("#33ff06".parse(), Ok(Rgb(0x33, 0xff, 0x06)));
+        assert_eq!("33FF06".parse(), Ok(Rgb(0x33, 0xff, 0x06)));
+        assert!("#3f6".parse::<Rgb>().is_err());
+
+        let mut out = String::new();
+        Palette { on: Rgb(255, 255, 255), off: Rgb(0, 0, 0) }.apply_into(&mut out, ColorDepth::TrueColor);
+        assert_eq!(out, "\x1B[38;2;255;255;255m\x1B[48;2;0;0;0m");
+
+        out.clear();
+        Palette { on: Rgb(0x33, 0xff, 0x66), off: Rgb(0x20, 0x20, 0x20) }.apply_into(&mut out, ColorDepth::Ansi256);
+        assert_eq!(out, "\x1B[38;5;83m\x1B[48;5;234m");
+        assert_eq!((Rgb(0, 0, 0).ansi256(), Rgb(255, 255, 255).ansi256(), Rgb(255, 0, 0).ansi256()), (16, 231, 196));
+    }
+
+    #[test]
+    fn test_render_into() {
+        let mut screen = Screen::new();
+        screen.flip(0, 0);
+
+        let mut buffer = String::from("stale");
+        screen.render_into(&mut buffer, ColorDepth::TrueColor);
+        assert_eq!(buffer, screen.to_string());
+        assert!(buffer.contains("│█ "));
+
+        // A 200x40 terminal fits the display at 2x, 128 columns by 32 rows.
+        screen.render_scaled_into(&mut buffer, 200, 40, ColorDepth::TrueColor);
+        assert!(buffer.starts_with("\x1B[2J\x1B[5;37H██  "));
+        assert!(buffer.contains("\x1B[36;37H  "));
+        assert!(!buffer.contains("\x1B[37;37H"));
+
+        // An 80x12 terminal is too short for half blocks, so 2x4 pixels go in
+        // each braille cell, 32 columns by 8 rows.
+        screen.flip(1, 3);
+        screen.render_scaled_into(&mut buffer, 80, 12, ColorDepth::TrueColor);
+        assert!(buffer.starts_with("\x1B[2J\x1B[3;25H\u{2881}  "));
+        assert!(buffer.contains("\x1B[10;25H ") && !buffer.contains("\x1B[11;25H"));
+        // A 20x4 terminal crops it.
+        screen.render_scaled_into(&mut buffer, 20, 4, ColorDepth::TrueColor);
+        assert!(buffer.starts_with("\x1B[2J\x1B[1;1H\u{2881}"));
+        assert!(buffer.ends_with(&" ".repeat(20)) && !buffer.contains("\x1B[5;1H"));
+        screen.flip(1, 3);
+
+        screen.render_text_into(&mut buffer);
+        assert_eq!(buffer.lines().count(), NROWS);
+        assert!(buffer.starts_with("#.") && buffer.ends_with("..\n"));
+
+        // Red on dark blue, with escapes only where the colors change.
+        screen.set_colors(Some(Colors::new()));
+        screen.render_into(&mut buffer, ColorDepth::TrueColor);
+        assert!(buffer.contains("│\x1B[48;2;0;0;128m\x1B[38;2;255;0;0m█\x1B[48;2;0;0;128m   "));
+        assert!(buffer.contains("\x1B[0m│\r\n"));
+        screen.render_into(&mut buffer, ColorDepth::Ansi256);
+        assert!(buffer.contains("│\x1B[48;5;18m\x1B[38;5;196m█\x1B[48;5;18m   "));
+    }
+}
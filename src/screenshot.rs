@@ -0,0 +1,145 @@
+use crate::screen::{Palette, Rgb, Screen, NCOLS};
+use std::{
+    fs::{self, File}, io::{self, BufWriter, Write}, path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+/// Directory screenshots are saved in, relative to the working directory.
+pub const SCREENSHOT_DIR: &str = "screenshots";
+/// Size of each CHIP-8 pixel in a screenshot, in image pixels.
+pub const DEFAULT_SCALE: u32 = 10;
+
+/// A display drawn as 8-bit RGBA pixels, a row at a time from the top left,
+/// for writing out as an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>
+}
+
+impl RgbaImage {
+    /// Draw `screen` with each pixel `scale` image pixels square, in the
+    /// display's own colors if it has them, and otherwise `palette`, or white
+    /// on black if there is none.
+    pub fn of(screen: &Screen, palette: Option<Palette>, scale: u32) -> Self {
+        let Palette { on, off } = palette.unwrap_or(Palette::DEFAULT);
+        let scale = scale.max(1) as usize;
+        let (width, height) = (NCOLS * scale, screen.height() * scale);
+
+        let mut data = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let (x, y) = (x / scale, y / scale);
+                let Rgb(r, g, b) = screen.color(x, y).unwrap_or(if screen.pixel(x, y) { on } else { off });
+                data.extend([r, g, b, 0xff]);
+            }
+        }
+
+        Self { width: width as u32, height: height as u32, data }
+    }
+}
+
+/// Write `screen` to `path` as a PNG, drawn as by `RgbaImage::of`.
+pub fn save(screen: &Screen, palette: Option<Palette>, scale: u32, path: &Path) -> io::Result<()> {
+    write(screen, palette, scale, BufWriter::new(File::create(path)?))
+}
+
+/// Like `save`, but writing the PNG to `out`.
+pub fn write<W: Write>(screen: &Screen, palette: Option<Palette>, scale: u32, out: W) -> io::Result<()> {
+    let image = RgbaImage::of(screen, palette, scale);
+    let mut encoder = png::Encoder::new(out, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.data))
+        .map_err(io::Error::other)
+}
+
+/// A new path in `SCREENSHOT_DIR` for a screenshot of `rom`, e.g.
+/// `screenshots/pong-20240131-235959.png`, creating the directory if needed.
+pub fn next_path(rom: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(SCREENSHOT_DIR)?;
+
+    let stem = rom.file_stem().unwrap_or_default().to_string_lossy();
+    let base = format!("{stem}-{}", timestamp(SystemTime::now()));
+    // Take care not to overwrite a screenshot taken in the same second.
+    let path = (0..)
+        .map(|n| match n {
+            0 => format!("{base}.png"),
+            n => format!("{base}-{n}.png")
+        })
+        .map(|name| Path::new(SCREENSHOT_DIR).join(name))
+        .find(|path| !path.exists())
+        .unwrap_or_default();
+
+    Ok(path)
+}
+
+/// Format `time` in UTC as `YYYYMMDD-HHMMSS`.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!("{year:04}{month:02}{day:02}-{:02}{:02}{:02}", rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// The Gregorian date `days` after 1970-01-01, using Howard Hinnant's
+/// `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::Colors;
+    use std::time::Duration;
+
+    #[test]
+    fn test_screenshot() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_709_251_199)), "20240229-235959");
+
+        let mut screen = Screen::new();
+        screen.flip(0, 0);
+        let path = std::env::temp_dir().join(format!("chip8-screenshot-{}.png", std::process::id()));
+        save(&screen, None, 2, &path).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (128, 64));
+        assert_eq!(&data[..12], &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0xff]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_image() {
+        let mut screen = Screen::new();
+        screen.flip(1, 0);
+        let palette = Palette { on: Rgb(1, 2, 3), off: Rgb(4, 5, 6) };
+        let image = RgbaImage::of(&screen, Some(palette), 1);
+        assert_eq!((image.width, image.height, image.data.len()), (64, 32, 64 * 32 * 4));
+        assert_eq!(&image.data[..8], &[4, 5, 6, 0xff, 1, 2, 3, 0xff]);
+
+        // A CHIP-8X display is drawn in its own colors, whatever the palette.
+        screen.set_colors(Some(Colors::new()));
+        let image = RgbaImage::of(&screen, Some(palette), 1);
+        let Rgb(r, g, b) = Colors::new().foreground(1, 0);
+        assert_eq!(&image.data[4..8], &[r, g, b, 0xff]);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{
+    address::Address, cheat::Target, cpu::{Cpu, CpuError}, memory::{Memory, SegmentationFault}, register::VRegister
+};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::{path::Path, sync::{Arc, Mutex, MutexGuard}};
+
+/// How many operations a single callback may run before it's stopped, so a
+/// script stuck in a loop doesn't hang the emulator.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Debug)]
+pub struct ScriptError(pub String);
+
+impl From<ScriptError> for CpuError {
+    fn from(e: ScriptError) -> Self {
+        Self::ScriptError(e.0)
+    }
+}
+
+/// A Rhai script that reads and changes the machine as it runs, for HUDs,
+/// bots, and cheats beyond freezing a value.
+///
+/// The script defines `on_frame` to be called at the end of every frame,
+/// `on_instruction` to be called after every instruction, or both. Inside
+/// them `this` is the machine:
+///
+/// ```rhai
+/// fn on_frame() {
+///     // Never run out of lives.
+///     if this.peek(0x2f0) < 2 { this.poke(0x2f0, 3); }
+///     // Hold 5 every other second.
+///     this.vars.frames = (this.vars.frames ?? 0) + 1;
+///     if this.vars.frames % 120 < 60 { this.press(5) } else { this.release(5) }
+/// }
+/// ```
+///
+/// `v(n)`, `set_v(n, value)`, `peek(addr)`, and `poke(addr, value)` read and
+/// write the registers and memory, `i`, `dt`, and `st` can be read and set,
+/// and `pc` read. `key(k)`, `press(k)`, and `release(k)` work the keypad.
+/// `vars` is a map kept between calls. `print` goes to the log.
+pub struct UserScript {
+    engine: Engine,
+    ast: AST,
+    /// `this.vars`, kept between calls.
+    vars: Map
+}
+
+impl UserScript {
+    pub fn load(path: &Path) -> Result<Self, ScriptError> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| ScriptError(format!("Failed to read {}: {e}", path.display())))?;
+        Self::compile(&source).map_err(|ScriptError(e)| ScriptError(format!("{}: {e}", path.display())))
+    }
+
+    /// Compile `source` and run its top level once.
+    pub fn compile(source: &str) -> Result<Self, ScriptError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|s| tracing::info!(target: "script", "{s}"));
+        engine.on_debug(|s, _, pos| tracing::debug!(target: "script", "{pos}: {s}"));
+        register(&mut engine);
+
+        let ast = engine.compile(source).map_err(|e| ScriptError(e.to_string()))?;
+        engine.run_ast(&ast).map_err(|e| ScriptError(e.to_string()))?;
+        Ok(Self { engine, ast, vars: Map::new() })
+    }
+
+    /// Whether the script defines a callback `name` taking no arguments.
+    pub fn defines(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name && f.params.is_empty())
+    }
+
+    /// Call the script's `name` with `this` bound to `cpu`, then make the
+    /// changes it made to the machine.
+    pub fn call(&mut self, name: &str, cpu: &mut Cpu) -> Result<(), ScriptError> {
+        let mut this = Dynamic::from(Machine::of(cpu, std::mem::take(&mut self.vars)));
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut this);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, name, ());
+
+        let machine = this.cast::<Machine>();
+        cpu.memory = std::mem::replace(&mut *lock(&machine.memory), Box::new(Lent));
+        let applied = machine.apply(cpu);
+        self.vars = machine.vars;
+        if let Err(e) = result {
+            return Err(ScriptError(format!("{name}: {e}")));
+        }
+        applied.map_err(|e| ScriptError(format!("{name}: {e}")))
+    }
+
+    /// Call the script's `on_frame` and `on_instruction` from `cpu`'s hooks,
+    /// whichever it defines.
+    pub fn attach(self, cpu: &mut Cpu) {
+        let (frame, instruction) = (self.defines("on_frame"), self.defines("on_instruction"));
+        let script = Arc::new(Mutex::new(self));
+        if frame {
+            let script = script.clone();
+            cpu.on_frame(move |cpu| Ok(lock(&script).call("on_frame", cpu)?));
+        }
+        if instruction {
+            cpu.on_instruction(move |cpu| Ok(lock(&script).call("on_instruction", cpu)?));
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// What a script works on: a copy of the registers and keys, and the
+/// machine's memory itself, lent for the call. Changes are made to the real
+/// machine afterwards.
+#[derive(Clone)]
+struct Machine {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    dt: u8,
+    st: u8,
+    keys: [bool; 16],
+    memory: Arc<Mutex<Box<dyn Memory + Send>>>,
+    len: usize,
+    /// The addresses the script poked and the values, in order, read back
+    /// by `peek` until they're made.
+    written: Vec<(usize, u8)>,
+    vars: Map
+}
+
+impl Machine {
+    /// The machine for a call, taking `cpu`'s memory until it's given back.
+    fn of(cpu: &mut Cpu, vars: Map) -> Self {
+        let (dt, st) = cpu.timers();
+        Self {
+            v: *cpu.registers(),
+            i: cpu.i().0,
+            pc: cpu.pc().0,
+            dt,
+            st,
+            keys: core::array::from_fn(|key| cpu.key_pressed(key as u8)),
+            len: cpu.memory.len(),
+            memory: Arc::new(Mutex::new(std::mem::replace(&mut cpu.memory, Box::new(Lent)))),
+            written: Vec::new(),
+            vars
+        }
+    }
+
+    fn peek(&self, addr: usize) -> Fallible<u8> {
+        match self.written.iter().rev().find(|&&(a, _)| a == addr) {
+            Some(&(_, value)) => Ok(value),
+            None => lock(&self.memory).get_byte(Address(addr as u16))
+                .map_err(|_| format!("address {addr:#x} can't be read").into())
+        }
+    }
+
+    fn apply(&self, cpu: &mut Cpu) -> Result<(), CpuError> {
+        for (n, &value) in self.v.iter().enumerate() {
+            if cpu.registers()[n] != value {
+                cpu.set_v(VRegister::try_from(n as u8)?, value);
+            }
+        }
+        cpu.set_i(Address(self.i));
+        cpu.set_dt(self.dt);
+        cpu.set_st(self.st);
+        for (key, &pressed) in self.keys.iter().enumerate() {
+            match pressed {
+                true if !cpu.key_pressed(key as u8) => cpu.press_key(key as u8),
+                false if cpu.key_pressed(key as u8) => cpu.release_key(key as u8),
+                _ => ()
+            }
+        }
+        for &(addr, value) in &self.written {
+            cpu.poke(Target::Memory(Address(addr as u16)), value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Stands in for the machine's memory while a script has it.
+struct Lent;
+
+impl Memory for Lent {
+    fn len(&self) -> usize {
+        0
+    }
+
+    fn get_byte(&self, address: Address) -> Result<u8, SegmentationFault> {
+        Err(SegmentationFault(address))
+    }
+
+    fn set_byte(&mut self, address: Address, _: u8) -> Result<u8, SegmentationFault> {
+        Err(SegmentationFault(address))
+    }
+}
+
+type Fallible<T> = Result<T, Box<EvalAltResult>>;
+
+fn register(engine: &mut Engine) {
+    fn reg(n: i64) -> Fallible<usize> {
+        usize::try_from(n).ok().filter(|&n| n < 16).ok_or_else(|| format!("no register V{n}").into())
+    }
+    fn addr(machine: &Machine, addr: i64) -> Fallible<usize> {
+        usize::try_from(addr).ok().filter(|&a| a < machine.len)
+            .ok_or_else(|| format!("address {addr:#x} is past the end of memory").into())
+    }
+    fn key(k: i64) -> Fallible<usize> {
+        usize::try_from(k).ok().filter(|&k| k < 16).ok_or_else(|| format!("no key {k:#x}").into())
+    }
+
+    engine.register_type_with_name::<Machine>("Machine")
+        .register_fn("v", |m: &mut Machine, n: i64| -> Fallible<i64> { Ok(m.v[reg(n)?].into()) })
+        .register_fn("set_v", |m: &mut Machine, n: i64, value: i64| -> Fallible<()> {
+            m.v[reg(n)?] = value as u8;
+            Ok(())
+        })
+        .register_fn("peek", |m: &mut Machine, a: i64| -> Fallible<i64> { Ok(m.peek(addr(m, a)?)?.into()) })
+        .register_fn("poke", |m: &mut Machine, a: i64, value: i64| -> Fallible<()> {
+            let a = addr(m, a)?;
+            m.written.push((a, value as u8));
+            Ok(())
+        })
+        .register_fn("key", |m: &mut Machine, k: i64| -> Fallible<bool> { Ok(m.keys[key(k)?]) })
+        .register_fn("press", |m: &mut Machine, k: i64| -> Fallible<()> {
+            m.keys[key(k)?] = true;
+            Ok(())
+        })
+        .register_fn("release", |m: &mut Machine, k: i64| -> Fallible<()> {
+            m.keys[key(k)?] = false;
+            Ok(())
+        })
+        .register_get_set("i", |m: &mut Machine| m.i as i64, |m: &mut Machine, i: i64| m.i = i as u16)
+        .register_get_set("dt", |m: &mut Machine| m.dt as i64, |m: &mut Machine, dt: i64| m.dt = dt as u8)
+        .register_get_set("st", |m: &mut Machine| m.st as i64, |m: &mut Machine, st: i64| m.st = st as u8)
+        .register_get("pc", |m: &mut Machine| m.pc as i64)
+        .register_get_set("vars", |m: &mut Machine| m.vars.clone(), |m: &mut Machine, vars: Map| m.vars = vars);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let script = UserScript::compile(r#"
+            fn on_frame() {
+                this.vars.frames = (this.vars.frames ?? 0) + 1;
+                if this.peek(0x300) < 2 { this.poke(0x300, 3); }
+                if this.vars.frames == 2 { this.press(0xA); this.i = 0x1234; }
+                if this.peek(0x300) == 3 { this.set_v(6, 1); }
+            }
+            fn on_instruction() {
+                this.set_v(5, this.v(5) + 1);
+            }
+        "#).unwrap();
+        assert!(script.defines("on_frame") && !script.defines("on_draw"));
+
+        // Keep storing 1 at 0x300.
+        let mut cpu = Cpu::with_program(&[0xA300, 0x6001, 0xF055, 0x1202]).unwrap();
+        cpu.set_ips(60 * 4);
+        script.attach(&mut cpu);
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.memory.get_byte(Address(0x300)).unwrap(), 3);
+        assert_eq!(cpu.registers()[5], 4);
+        assert_eq!(cpu.registers()[6], 1);
+        assert!(!cpu.key_pressed(0xA));
+        cpu.run_frame().unwrap();
+        assert!(cpu.key_pressed(0xA));
+        assert_eq!(cpu.i(), Address(0x1234));
+
+        let mut cpu = Cpu::with_program(&[0x7001, 0x1200]).unwrap();
+        UserScript::compile("fn on_frame() { this.set_v(16, 1) }").unwrap().attach(&mut cpu);
+        assert_eq!(cpu.run_frame().unwrap_err().kind(), crate::policy::ErrorKind::ScriptError);
+        assert!(UserScript::compile("fn on_frame( {").is_err());
+    }
+}
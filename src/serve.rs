@@ -0,0 +1,165 @@
+use std::{
+    io::{self, Read, Write}, net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver}, thread, time::Duration
+};
+use tungstenite::{Message, WebSocket};
+
+/// The page served to browsers, which connects back to stream the display
+/// and send key presses.
+const VIEWER: &str = include_str!("viewer.html");
+/// How long a browser may take over its request or the handshake before
+/// it's dropped, so one that stalls doesn't keep the others out.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Streams the display to browsers over WebSocket, and takes their key
+/// presses, so the emulator can be played from another machine.
+///
+/// A plain HTTP request gets the viewer page; a WebSocket connection is sent
+/// every frame as the 256 bytes of `Screen::rows`, big-endian, and may send
+/// `[key, pressed]` pairs back.
+pub struct Server {
+    addr: SocketAddr,
+    /// Connections that finished the handshake on the accept thread.
+    joined: Receiver<WebSocket<TcpStream>>,
+    clients: Vec<WebSocket<TcpStream>>,
+    /// The last frame broadcast, for clients that join between frames.
+    last: Option<Vec<u8>>
+}
+
+impl Server {
+    /// Listen on `addr`, e.g. `0.0.0.0:8080`.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let (tx, joined) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                match accept(stream) {
+                    Ok(Some(socket)) => if tx.send(socket).is_err() { break },
+                    Ok(None) => (),
+                    Err(e) => tracing::warn!("Failed to accept a viewer: {e}")
+                }
+            }
+        });
+
+        Ok(Self { addr, joined, clients: Vec::new(), last: None })
+    }
+
+    /// The address listened on, which has the port chosen if the one bound
+    /// was 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Number of browsers connected.
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Send `rows` to every connected browser, dropping any that hung up.
+    pub fn broadcast(&mut self, rows: &[u64]) {
+        let frame: Vec<u8> = rows.iter().flat_map(|row| row.to_be_bytes()).collect();
+        self.clients.retain_mut(|client| send(client, frame.clone()));
+        self.last = Some(frame);
+    }
+
+    /// Take in newly connected browsers and return the key presses and
+    /// releases sent since the last call, as `(key, pressed)`.
+    pub fn poll(&mut self) -> Vec<(u8, bool)> {
+        while let Ok(mut client) = self.joined.try_recv() {
+            if self.last.clone().is_none_or(|frame| send(&mut client, frame)) {
+                self.clients.push(client);
+            }
+        }
+
+        let mut keys = Vec::new();
+        self.clients.retain_mut(|client| loop {
+            match client.read() {
+                Ok(Message::Binary(data)) => if let [key, pressed] = data[..] {
+                    keys.push((key & 0xF, pressed != 0));
+                },
+                Ok(Message::Close(_)) => return false,
+                Ok(_) => (),
+                Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(_) => return false
+            }
+        });
+        keys
+    }
+}
+
+/// Complete the WebSocket handshake on `stream`, or answer a plain request
+/// with the viewer page and return `None`.
+fn accept(mut stream: TcpStream) -> io::Result<Option<WebSocket<TcpStream>>> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    // Look at the request without consuming it, so the handshake can read it.
+    let mut request = [0; 2048];
+    let len = stream.peek(&mut request)?;
+    let request = String::from_utf8_lossy(&request[..len]).to_ascii_lowercase();
+
+    if !request.contains("upgrade: websocket") {
+        // Read the request so closing the socket doesn't reset the response.
+        let _ = stream.read(&mut [0; 2048])?;
+        write!(
+            stream, "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{VIEWER}",
+            VIEWER.len()
+        )?;
+        return Ok(None);
+    }
+
+    let socket = tungstenite::accept(stream).map_err(io::Error::other)?;
+    // The frontend polls for key presses between frames.
+    socket.get_ref().set_nonblocking(true)?;
+    socket.get_ref().set_nodelay(true)?;
+    Ok(Some(socket))
+}
+
+/// Send `frame` to `client`, returning false if it has gone. A slow client's
+/// frames queue up in the socket rather than holding up the emulator.
+fn send(client: &mut WebSocket<TcpStream>, frame: Vec<u8>) -> bool {
+    match client.send(Message::Binary(frame)) {
+        Ok(()) => true,
+        Err(tungstenite::Error::Io(e)) => e.kind() == io::ErrorKind::WouldBlock,
+        Err(_) => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::NROWS;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_server() {
+        let mut server = Server::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+
+        let mut page = String::new();
+        let mut http = TcpStream::connect(addr).unwrap();
+        http.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        http.read_to_string(&mut page).unwrap();
+        assert!(page.starts_with("HTTP/1.1 200 OK") && page.contains("new WebSocket"));
+
+        let (mut browser, _) = tungstenite::connect(format!("ws://{addr}/")).unwrap();
+        browser.send(Message::Binary(vec![0xA, 1])).unwrap();
+
+        let mut rows = [0; NROWS];
+        rows[0] = 1 << 63;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut keys = Vec::new();
+        while keys.is_empty() && Instant::now() < deadline {
+            keys = server.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(keys, vec![(0xA, true)]);
+        assert_eq!(server.clients(), 1);
+
+        server.broadcast(&rows);
+        match browser.read().unwrap() {
+            Message::Binary(frame) => assert_eq!((frame.len(), frame[0]), (256, 0x80)),
+            message => panic!("unexpected {message:?}")
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::address::Address;
+use serde::{Deserialize, Serialize};
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+/// The first bytes of a save state file.
+#[cfg(feature = "std")]
+const MAGIC: &[u8; 4] = b"C8SS";
+/// The save state format, bumped whenever `Snapshot` changes shape.
+#[cfg(feature = "std")]
+pub const VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub struct InvalidSnapshot(pub String);
+
+/// A complete, owned copy of the machine state that can be written to disk and
+/// restored later.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub v: [u8; 16],
+    pub i: Address,
+    pub pc: Address,
+    pub sp: usize,
+    pub stack: [Address; 16],
+    pub dt: u8,
+    pub st: u8,
+    pub memory: Vec<u8>,
+    /// One bitmask per display row, with the leftmost pixel in the MSB.
+    pub display: Vec<u64>
+}
+
+#[cfg(feature = "std")]
+impl Snapshot {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, InvalidSnapshot> {
+        bincode::serialize(self)
+            .map_err(|e| InvalidSnapshot(format!("Failed to serialize state: {e}")))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidSnapshot> {
+        bincode::deserialize(bytes)
+            .map_err(|e| InvalidSnapshot(format!("Failed to deserialize state: {e}")))
+    }
+
+    /// The snapshot as a save state file for the ROM with `rom::hash` `rom`:
+    /// `MAGIC`, `VERSION`, the length of the hash and the hash, then the
+    /// snapshot deflated.
+    pub fn to_state(&self, rom: &str) -> Result<Vec<u8>, InvalidSnapshot> {
+        let mut state = MAGIC.to_vec();
+        state.extend(VERSION.to_le_bytes());
+        state.push(rom.len() as u8);
+        state.extend(rom.as_bytes());
+        state.extend(miniz_oxide::deflate::compress_to_vec(&self.to_bytes()?, 6));
+        Ok(state)
+    }
+
+    /// Read a save state file written by `to_state`, refusing one from an
+    /// older version or for a ROM other than the one with hash `rom`.
+    pub fn from_state(state: &[u8], rom: &str) -> Result<Self, InvalidSnapshot> {
+        let Some(rest) = state.strip_prefix(MAGIC) else {
+            return Err(InvalidSnapshot("Invalid save state: not a save state file".into()));
+        };
+        let (version, rest) = rest.split_at_checked(2)
+            .ok_or_else(|| InvalidSnapshot("Invalid save state: truncated".into()))?;
+        let version = u16::from_le_bytes([version[0], version[1]]);
+        if version != VERSION {
+            return Err(InvalidSnapshot(format!(
+                "Invalid save state: written by version {version} of the format, this is version {VERSION}"
+            )));
+        }
+        let (&len, rest) = rest.split_first()
+            .ok_or_else(|| InvalidSnapshot("Invalid save state: truncated".into()))?;
+        let (hash, payload) = rest.split_at_checked(len as usize)
+            .ok_or_else(|| InvalidSnapshot("Invalid save state: truncated".into()))?;
+        let hash = String::from_utf8_lossy(hash);
+        if hash != rom {
+            return Err(InvalidSnapshot(format!(
+                "Invalid save state: it's for the ROM with hash {hash}, not this one ({rom})"
+            )));
+        }
+
+        let bytes = miniz_oxide::inflate::decompress_to_vec(payload)
+            .map_err(|e| InvalidSnapshot(format!("Invalid save state: doesn't inflate: {e}")))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn save(&self, path: &Path, rom: &str) -> Result<(), InvalidSnapshot> {
+        fs::write(path, self.to_state(rom)?)
+            .map_err(|e| InvalidSnapshot(format!("Failed to write {}: {e}", path.display())))
+    }
+
+    pub fn load(path: &Path, rom: &str) -> Result<Self, InvalidSnapshot> {
+        let bytes = fs::read(path)
+            .map_err(|e| InvalidSnapshot(format!("Failed to read {}: {e}", path.display())))?;
+        Self::from_state(&bytes, rom)
+            .map_err(|e| InvalidSnapshot(format!("{}: {}", path.display(), e.0)))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{cpu::Cpu, rom};
+
+    #[test]
+    fn test_state() {
+        let program = [0x60, 0x2A, 0x12, 0x02];
+        let mut cpu = Cpu::from_program(program.to_vec()).unwrap();
+        cpu.step().unwrap();
+        let snapshot = cpu.snapshot();
+
+        let state = snapshot.to_state(&rom::hash(&program)).unwrap();
+        // Mostly zeroed memory deflates to almost nothing.
+        assert!(state.len() < snapshot.memory.len() / 4);
+        assert_eq!(Snapshot::from_state(&state, &rom::hash(&program)).unwrap(), snapshot);
+
+        let error = Snapshot::from_state(&state, &rom::hash(&[0x00, 0xE0])).unwrap_err();
+        assert!(error.0.contains(&rom::hash(&program)));
+        let mut newer = state.clone();
+        newer[4] += 1;
+        assert!(Snapshot::from_state(&newer, &rom::hash(&program)).unwrap_err().0.contains("version 2"));
+        assert!(Snapshot::from_state(b"junk", &rom::hash(&program)).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt::Write;
+
+/// The most rows a `DRW` can draw.
+pub const MAX_HEIGHT: usize = 15;
+
+/// An 8 pixel wide sprite being drawn on a grid, a row per byte, with the
+/// most significant bit on the left like `DRW` draws it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteEditor {
+    rows: Vec<u8>,
+    /// The pixel under the cursor, as `(x, y)`.
+    cursor: (usize, usize)
+}
+
+impl SpriteEditor {
+    /// An empty sprite `height` rows tall, between 1 and `MAX_HEIGHT`.
+    pub fn new(height: usize) -> Self {
+        Self::from_bytes(&vec![0; height])
+    }
+
+    /// Edit the sprite `bytes` draws, cut or padded to between 1 and
+    /// `MAX_HEIGHT` rows.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut rows = bytes[..bytes.len().min(MAX_HEIGHT)].to_vec();
+        if rows.is_empty() {
+            rows.push(0);
+        }
+        Self { rows, cursor: (0, 0) }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.rows
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows.get(y).is_some_and(|row| x < 8 && row & (0x80 >> x) != 0)
+    }
+
+    /// Move the cursor by `dx` columns and `dy` rows, stopping at the edges.
+    pub fn move_cursor(&mut self, dx: isize, dy: isize) {
+        let (x, y) = self.cursor;
+        self.cursor = (
+            x.saturating_add_signed(dx).min(7),
+            y.saturating_add_signed(dy).min(self.rows.len() - 1)
+        );
+    }
+
+    /// Flip the pixel under the cursor.
+    pub fn toggle(&mut self) {
+        let (x, y) = self.cursor;
+        self.rows[y] ^= 0x80 >> x;
+    }
+
+    /// Add an empty row at the bottom, up to `MAX_HEIGHT`.
+    pub fn add_row(&mut self) {
+        if self.rows.len() < MAX_HEIGHT {
+            self.rows.push(0);
+        }
+    }
+
+    /// Remove the bottom row, keeping at least one.
+    pub fn remove_row(&mut self) {
+        if self.rows.len() > 1 {
+            self.rows.pop();
+            self.move_cursor(0, 0);
+        }
+    }
+
+    /// The rows as space-separated hex bytes, e.g. `f0 90 90 90 f0`.
+    pub fn hex(&self) -> String {
+        self.rows.iter().map(|row| format!("{row:02x}")).collect::<Vec<_>>().join(" ")
+    }
+
+    /// The rows as assembler `DB` directives, a line each, with the row
+    /// drawn in a comment.
+    pub fn db(&self) -> String {
+        self.rows.iter().fold(String::new(), |mut out, row| {
+            let pixels: String = (0..8).map(|x| if row & (0x80 >> x) != 0 { '#' } else { '.' }).collect();
+            let _ = writeln!(out, "DB 0x{row:02x} ; {pixels}");
+            out
+        })
+    }
+
+    /// Clear the terminal and draw the grid, with the cursor in reverse
+    /// video, each row's byte beside it, and the keys underneath.
+    pub fn render_into(&self, out: &mut String) {
+        out.clear();
+        out.push_str("\x1B[H\x1B[2J┌────────────────┐\r\n");
+        for (y, row) in self.rows.iter().enumerate() {
+            out.push('│');
+            for x in 0..8 {
+                let pixel = if self.pixel(x, y) { "██" } else { "  " };
+                if (x, y) == self.cursor {
+                    let _ = write!(out, "\x1B[7m{pixel}\x1B[27m");
+                } else {
+                    out.push_str(pixel);
+                }
+            }
+            let _ = write!(out, "│ {row:02x}\r\n");
+        }
+        let _ = write!(
+            out, "└────────────────┘\r\n8x{} | arrows move, space draws, +/- rows, Enter exports, Esc discards",
+            self.rows.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprite_editor() {
+        let mut editor = SpriteEditor::new(2);
+        editor.toggle();
+        editor.move_cursor(9, 1);
+        editor.toggle();
+        assert_eq!(editor.cursor(), (7, 1));
+        assert_eq!(editor.bytes(), &[0x80, 0x01]);
+        assert_eq!(editor.hex(), "80 01");
+        assert_eq!(editor.db(), "DB 0x80 ; #.......\nDB 0x01 ; .......#\n");
+
+        // The cursor follows the bottom row up when it's removed.
+        editor.remove_row();
+        assert_eq!((editor.cursor(), editor.bytes()), ((7, 0), &[0x80][..]));
+        editor.remove_row();
+        assert_eq!(editor.bytes().len(), 1);
+        (0..20).for_each(|_| editor.add_row());
+        assert_eq!(editor.bytes().len(), MAX_HEIGHT);
+
+        let editor = SpriteEditor::from_bytes(&[0xF0, 0x90]);
+        let mut out = String::new();
+        editor.render_into(&mut out);
+        assert!(out.contains("│\x1B[7m██\x1B[27m██████        │ f0\r\n│██    ██        │ 90\r\n"));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{emulator::Stats, pacing::Intervals};
+use crossterm::{terminal::SetTitle, Command};
+use std::{fmt::Write, path::Path, time::{Duration, Instant}};
+
+/// How long a message from `notify` stays on the status bar.
+const MESSAGE_DURATION: Duration = Duration::from_secs(3);
+
+/// The line under the display and the terminal title, showing which ROM is
+/// running and how fast, so it's easy to check it runs at the intended speed.
+#[derive(Debug, Default)]
+pub struct StatusBar {
+    rom: String,
+    stats: Stats,
+    /// A confirmation or error to show, and when it was posted.
+    message: Option<(String, Instant)>
+}
+
+impl StatusBar {
+    pub fn new(rom: &Path) -> Self {
+        let mut status = Self::default();
+        status.set_rom(rom);
+        status
+    }
+
+    pub fn set_rom(&mut self, rom: &Path) {
+        self.rom = rom.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    }
+
+    pub fn set_stats(&mut self, stats: Stats) {
+        self.stats = stats;
+    }
+
+    /// Show `message` on the status bar for the next few seconds.
+    pub fn notify(&mut self, message: String) {
+        self.message = Some((message, Instant::now()));
+    }
+
+    /// What the machine is doing, if anything unusual.
+    fn state(&self) -> Option<&'static str> {
+        match self.stats {
+            Stats { paused: true, .. } => Some("paused"),
+            Stats { turbo: true, .. } => Some("turbo"),
+            _ => None
+        }
+    }
+
+    /// Append the status line, below a display `rows` tall rendered by
+    /// `Screen::render_into`, and an escape setting the terminal title.
+    pub fn render_into(&self, out: &mut String, rows: usize) {
+        let Stats { fps, ips, speed, .. } = self.stats;
+        let _ = write!(out, "\x1B[{};1H\x1B[2K{} | {ips} IPS | {fps} FPS", rows + 3, self.rom);
+        if speed != 1.0 {
+            let _ = write!(out, " | x{speed}");
+        }
+        if let Some(state) = self.state() {
+            let _ = write!(out, " | {state}");
+        }
+        if let Some((message, _)) = self.message.as_ref().filter(|(_, at)| at.elapsed() < MESSAGE_DURATION) {
+            let _ = write!(out, " | {message}");
+        }
+
+        let title = match self.state() {
+            Some(state) => format!("chip8 - {} [{state}]", self.rom),
+            None => format!("chip8 - {}", self.rom)
+        };
+        let _ = SetTitle(title).write_ansi(out);
+    }
+
+    /// Append the time between timer ticks and their rate over the last 
+    /// second, and a graph of the time between the frames `presented`, on the
+    /// lines below the status line.
+    pub fn render_pacing_into(&self, out: &mut String, rows: usize, presented: &Intervals) {
+        let _ = write!(out, "\x1B[{};1H\x1B[2Kticks  {} | timers {}Hz", rows + 4, self.stats.ticks, self.stats.timer_hz);
+        let _ = write!(out, "\x1B[{};1H\x1B[2Kframes ", rows + 5);
+        presented.graph_into(out);
+        let max = presented.stats().max.as_secs_f64() * 1000.0;
+        let _ = write!(out, " max {max:.1}ms");
+    }
+
+    /// Append a counter of rendered frames and executed instructions per 
+    /// second, drawn over the top left corner of the display.
+    pub fn render_counter_into(&self, out: &mut String) {
+        let Stats { rendered, ips, .. } = self.stats;
+        let _ = write!(out, "\x1B[2;2H {rendered:>2} FPS {ips:>5} IPS ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::NROWS;
+
+    #[test]
+    fn test_status_bar() {
+        let mut status = StatusBar::new(Path::new("roms/pong.ch8"));
+        status.set_stats(Stats { fps: 60, ips: 350, rendered: 30, paused: true, turbo: false, speed: 0.5, ..Stats::default() });
+
+        let mut out = String::new();
+        status.render_into(&mut out, NROWS);
+        assert!(out.contains("pong.ch8 | 350 IPS | 60 FPS | x0.5 | paused"));
+        assert!(out.contains("chip8 - pong.ch8 [paused]"));
+
+        status.notify("saved".to_string());
+        out.clear();
+        status.render_into(&mut out, NROWS);
+        assert!(out.contains("| paused | saved"));
+
+        out.clear();
+        status.render_counter_into(&mut out);
+        assert!(out.ends_with(" 30 FPS   350 IPS "));
+    }
+}
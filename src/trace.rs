@@ -0,0 +1,199 @@
+//! Instruction traces in a stable, machine-readable format, for comparing a
+//! run against another emulator's or an earlier build's.
+//!
+//! A trace is JSON Lines: one object per executed instruction, e.g.
+//!
+//! ```text
+//! {"cycle":4,"pc":520,"opcode":24842,"changed":{"v1":10},"i":554,"dt":0,"st":0}
+//! ```
+//!
+//! - `cycle`: instructions executed before this one, counting from 0.
+//! - `pc`: the instruction's address.
+//! - `opcode`: the instruction's first 16-bit word.
+//! - `changed`: the V registers the instruction changed (`v0` to `vf`) and
+//!   their new values. Registers written with the value they held are left
+//!   out.
+//! - `i`, `dt`, `st`: the index register and the delay and sound timers after
+//!   the instruction.
+//!
+//! All numbers are decimal. Other emulators only need to write these fields
+//! for `chip8 trace-diff` to compare against them; any others are ignored.
+
+use crate::{address::Address, cpu::Cpu, isa::Instruction, observer::Observer};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap, fmt::{self, Display, Formatter}, fs::File,
+    io::{self, BufRead, BufWriter, Write}, path::Path
+};
+
+#[derive(Debug)]
+pub struct InvalidTrace(pub String);
+
+/// One executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    #[serde(default)]
+    pub changed: BTreeMap<String, u8>,
+    pub i: u16,
+    pub dt: u8,
+    pub st: u8
+}
+
+impl TraceRecord {
+    pub fn parse(line: &str) -> Result<Self, InvalidTrace> {
+        serde_json::from_str(line).map_err(|e| InvalidTrace(e.to_string()))
+    }
+
+    /// The names of the fields that differ from `other`.
+    pub fn differences(&self, other: &TraceRecord) -> Vec<&'static str> {
+        let fields = [
+            ("cycle", self.cycle != other.cycle),
+            ("pc", self.pc != other.pc),
+            ("opcode", self.opcode != other.opcode),
+            ("changed", self.changed != other.changed),
+            ("i", self.i != other.i),
+            ("dt", self.dt != other.dt),
+            ("st", self.st != other.st)
+        ];
+        fields.into_iter().filter(|&(_, differs)| differs).map(|(name, _)| name).collect()
+    }
+}
+
+impl Display for TraceRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Serializing plain integers and strings can't fail.
+        write!(f, "{}", serde_json::to_string(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// Writes a `TraceRecord` for every instruction the `Cpu` it's attached to
+/// executes.
+pub struct TraceWriter {
+    out: BufWriter<File>,
+    cycle: u64,
+    /// The instruction being executed, and the registers before it.
+    pending: Option<(u16, u16, [u8; 16])>
+}
+
+impl TraceWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self { out: BufWriter::new(File::create(path)?), cycle: 0, pending: None })
+    }
+}
+
+impl Observer for TraceWriter {
+    fn before_execute(&mut self, cpu: &Cpu, _instruction: &Instruction) {
+        // The PC has already moved past the instruction by the time it executes.
+        let pc = cpu.pc().0.wrapping_sub(2);
+        let opcode = cpu.memory.get_short(Address(pc)).unwrap_or_default();
+        self.pending = Some((pc, opcode, *cpu.registers()));
+    }
+
+    fn after_execute(&mut self, cpu: &Cpu, _instruction: &Instruction) {
+        let Some((pc, opcode, before)) = self.pending.take() else { return };
+        let changed = before.iter().zip(cpu.registers()).enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(reg, (_, &new))| (format!("v{reg:x}"), new))
+            .collect();
+        let (dt, st) = cpu.timers();
+
+        let record = TraceRecord { cycle: self.cycle, pc, opcode, changed, i: cpu.i().0, dt, st };
+        self.cycle += 1;
+        if let Err(e) = writeln!(self.out, "{record}") {
+            tracing::warn!("Failed to write the trace: {e}");
+        }
+    }
+}
+
+impl Drop for TraceWriter {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+/// Where two traces first disagree. A missing record means that trace ended
+/// first.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The 1-based line both traces were read up to.
+    pub line: usize,
+    pub left: Option<TraceRecord>,
+    pub right: Option<TraceRecord>
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => {
+                writeln!(f, "traces diverge at line {} ({})", self.line, left.differences(right).join(", "))?;
+                writeln!(f, "< {left}")?;
+                write!(f, "> {right}")
+            },
+            (Some(left), None) => write!(f, "the second trace ends at line {}, before\n< {left}", self.line),
+            (None, Some(right)) => write!(f, "the first trace ends at line {}, before\n> {right}", self.line),
+            (None, None) => write!(f, "the traces match")
+        }
+    }
+}
+
+/// Read two traces in step and return where they first differ, or `None` if
+/// they're the same.
+pub fn diff(left: impl BufRead, right: impl BufRead) -> Result<Option<Divergence>, InvalidTrace> {
+    let parse = |line: Option<io::Result<String>>, n: usize, side: &str| {
+        line.map(|line| {
+            let line = line.map_err(|e| InvalidTrace(format!("Failed to read the {side} trace: {e}")))?;
+            TraceRecord::parse(&line).map_err(|InvalidTrace(e)| InvalidTrace(format!("{side} trace, line {n}: {e}")))
+        }).transpose()
+    };
+
+    let (mut left, mut right) = (left.lines(), right.lines());
+    for line in 1.. {
+        let l = parse(left.next(), line, "first")?;
+        let r = parse(right.next(), line, "second")?;
+        if l.is_none() && r.is_none() {
+            break;
+        }
+        if l != r {
+            return Ok(Some(Divergence { line, left: l, right: r }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        let path = std::env::temp_dir().join(format!("chip8-trace-{}", std::process::id()));
+        // `LD V1, 0x0A`, `LD I, 0x22A`, `LD V1, 0x0A`.
+        let mut cpu = Cpu::with_program(&[0x610A, 0xA22A, 0x610A]).unwrap();
+        cpu.attach(TraceWriter::create(&path).unwrap());
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        drop(cpu);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], r#"{"cycle":0,"pc":512,"opcode":24842,"changed":{"v1":10},"i":0,"dt":0,"st":0}"#);
+        assert_eq!(TraceRecord::parse(lines[2]).unwrap().changed, BTreeMap::new());
+        let extra = lines[0].replace(r#""cycle":0,"#, r#""cycle":0,"mnemonic":"LD V1, 0x0A","ticks":[1,2],"#);
+        assert_eq!(TraceRecord::parse(&extra).unwrap(), TraceRecord::parse(lines[0]).unwrap());
+
+        assert_eq!(diff(text.as_bytes(), text.as_bytes()).unwrap(), None);
+        let other = text.replace(r#""i":554"#, r#""i":555"#);
+        let divergence = diff(text.as_bytes(), other.as_bytes()).unwrap().unwrap();
+        assert_eq!(divergence.line, 2);
+        assert!(divergence.to_string().starts_with("traces diverge at line 2 (i)"));
+        let shorter = lines[..2].join("\n");
+        assert_eq!(diff(text.as_bytes(), shorter.as_bytes()).unwrap().unwrap().right, None);
+        assert!(diff(text.as_bytes(), "{}".as_bytes()).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
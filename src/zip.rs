@@ -0,0 +1,160 @@
+use std::io;
+
+/// Signature of the end of central directory record.
+const END_SIGNATURE: u32 = 0x0605_4b50;
+/// Signature of each entry in the central directory.
+const ENTRY_SIGNATURE: u32 = 0x0201_4b50;
+/// Signature of the header in front of each file's data.
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+/// Size of the end of central directory record, without its comment.
+const END_SIZE: usize = 22;
+
+/// A file in a zip archive, as listed in its central directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    /// 0 if stored, 8 if deflated.
+    method: u16,
+    encrypted: bool,
+    crc: u32,
+    compressed_size: usize,
+    size: usize,
+    /// Where the entry's local header is in the archive.
+    offset: usize
+}
+
+/// The files in `archive`, in the order the central directory lists them.
+/// ZIP64 archives, which are only needed past 4GB, aren't supported.
+pub fn entries(archive: &[u8]) -> io::Result<Vec<Entry>> {
+    // The end record is last, followed by a comment of up to 64K.
+    let end = (0..=archive.len().saturating_sub(END_SIZE)).rev()
+        .take(u16::MAX as usize + 1)
+        .find(|&at| u32_at(archive, at) == Some(END_SIGNATURE))
+        .ok_or_else(|| invalid("no end of central directory; not a zip archive?"))?;
+    let count = u16_at(archive, end + 10).ok_or_else(|| invalid("truncated"))?;
+    let mut at = u32_at(archive, end + 16).ok_or_else(|| invalid("truncated"))? as usize;
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if u32_at(archive, at) != Some(ENTRY_SIGNATURE) {
+            return Err(invalid("bad central directory entry"));
+        }
+        let field = |offset: usize| u16_at(archive, at + offset).map(usize::from).ok_or_else(|| invalid("truncated"));
+        let long = |offset: usize| u32_at(archive, at + offset).ok_or_else(|| invalid("truncated"));
+        let (name_len, extra_len, comment_len) = (field(28)?, field(30)?, field(32)?);
+        let name = archive.get(at + 46..at + 46 + name_len).ok_or_else(|| invalid("truncated"))?;
+
+        entries.push(Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: field(10)? as u16,
+            encrypted: field(8)? & 1 != 0,
+            crc: long(16)?,
+            compressed_size: long(20)? as usize,
+            size: long(24)? as usize,
+            offset: long(42)? as usize
+        });
+        at += 46 + name_len + extra_len + comment_len;
+    }
+
+    Ok(entries)
+}
+
+/// The contents of `entry` in `archive`, checked against its CRC.
+pub fn extract(archive: &[u8], entry: &Entry) -> io::Result<Vec<u8>> {
+    let at = entry.offset;
+    if u32_at(archive, at) != Some(LOCAL_SIGNATURE) {
+        return Err(invalid("bad local header"));
+    }
+    if entry.encrypted {
+        return Err(invalid(&format!("{} is encrypted", entry.name)));
+    }
+    let name_len = u16_at(archive, at + 26).ok_or_else(|| invalid("truncated"))? as usize;
+    let extra_len = u16_at(archive, at + 28).ok_or_else(|| invalid("truncated"))? as usize;
+    let start = at + 30 + name_len + extra_len;
+    let data = archive.get(start..start + entry.compressed_size).ok_or_else(|| invalid("truncated"))?;
+
+    let contents = match entry.method {
+        0 => data.to_vec(),
+        8 => miniz_oxide::inflate::decompress_to_vec_with_limit(data, entry.size)
+            .map_err(|e| invalid(&format!("{} doesn't inflate: {e}", entry.name)))?,
+        method => return Err(invalid(&format!("{} is compressed with unsupported method {method}", entry.name)))
+    };
+    if contents.len() != entry.size || crc32fast::hash(&contents) != entry.crc {
+        return Err(invalid(&format!("{} is corrupt", entry.name)));
+    }
+
+    Ok(contents)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid zip archive: {reason}"))
+}
+
+/// Build an archive of `files`, deflated, for tests of the reader.
+#[cfg(test)]
+pub(crate) fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let (mut out, mut directory) = (Vec::new(), Vec::new());
+    for &(name, contents) in files {
+        let data = miniz_oxide::deflate::compress_to_vec(contents, 6);
+        let header = |signature: u32| {
+            let mut header = signature.to_le_bytes().to_vec();
+            if signature == ENTRY_SIGNATURE {
+                header.extend(20u16.to_le_bytes());
+            }
+            header.extend([20, 0, 0, 0, 8, 0, 0, 0, 0, 0]);
+            header.extend(crc32fast::hash(contents).to_le_bytes());
+            header.extend((data.len() as u32).to_le_bytes());
+            header.extend((contents.len() as u32).to_le_bytes());
+            header.extend((name.len() as u16).to_le_bytes());
+            header.extend([0, 0]);
+            header
+        };
+
+        let mut entry = header(ENTRY_SIGNATURE);
+        entry.extend([0; 10]);
+        entry.extend((out.len() as u32).to_le_bytes());
+        entry.extend(name.as_bytes());
+        directory.extend(entry);
+
+        out.extend(header(LOCAL_SIGNATURE));
+        out.extend(name.as_bytes());
+        out.extend(data);
+    }
+
+    let start = out.len() as u32;
+    out.extend(&directory);
+    out.extend(END_SIGNATURE.to_le_bytes());
+    out.extend([0; 4]);
+    out.extend((files.len() as u16).to_le_bytes());
+    out.extend((files.len() as u16).to_le_bytes());
+    out.extend((directory.len() as u32).to_le_bytes());
+    out.extend(start.to_le_bytes());
+    out.extend([0, 0]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zip() {
+        let bytes = archive(&[("README", b"hello"), ("games/pong.ch8", &[0x00, 0xE0, 0x12, 0x02])]);
+        let entries = entries(&bytes).unwrap();
+        assert_eq!(entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["README", "games/pong.ch8"]);
+        assert_eq!(extract(&bytes, &entries[1]).unwrap(), [0x00, 0xE0, 0x12, 0x02]);
+
+        let mut corrupt = entries[0].clone();
+        corrupt.crc ^= 1;
+        assert!(extract(&bytes, &corrupt).is_err());
+        assert!(super::entries(b"not a zip").is_err());
+    }
+}
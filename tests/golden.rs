@@ -0,0 +1,25 @@
+//! Snapshots of what the ROMs in `rom/` draw, to catch regressions in
+//! drawing and quirks. Run with `CHIP8_UPDATE_GOLDEN=1` to re-record them
+//! after an intended change.
+
+use chip8::{cpu::Cpu, golden};
+use std::path::Path;
+
+fn check(rom: &str, cycles: u64) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let program = std::fs::read(root.join("rom").join(rom)).unwrap();
+    let screen = golden::run(Cpu::from_program(program).unwrap(), cycles).unwrap();
+
+    let name = Path::new(rom).with_extension("txt");
+    golden::assert_matches(&root.join("tests/golden").join(name), &screen);
+}
+
+#[test]
+fn ibm_logo() {
+    check("ibm.ch8", 1_000);
+}
+
+#[test]
+fn tank() {
+    check("tank.ch8", 20_000);
+}